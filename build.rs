@@ -7,6 +7,7 @@ use std::env;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
+use std::process::Command;
 
 fn main() {
     if cfg!(target_os = "windows") {
@@ -15,6 +16,8 @@ fn main() {
         set_win_icon();
     }
 
+    set_git_commit();
+
     let path = Path::new(&env::var("OUT_DIR").unwrap()).join("key_map_table.rs");
     let mut file = BufWriter::new(File::create(&path).unwrap());
 
@@ -48,11 +51,55 @@ fn main() {
         .entry("Enter", "\"CR\"")
         .entry("Tab", "\"Tab\"")
         .entry("ISO_Left_Tab", "\"Tab\"")
+        // numpad
+        .entry("KP_0", "\"k0\"")
+        .entry("KP_1", "\"k1\"")
+        .entry("KP_2", "\"k2\"")
+        .entry("KP_3", "\"k3\"")
+        .entry("KP_4", "\"k4\"")
+        .entry("KP_5", "\"k5\"")
+        .entry("KP_6", "\"k6\"")
+        .entry("KP_7", "\"k7\"")
+        .entry("KP_8", "\"k8\"")
+        .entry("KP_9", "\"k9\"")
+        .entry("KP_Enter", "\"kEnter\"")
+        .entry("KP_Add", "\"kPlus\"")
+        .entry("KP_Subtract", "\"kMinus\"")
+        .entry("KP_Multiply", "\"kMultiply\"")
+        .entry("KP_Divide", "\"kDivide\"")
+        .entry("KP_Decimal", "\"kPoint\"")
+        .entry("KP_Separator", "\"kComma\"")
+        .entry("KP_Equal", "\"kEqual\"")
+        .entry("KP_Home", "\"kHome\"")
+        .entry("KP_End", "\"kEnd\"")
+        .entry("KP_Begin", "\"kOrigin\"")
+        .entry("KP_Up", "\"kUp\"")
+        .entry("KP_Down", "\"kDown\"")
+        .entry("KP_Left", "\"kLeft\"")
+        .entry("KP_Right", "\"kRight\"")
+        .entry("KP_Page_Up", "\"kPageUp\"")
+        .entry("KP_Page_Down", "\"kPageDown\"")
+        .entry("KP_Insert", "\"kInsert\"")
+        .entry("KP_Delete", "\"kDel\"")
         .build(&mut file)
         .unwrap();
     write!(&mut file, ";\n").unwrap();
 }
 
+/// Commit is shown in about dialog, not set when building outside of git repository
+fn set_git_commit() {
+    let output = Command::new("git")
+        .args(&["rev-parse", "--short", "HEAD"])
+        .output();
+
+    if let Ok(output) = output {
+        if output.status.success() {
+            let commit = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=NVIM_GTK_GIT_COMMIT={}", commit.trim());
+        }
+    }
+}
+
 #[cfg(windows)]
 fn set_win_icon() {
     let mut res = winres::WindowsResource::new();
@@ -3,22 +3,25 @@ if !has('nvim') || exists('g:GuiLoaded')
 	finish
 endif
 let g:GuiLoaded = 1
+" channel of GUI, updated by GUI once it is attached
+let g:GtkGuiChannel = get(g:, 'GtkGuiChannel', 1)
 
 if exists('g:GuiInternalClipboard')
 	let s:LastRegType = 'v'
 	function! provider#clipboard#Call(method, args) abort
 		if a:method == 'get'
-			return [rpcrequest(1, 'Gui', 'Clipboard', 'Get', a:args[0]), s:LastRegType]
+			return [rpcrequest(g:GtkGuiChannel, 'Gui', 'Clipboard', 'Get', a:args[0]), s:LastRegType]
 		elseif a:method == 'set'
 			let s:LastRegType = a:args[1]
-			call rpcnotify(1, 'Gui', 'Clipboard', 'Set', a:args[2], join(a:args[0], ''))
+			call rpcnotify(g:GtkGuiChannel, 'Gui', 'Clipboard', 'Set', a:args[2], join(a:args[0], '
+'))
 		endif
 	endfunction
 endif
 
 " Set GUI font
 function! GuiFont(fname, ...) abort
-	call rpcnotify(1, 'Gui', 'Font', s:NvimQtToPangoFont(a:fname))
+	call rpcnotify(g:GtkGuiChannel, 'Gui', 'Font', s:NvimQtToPangoFont(a:fname))
 endfunction
 
 " Some subset of parse command from neovim-qt
@@ -43,11 +46,7 @@ endf
 " The GuiFont command. For compatibility there is also Guifont
 function s:GuiFontCommand(fname, bang) abort
 	if a:fname ==# ''
-		if exists('g:GuiFont')
-			echo g:GuiFont
-		else
-			echo 'No GuiFont is set'
-		endif
+		echo rpcrequest(g:GtkGuiChannel, 'Gui', 'Font')
 	else
 		call GuiFont(a:fname, a:bang ==# '!')
 	endif
@@ -55,10 +54,54 @@ endfunction
 command! -nargs=1 -bang Guifont call s:GuiFontCommand("<args>", "<bang>")
 command! -nargs=1 -bang GuiFont call s:GuiFontCommand("<args>", "<bang>")
 
-command! -nargs=? GuiFontFeatures call rpcnotify(1, 'Gui', 'FontFeatures', <q-args>)
-command! -nargs=1 GuiLinespace call rpcnotify(1, 'Gui', 'Linespace', <q-args>)
+command! GuiName echo 'nvim-gtk'
+command! GuiVersion echo rpcrequest(g:GtkGuiChannel, 'Gui', 'Version')
+
+" Resolve color name like in :highlight to '#rrggbb'
+function! GuiColor(name) abort
+	return rpcrequest(g:GtkGuiChannel, 'Gui', 'Color', a:name)
+endfunction
+
+command! -nargs=? GuiFontFeatures call rpcnotify(g:GtkGuiChannel, 'Gui', 'FontFeatures', <q-args>)
+command! -nargs=? GtkGuiFontBold call rpcnotify(g:GtkGuiChannel, 'Gui', 'FontVariant', 'bold', <q-args>)
+command! -nargs=? GtkGuiFontItalic call rpcnotify(g:GtkGuiChannel, 'Gui', 'FontVariant', 'italic', <q-args>)
+command! -nargs=? GtkGuiFontBoldItalic call rpcnotify(g:GtkGuiChannel, 'Gui', 'FontVariant', 'bold_italic', <q-args>)
+function! s:GtkGuiAntiAliasingModes(arglead, cmdline, cursorpos)
+  return filter(['none', 'gray', 'subpixel'], 'v:val =~# "^" . a:arglead')
+endfunction
+command! -nargs=1 -complete=customlist,s:GtkGuiAntiAliasingModes GtkGuiAntiAliasing call rpcnotify(g:GtkGuiChannel, 'Gui', 'AntiAliasing', <q-args>)
+command! -nargs=1 GuiLinespace call rpcnotify(g:GtkGuiChannel, 'Gui', 'Linespace', <q-args>)
+command! -nargs=1 -complete=file GtkGuiScreenshot call rpcnotify(g:GtkGuiChannel, 'Gui', 'Screenshot', fnamemodify(<q-args>, ':p'))
+command! GuiDumpEvents call rpcnotify(g:GtkGuiChannel, 'Gui', 'DumpEvents')
+command! GuiServerName echo get(g:, 'gui_server_address', v:servername)
 
-command! NGToggleSidebar call rpcnotify(1, 'Gui', 'Command', 'ToggleSidebar')
-command! -nargs=+ NGTransparency call rpcnotify(1, 'Gui', 'Command', 'Transparency', <f-args>)
-command! -nargs=1 NGPreferDarkTheme call rpcnotify(1, 'Gui', 'Command', 'PreferDarkTheme', <q-args>)
+command! NGToggleSidebar call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'ToggleSidebar')
+command! -nargs=+ NGTransparency call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'Transparency', <f-args>)
+command! -nargs=1 GtkGuiOpacityBG call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'OpacityBG', <q-args>)
+command! -nargs=1 GtkGuiWindowBorder call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'WindowBorder', <q-args>)
+command! -nargs=1 NGPreferDarkTheme call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'PreferDarkTheme', <q-args>)
+command! -nargs=1 GtkGuiDarkTheme call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'PreferDarkTheme', <q-args>)
+command! -nargs=1 GtkGuiTabline call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'Tabline', <q-args>)
+command! -nargs=1 GtkGuiFullscreenHideTabline call rpcnotify(g:GtkGuiChannel, 'Gui', 'Command', 'FullscreenHideTabline', <q-args>)
+command! -nargs=1 GtkGuiCursorAnimation call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'CursorAnimation', <args>)
+function s:GtkGuiCursorShapes(arglead, cmdline, cursorpos)
+  return filter(['auto', 'block', 'line', 'underline'], 'v:val =~# "^" . a:arglead')
+endfunction
+command! -nargs=1 -complete=customlist,s:GtkGuiCursorShapes GtkGuiCursorShape call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'CursorShape', <q-args>)
+command! -nargs=1 GtkGuiPadding call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'Padding', <q-args>)
+command! -nargs=1 GtkGuiScrollLines call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'ScrollLines', <args>)
+command! -nargs=1 GtkGuiDimInactive call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'DimInactive', <args>)
+command! -nargs=1 GtkGuiMessageTimeout call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'MessageTimeout', <args>)
+command! -nargs=1 GtkGuiHorizontalScroll call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'HorizontalScroll', <args>)
+command! -nargs=1 GtkGuiNowrapIndicator call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'NowrapIndicator', <args>)
+command! -nargs=? GtkGuiImModule call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'ImModule', <q-args>)
+
+" g:neovim_gtk_scroll_lines is alternative to GtkGuiScrollLines, default is 1
+function! s:GuiScrollLinesChanged(dict, key, change) abort
+	call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'ScrollLines', get(a:change, 'new', 1))
+endfunction
+call dictwatcheradd(g:, 'neovim_gtk_scroll_lines', function('s:GuiScrollLinesChanged'))
+if exists('g:neovim_gtk_scroll_lines')
+	call rpcnotify(g:GtkGuiChannel, 'Gui', 'Option', 'ScrollLines', g:neovim_gtk_scroll_lines)
+endif
 
@@ -0,0 +1,499 @@
+use std::cell::{Cell, RefCell};
+#[cfg(unix)]
+use std::env;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::rc::Rc;
+
+use atk_sys;
+#[cfg(unix)]
+use gio;
+#[cfg(unix)]
+use gio::SettingsExt;
+use glib::translate::*;
+use glib_ffi;
+use gobject_ffi;
+use gtk;
+use gtk_sys;
+
+use ui_model::UiModel;
+
+#[cfg(unix)]
+const A11Y_SCHEMA: &str = "org.gnome.desktop.a11y.applications";
+const TEXT_STATE_KEY: &[u8] = b"nvim-gtk-grid-text\0";
+
+lazy_static! {
+    static ref GRID_ACCESSIBLE_TYPE: glib_ffi::GType = unsafe { register_grid_accessible_type() };
+    static ref GRID_TYPE: glib_ffi::GType = unsafe { register_grid_type() };
+}
+
+/// Drawing area with accessible that implements `AtkText` over visible grid
+pub fn grid_drawing_area() -> gtk::DrawingArea {
+    unsafe {
+        let object = gobject_ffi::g_object_new(*GRID_TYPE, ptr::null());
+        from_glib_none(object as *mut gtk_sys::GtkDrawingArea)
+    }
+}
+
+/// Report grid text and cursor to assistive technologies
+///
+/// Visible grid rows are exposed as text joined by new lines,
+/// changed rows emit `text-changed` and cursor emits `text-caret-moved`.
+/// Nothing is computed while screen reader is disabled.
+pub struct GridAccessible {
+    drawing_area: gtk::DrawingArea,
+    enabled: Rc<Cell<bool>>,
+    // keeps changed signal connected
+    #[cfg(unix)]
+    _a11y_settings: Option<gio::Settings>,
+
+    text: Rc<RefCell<TextState>>,
+}
+
+impl GridAccessible {
+    pub fn new(drawing_area: &gtk::DrawingArea) -> Self {
+        let text = Rc::new(RefCell::new(TextState::default()));
+        let object = accessible(drawing_area);
+        unsafe {
+            atk_sys::atk_object_set_role(object, atk_sys::ATK_ROLE_TERMINAL);
+            gobject_ffi::g_object_set_data_full(
+                object as *mut gobject_ffi::GObject,
+                TEXT_STATE_KEY.as_ptr() as *const c_char,
+                Box::into_raw(Box::new(text.clone())) as glib_ffi::gpointer,
+                Some(free_text_state),
+            );
+        }
+
+        GridAccessible::with_settings(drawing_area.clone(), text)
+    }
+
+    #[cfg(unix)]
+    fn with_settings(drawing_area: gtk::DrawingArea, text: Rc<RefCell<TextState>>) -> Self {
+        let enabled = Rc::new(Cell::new(false));
+
+        // Settings::new aborts on missing schema, it is not installed outside GNOME
+        let has_schema = gio::SettingsSchemaSource::get_default()
+            .and_then(|source| source.lookup(A11Y_SCHEMA, true))
+            .is_some();
+        let a11y_settings = if has_schema {
+            let a11y_settings = gio::Settings::new(A11Y_SCHEMA);
+            enabled.set(a11y_settings.get_boolean("screen-reader-enabled"));
+
+            let enabled_ref = enabled.clone();
+            a11y_settings.connect_changed(move |settings, key| {
+                if key == "screen-reader-enabled" {
+                    enabled_ref.set(settings.get_boolean(key));
+                }
+            });
+            Some(a11y_settings)
+        } else {
+            // screen readers outside GNOME (Orca on KDE or XFCE) have no such key
+            let allowed = a11y_allowed_by_env(
+                env::var("GTK_A11Y").ok().as_ref().map(String::as_str),
+                env::var("NO_AT_BRIDGE").ok().as_ref().map(String::as_str),
+            );
+            info!(
+                "Schema {} is not installed, accessibility is {}",
+                A11Y_SCHEMA,
+                if allowed { "enabled" } else { "disabled by environment" }
+            );
+            enabled.set(allowed);
+            None
+        };
+
+        GridAccessible {
+            drawing_area,
+            enabled,
+            _a11y_settings: a11y_settings,
+            text,
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn with_settings(drawing_area: gtk::DrawingArea, text: Rc<RefCell<TextState>>) -> Self {
+        GridAccessible {
+            drawing_area,
+            enabled: Rc::new(Cell::new(false)),
+            text,
+        }
+    }
+
+    /// Notify about changed rows and cursor position
+    pub fn update(&mut self, model: &UiModel) {
+        if !self.enabled.get() {
+            *self.text.borrow_mut() = TextState::default();
+            return;
+        }
+
+        let lines: Vec<String> = (0..model.rows).map(|row| model.rows_text(row, row)).collect();
+
+        let (row, col) = model.get_cursor();
+        let caret = if row < lines.len() {
+            let line_len = lines[row].chars().count();
+            line_start(&lines, row) + caret_in_line(model, row, col).min(line_len)
+        } else {
+            0
+        };
+
+        let (changes, caret_moved) = {
+            let mut state = self.text.borrow_mut();
+            let changes = line_changes(&state.lines, &lines);
+            let caret_moved = state.caret != caret;
+            state.lines = lines;
+            state.caret = caret;
+            (changes, caret_moved)
+        };
+
+        let object = accessible(&self.drawing_area);
+        for (offset, deleted, inserted) in changes {
+            if deleted > 0 {
+                emit_text_changed(object, b"text-changed::delete\0", offset, deleted);
+            }
+            if inserted > 0 {
+                emit_text_changed(object, b"text-changed::insert\0", offset, inserted);
+            }
+        }
+        if caret_moved {
+            emit_caret_moved(object, caret);
+        }
+    }
+}
+
+#[derive(Default)]
+struct TextState {
+    lines: Vec<String>,
+    /// Char offset of cursor in whole text
+    caret: usize,
+}
+
+impl TextState {
+    fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TextUnit {
+    Char,
+    Word,
+    Line,
+}
+
+/// Offset of row first char in text of rows joined by new lines
+fn line_start(lines: &[String], row: usize) -> usize {
+    lines[..row].iter().map(|line| line.chars().count() + 1).sum()
+}
+
+/// Char index of cell, second halves of double width chars have no char
+fn caret_in_line(model: &UiModel, row: usize, col: usize) -> usize {
+    model.model()[row]
+        .line
+        .iter()
+        .take(col)
+        .filter(|cell| !(cell.ch.is_empty() && cell.attrs.double_width))
+        .count()
+}
+
+/// Changed ranges as `(offset, deleted, inserted)` char counts,
+/// grid resize replaces whole text
+fn line_changes(old: &[String], new: &[String]) -> Vec<(usize, usize, usize)> {
+    let text_len = |lines: &[String]| line_start(lines, lines.len()).saturating_sub(1);
+
+    if old.len() != new.len() {
+        return vec![(0, text_len(old), text_len(new))];
+    }
+
+    let mut changes = vec![];
+    let mut offset = 0;
+    for (old_line, new_line) in old.iter().zip(new) {
+        let new_len = new_line.chars().count();
+        if old_line != new_line {
+            changes.push((offset, old_line.chars().count(), new_len));
+        }
+        offset += new_len + 1;
+    }
+    changes
+}
+
+/// Char range of unit around offset, line range excludes new line
+fn text_range(text: &str, offset: usize, unit: TextUnit) -> (usize, usize) {
+    if unit == TextUnit::Char {
+        let len = text.chars().count();
+        return (offset.min(len), (offset + 1).min(len));
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let offset = offset.min(chars.len());
+
+    let is_separator = |ch: &char| match unit {
+        TextUnit::Word => ch.is_whitespace(),
+        _ => *ch == '\n',
+    };
+
+    let start = chars[..offset]
+        .iter()
+        .rposition(&is_separator)
+        .map_or(0, |pos| pos + 1);
+    let end = chars[offset..]
+        .iter()
+        .position(&is_separator)
+        .map_or(chars.len(), |pos| offset + pos);
+    (start, end)
+}
+
+fn substring(text: &str, start: usize, end: usize) -> String {
+    text.chars().skip(start).take(end.saturating_sub(start)).collect()
+}
+
+fn accessible(drawing_area: &gtk::DrawingArea) -> *mut atk_sys::AtkObject {
+    unsafe { gtk_sys::gtk_widget_get_accessible(drawing_area.to_glib_none().0 as *mut _) }
+}
+
+fn emit_text_changed(object: *mut atk_sys::AtkObject, signal: &[u8], offset: usize, len: usize) {
+    unsafe {
+        gobject_ffi::g_signal_emit_by_name(
+            object as glib_ffi::gpointer,
+            signal.as_ptr() as *const c_char,
+            offset as c_int,
+            len as c_int,
+        );
+    }
+}
+
+fn emit_caret_moved(object: *mut atk_sys::AtkObject, caret: usize) {
+    unsafe {
+        gobject_ffi::g_signal_emit_by_name(
+            object as glib_ffi::gpointer,
+            b"text-caret-moved\0".as_ptr() as *const c_char,
+            caret as c_int,
+        );
+    }
+}
+
+unsafe fn register_type(
+    parent: glib_ffi::GType,
+    name: &[u8],
+    class_init: gobject_ffi::GClassInitFunc,
+) -> glib_ffi::GType {
+    let mut query: gobject_ffi::GTypeQuery = mem::zeroed();
+    gobject_ffi::g_type_query(parent, &mut query);
+
+    gobject_ffi::g_type_register_static_simple(
+        parent,
+        name.as_ptr() as *const c_char,
+        query.class_size,
+        class_init,
+        query.instance_size,
+        None,
+        0,
+    )
+}
+
+unsafe fn register_grid_accessible_type() -> glib_ffi::GType {
+    let accessible_type = register_type(
+        gtk_sys::gtk_widget_accessible_get_type(),
+        b"NvimGtkGridAccessible\0",
+        None,
+    );
+
+    let text_info = gobject_ffi::GInterfaceInfo {
+        interface_init: Some(text_iface_init),
+        interface_finalize: None,
+        interface_data: ptr::null_mut(),
+    };
+    gobject_ffi::g_type_add_interface_static(
+        accessible_type,
+        atk_sys::atk_text_get_type(),
+        &text_info,
+    );
+
+    accessible_type
+}
+
+unsafe fn register_grid_type() -> glib_ffi::GType {
+    register_type(
+        gtk_sys::gtk_drawing_area_get_type(),
+        b"NvimGtkGrid\0",
+        Some(grid_class_init),
+    )
+}
+
+unsafe extern "C" fn grid_class_init(class: glib_ffi::gpointer, _: glib_ffi::gpointer) {
+    gtk_sys::gtk_widget_class_set_accessible_type(
+        class as *mut gtk_sys::GtkWidgetClass,
+        *GRID_ACCESSIBLE_TYPE,
+    );
+}
+
+unsafe extern "C" fn text_iface_init(iface: glib_ffi::gpointer, _: glib_ffi::gpointer) {
+    let iface = &mut *(iface as *mut atk_sys::AtkTextIface);
+    iface.get_text = Some(get_text);
+    iface.get_character_count = Some(get_character_count);
+    iface.get_caret_offset = Some(get_caret_offset);
+    iface.get_character_at_offset = Some(get_character_at_offset);
+    iface.get_text_at_offset = Some(get_text_at_offset);
+    iface.get_string_at_offset = Some(get_string_at_offset);
+}
+
+unsafe extern "C" fn free_text_state(data: glib_ffi::gpointer) {
+    drop(Box::from_raw(data as *mut Rc<RefCell<TextState>>));
+}
+
+unsafe fn with_text_state<T, F>(text: *mut atk_sys::AtkText, default: T, f: F) -> T
+where
+    F: FnOnce(&TextState) -> T,
+{
+    let data = gobject_ffi::g_object_get_data(
+        text as *mut gobject_ffi::GObject,
+        TEXT_STATE_KEY.as_ptr() as *const c_char,
+    ) as *const Rc<RefCell<TextState>>;
+
+    match data.as_ref() {
+        Some(state) => f(&*state.borrow()),
+        None => default,
+    }
+}
+
+unsafe fn range_text(
+    text: *mut atk_sys::AtkText,
+    offset: c_int,
+    unit: TextUnit,
+    start_offset: *mut c_int,
+    end_offset: *mut c_int,
+) -> *mut c_char {
+    let (start, end, range) = with_text_state(text, (0, 0, String::new()), |state| {
+        let text = state.text();
+        let (start, end) = text_range(&text, offset.max(0) as usize, unit);
+        (start, end, substring(&text, start, end))
+    });
+
+    if !start_offset.is_null() {
+        *start_offset = start as c_int;
+    }
+    if !end_offset.is_null() {
+        *end_offset = end as c_int;
+    }
+    range.to_glib_full()
+}
+
+unsafe extern "C" fn get_text(
+    text: *mut atk_sys::AtkText,
+    start_offset: c_int,
+    end_offset: c_int,
+) -> *mut c_char {
+    with_text_state(text, String::new(), |state| {
+        let text = state.text();
+        let end = if end_offset < 0 {
+            text.chars().count()
+        } else {
+            end_offset as usize
+        };
+        substring(&text, start_offset.max(0) as usize, end)
+    }).to_glib_full()
+}
+
+unsafe extern "C" fn get_character_count(text: *mut atk_sys::AtkText) -> c_int {
+    with_text_state(text, 0, |state| {
+        line_start(&state.lines, state.lines.len()).saturating_sub(1) as c_int
+    })
+}
+
+unsafe extern "C" fn get_caret_offset(text: *mut atk_sys::AtkText) -> c_int {
+    with_text_state(text, 0, |state| state.caret as c_int)
+}
+
+unsafe extern "C" fn get_character_at_offset(
+    text: *mut atk_sys::AtkText,
+    offset: c_int,
+) -> glib_ffi::gunichar {
+    with_text_state(text, 0, |state| {
+        state
+            .text()
+            .chars()
+            .nth(offset.max(0) as usize)
+            .map_or(0, |ch| ch as glib_ffi::gunichar)
+    })
+}
+
+unsafe extern "C" fn get_text_at_offset(
+    text: *mut atk_sys::AtkText,
+    offset: c_int,
+    boundary: atk_sys::AtkTextBoundary,
+    start_offset: *mut c_int,
+    end_offset: *mut c_int,
+) -> *mut c_char {
+    let unit = match boundary {
+        atk_sys::ATK_TEXT_BOUNDARY_CHAR => TextUnit::Char,
+        atk_sys::ATK_TEXT_BOUNDARY_WORD_START | atk_sys::ATK_TEXT_BOUNDARY_WORD_END => {
+            TextUnit::Word
+        }
+        _ => TextUnit::Line,
+    };
+    range_text(text, offset, unit, start_offset, end_offset)
+}
+
+unsafe extern "C" fn get_string_at_offset(
+    text: *mut atk_sys::AtkText,
+    offset: c_int,
+    granularity: atk_sys::AtkTextGranularity,
+    start_offset: *mut c_int,
+    end_offset: *mut c_int,
+) -> *mut c_char {
+    let unit = match granularity {
+        atk_sys::ATK_TEXT_GRANULARITY_CHAR => TextUnit::Char,
+        atk_sys::ATK_TEXT_GRANULARITY_WORD => TextUnit::Word,
+        _ => TextUnit::Line,
+    };
+    range_text(text, offset, unit, start_offset, end_offset)
+}
+
+/// Accessibility is on unless GTK or AT-SPI bridge is turned off by environment
+#[cfg(unix)]
+fn a11y_allowed_by_env(gtk_a11y: Option<&str>, no_at_bridge: Option<&str>) -> bool {
+    gtk_a11y != Some("none") && no_at_bridge != Some("1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&line| line.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_line_changes() {
+        let old = lines(&["abc", "de", ""]);
+        assert!(line_changes(&old, &old).is_empty());
+        assert_eq!(
+            vec![(4, 2, 3)],
+            line_changes(&old, &lines(&["abc", "dex", ""]))
+        );
+        // offsets of later rows follow new text
+        assert_eq!(
+            vec![(0, 3, 1), (5, 0, 1)],
+            line_changes(&old, &lines(&["a", "de", "f"]))
+        );
+        assert_eq!(vec![(0, 7, 1)], line_changes(&old, &lines(&["a"])));
+    }
+
+    #[test]
+    fn test_text_range() {
+        let text = "ab cd\nefg";
+        assert_eq!((1, 2), text_range(text, 1, TextUnit::Char));
+        assert_eq!((3, 5), text_range(text, 4, TextUnit::Word));
+        assert_eq!((0, 5), text_range(text, 2, TextUnit::Line));
+        assert_eq!((6, 9), text_range(text, 9, TextUnit::Line));
+        assert_eq!("efg", substring(text, 6, 9));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_a11y_allowed_by_env() {
+        assert!(a11y_allowed_by_env(None, None));
+        assert!(a11y_allowed_by_env(Some("atspi"), Some("0")));
+        assert!(!a11y_allowed_by_env(Some("none"), None));
+        assert!(!a11y_allowed_by_env(None, Some("1")));
+    }
+}
@@ -98,6 +98,7 @@ impl Level {
         let max_width_chars = (max_width as f64 / char_width) as u64;
 
         let mut model_layout = ModelLayout::new(max_width_chars);
+        model_layout.set_tabstop(render_state.tabstop);
         model_layout.layout(lines);
 
         let mut level = Level {
@@ -262,6 +263,7 @@ impl CmdLine {
     pub fn new(drawing: &gtk::DrawingArea, render_state: Rc<RefCell<shell::RenderState>>) -> Self {
         let popover = gtk::Popover::new(Some(drawing));
         popover.set_modal(false);
+        popover.get_style_context().unwrap().add_class("nvim-cmdline");
         popover.set_position(gtk::PositionType::Right);
 
         let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
@@ -341,6 +343,10 @@ impl CmdLine {
         (scroll, tree, css_provider, renderer, column)
     }
 
+    pub fn add_style_class(&self, class: &str) {
+        self.popover.get_style_context().unwrap().add_class(class);
+    }
+
     pub fn show_level(&mut self, ctx: &CmdLineContext) {
         let mut state = self.state.borrow_mut();
         if state.nvim.is_none() {
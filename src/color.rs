@@ -1,6 +1,7 @@
 use std;
 
 use gdk;
+use neovim_lib::Value;
 
 use ui_model::Cell;
 use theme::Theme;
@@ -31,6 +32,55 @@ impl Color {
         Color(r / 255.0, g / 255.0, b / 255.0)
     }
 
+    /// Mix with color below, blend is transparency from 0 (opaque) to 100
+    pub fn blend(&self, below: &Color, blend: u8) -> Color {
+        let alpha = f64::from(100 - blend.min(100)) / 100.0;
+        Color(
+            self.0 * alpha + below.0 * (1.0 - alpha),
+            self.1 * alpha + below.1 * (1.0 - alpha),
+            self.2 * alpha + below.2 * (1.0 - alpha),
+        )
+    }
+
+    /// Color from rgb value of redraw events, -1 means color is not set
+    pub fn from_rgb_value(value: i64) -> Option<Color> {
+        if value >= 0 {
+            Some(Color::from_indexed_color(value as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Color from cterm value of `default_colors_set`, 0 means not set
+    /// and other values are xterm 256 colors index + 1
+    pub fn from_cterm_value(value: i64) -> Option<Color> {
+        const ANSI: [u64; 16] = [
+            0x000000, 0x800000, 0x008000, 0x808000, 0x000080, 0x800080, 0x008080, 0xc0c0c0,
+            0x808080, 0xff0000, 0x00ff00, 0xffff00, 0x0000ff, 0xff00ff, 0x00ffff, 0xffffff,
+        ];
+        const CUBE_LEVELS: [u64; 6] = [0, 95, 135, 175, 215, 255];
+
+        if value <= 0 || value > 256 {
+            return None;
+        }
+
+        let idx = (value - 1) as u64;
+        let rgb = if idx < 16 {
+            ANSI[idx as usize]
+        } else if idx < 232 {
+            let idx = idx - 16;
+            let r = CUBE_LEVELS[(idx / 36) as usize];
+            let g = CUBE_LEVELS[(idx / 6 % 6) as usize];
+            let b = CUBE_LEVELS[(idx % 6) as usize];
+            (r << 16) | (g << 8) | b
+        } else {
+            let level = 8 + (idx - 232) * 10;
+            (level << 16) | (level << 8) | level
+        };
+
+        Some(Color::from_indexed_color(rgb))
+    }
+
     pub fn to_u16(&self) -> (u16, u16, u16) {
         (
             (std::u16::MAX as f64 * self.0) as u16,
@@ -49,6 +99,48 @@ impl Color {
     }
 }
 
+/// Default colors of `default_colors_set` event, None means built-in default
+#[derive(Clone, PartialEq, Debug)]
+pub struct DefaultColors {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub sp: Option<Color>,
+}
+
+impl DefaultColors {
+    /// cterm colors are used only if rgb color is not set
+    pub fn new(rgb_fg: i64, rgb_bg: i64, rgb_sp: i64, cterm_fg: i64, cterm_bg: i64) -> Self {
+        DefaultColors {
+            fg: Color::from_rgb_value(rgb_fg).or_else(|| Color::from_cterm_value(cterm_fg)),
+            bg: Color::from_rgb_value(rgb_bg).or_else(|| Color::from_cterm_value(cterm_bg)),
+            sp: Color::from_rgb_value(rgb_sp),
+        }
+    }
+}
+
+/// `#rrggbb` or color name from `nvim_get_color_map` result,
+/// names are case insensitive like in nvim
+pub fn resolve_color(color_map: &[(Value, Value)], name: &str) -> Option<Color> {
+    let name = name.trim();
+    if name.starts_with('#') {
+        if name.len() != 7 {
+            return None;
+        }
+        return u64::from_str_radix(&name[1..], 16)
+            .ok()
+            .map(Color::from_indexed_color);
+    }
+
+    color_map
+        .iter()
+        .find(|&&(ref key, _)| {
+            key.as_str()
+                .map_or(false, |key| key.eq_ignore_ascii_case(name))
+        })
+        .and_then(|&(_, ref rgb)| rgb.as_u64())
+        .map(Color::from_indexed_color)
+}
+
 pub struct ColorModel {
     pub bg_color: Color,
     pub fg_color: Color,
@@ -66,6 +158,38 @@ impl ColorModel {
         }
     }
 
+    /// Return true if color changed
+    pub fn set_default_fg(&mut self, fg: Option<Color>) -> bool {
+        let fg = fg.unwrap_or(COLOR_WHITE);
+        let changed = self.fg_color != fg;
+        self.fg_color = fg;
+        changed
+    }
+
+    /// Return true if color changed
+    pub fn set_default_bg(&mut self, bg: Option<Color>) -> bool {
+        let bg = bg.unwrap_or(COLOR_BLACK);
+        let changed = self.bg_color != bg;
+        self.bg_color = bg;
+        changed
+    }
+
+    /// Return true if color changed
+    pub fn set_default_sp(&mut self, sp: Option<Color>) -> bool {
+        let sp = sp.unwrap_or(COLOR_RED);
+        let changed = self.sp_color != sp;
+        self.sp_color = sp;
+        changed
+    }
+
+    /// Return true if any color changed
+    pub fn set_default_colors(&mut self, colors: DefaultColors) -> bool {
+        let fg_changed = self.set_default_fg(colors.fg);
+        let bg_changed = self.set_default_bg(colors.bg);
+        let sp_changed = self.set_default_sp(colors.sp);
+        fg_changed || bg_changed || sp_changed
+    }
+
     pub fn cell_fg<'a>(&'a self, cell: &'a Cell) -> Option<&'a Color> {
         if !cell.attrs.reverse {
             cell.attrs.foreground.as_ref()
@@ -90,6 +214,17 @@ impl ColorModel {
         }
     }
 
+    /// Cell background composited over default background for blended cells
+    pub fn blended_cell_bg(&self, cell: &Cell) -> Option<Color> {
+        self.cell_bg(cell).map(|bg| {
+            if cell.attrs.blend > 0 {
+                bg.blend(&self.bg_color, cell.attrs.blend)
+            } else {
+                bg.clone()
+            }
+        })
+    }
+
     #[inline]
     pub fn actual_cell_sp<'a>(&'a self, cell: &'a Cell) -> &'a Color {
         cell.attrs.special.as_ref().unwrap_or(&self.sp_color)
@@ -127,6 +262,14 @@ impl ColorModel {
             .unwrap_or_else(|| self.fg_color.clone())
     }
 
+    pub fn cursor_line_bg(&self) -> Option<Color> {
+        self.theme.cursor_line().bg.clone()
+    }
+
+    pub fn color_column_bg(&self) -> Option<Color> {
+        self.theme.color_column().bg.clone()
+    }
+
     pub fn cursor_bg(&self) -> Color {
         self.theme
             .cursor()
@@ -140,9 +283,85 @@ impl ColorModel {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_resolve_color() {
+        let map = vec![
+            ("Red".into(), 0xff0000.into()),
+            ("DarkSlateGray".into(), 0x2f4f4f.into()),
+        ];
+
+        assert_eq!(Some(COLOR_RED), resolve_color(&map, "red"));
+        assert_eq!(
+            Some(Color::from_indexed_color(0x2f4f4f)),
+            resolve_color(&map, "DarkSlateGray")
+        );
+        assert_eq!(
+            Some(Color::from_indexed_color(0x00ff00)),
+            resolve_color(&map, "#00ff00")
+        );
+        assert_eq!(None, resolve_color(&map, "#00ff"));
+        assert_eq!(None, resolve_color(&map, "NoSuchColor"));
+    }
+
     #[test]
     fn test_to_hex() {
         let col = Color(0.0, 1.0, 0.0);
         assert_eq!("#00FF00", &col.to_hex());
     }
+
+    #[test]
+    fn test_blend() {
+        assert_eq!(COLOR_RED, COLOR_RED.blend(&COLOR_WHITE, 0));
+        assert_eq!(COLOR_WHITE, COLOR_RED.blend(&COLOR_WHITE, 100));
+        assert_eq!(Color(1.0, 0.5, 0.5), COLOR_RED.blend(&COLOR_WHITE, 50));
+    }
+
+    #[test]
+    fn test_blended_cell_bg() {
+        let mut color_model = ColorModel::new();
+        color_model.bg_color = COLOR_BLACK;
+
+        let mut cell = Cell::new_empty();
+        assert_eq!(None, color_model.blended_cell_bg(&cell));
+
+        cell.attrs.background = Some(COLOR_WHITE);
+        cell.attrs.blend = 50;
+        assert_eq!(
+            Some(Color(0.5, 0.5, 0.5)),
+            color_model.blended_cell_bg(&cell)
+        );
+    }
+
+    #[test]
+    fn test_default_colors_rgb() {
+        let colors = DefaultColors::new(0xffffff, 0x000000, -1, 0, 0);
+        assert_eq!(Some(COLOR_WHITE), colors.fg);
+        assert_eq!(Some(COLOR_BLACK), colors.bg);
+        assert_eq!(None, colors.sp);
+    }
+
+    #[test]
+    fn test_default_colors_unset() {
+        let colors = DefaultColors::new(-1, -1, -1, 0, 0);
+        assert_eq!(None, colors.fg);
+        assert_eq!(None, colors.bg);
+
+        let mut color_model = ColorModel::new();
+        color_model.bg_color = COLOR_WHITE;
+        assert!(color_model.set_default_colors(colors));
+        assert_eq!(COLOR_BLACK, color_model.bg_color);
+        assert_eq!(COLOR_WHITE, color_model.fg_color);
+    }
+
+    #[test]
+    fn test_default_colors_cterm() {
+        // cterm values are index + 1
+        let colors = DefaultColors::new(-1, -1, -1, 10, 17);
+        assert_eq!(Some(Color(1.0, 0.0, 0.0)), colors.fg);
+        assert_eq!(Some(COLOR_BLACK), colors.bg);
+
+        let colors = DefaultColors::new(-1, 0x00ff00, -1, 256, 17);
+        assert_eq!(Some(Color(0.0, 1.0, 0.0)), colors.bg);
+        assert_eq!(Some(Color::from_indexed_color(0xeeeeee)), colors.fg);
+    }
 }
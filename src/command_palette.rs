@@ -0,0 +1,413 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use gdk;
+use glib;
+use gtk;
+use gtk::prelude::*;
+use pango;
+
+use neovim_lib::{NeovimApiAsync, Value};
+
+use nvim::{ErrorReport, NeovimClient};
+use shell::Shell;
+use ui::UiMutex;
+use value::ValueMapExt;
+
+const MAX_RESULTS: usize = 50;
+const MAX_HEIGHT: i32 = 400;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    /// Ex command without arguments, executed immediately
+    Command(String),
+    /// Keys fed to nvim, mapping LHS or command line with command name
+    Keys(String),
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    name: String,
+    definition: String,
+    action: Action,
+}
+
+struct State {
+    entries: Vec<Entry>,
+    /// Indexes of entries shown in list, in list order
+    shown: Vec<usize>,
+    /// Incremented on every open, late replies of previous open are dropped
+    generation: u64,
+    list: gtk::ListBox,
+    search: gtk::SearchEntry,
+}
+
+impl State {
+    fn loaded(&mut self, generation: u64, entries: Vec<Entry>) {
+        if generation != self.generation {
+            return;
+        }
+
+        self.entries.extend(entries);
+        self.filter();
+    }
+
+    fn filter(&mut self) {
+        let query = self.search.get_text().unwrap_or_default();
+        self.shown = filter_entries(&self.entries, &query);
+
+        for row in self.list.get_children() {
+            self.list.remove(&row);
+        }
+
+        for &idx in &self.shown {
+            self.list.insert(&entry_row(&self.entries[idx]), -1);
+        }
+        self.list.show_all();
+
+        if let Some(row) = self.list.get_row_at_index(0) {
+            self.list.select_row(&row);
+        }
+    }
+
+    fn action(&self, row: i32) -> Option<Action> {
+        self.shown
+            .get(row as usize)
+            .map(|&idx| self.entries[idx].action.clone())
+    }
+}
+
+/// Searchable list of user commands and normal mode mappings
+///
+/// Popover grabs keyboard only while shown, so keys go to nvim otherwise.
+pub struct CommandPalette {
+    popover: gtk::Popover,
+    state: Arc<UiMutex<State>>,
+    nvim: Rc<NeovimClient>,
+}
+
+impl CommandPalette {
+    pub fn new(shell: &Shell) -> Rc<CommandPalette> {
+        let (drawing_area, nvim) = {
+            let state = shell.state.borrow();
+            (state.drawing_area.clone(), state.nvim_clone())
+        };
+
+        let popover = gtk::Popover::new(Some(&drawing_area));
+        popover.set_position(gtk::PositionType::Bottom);
+
+        let search = gtk::SearchEntry::new();
+        let list = gtk::ListBox::new();
+        list.set_activate_on_single_click(true);
+
+        let scroll = gtk::ScrolledWindow::new(None, None);
+        scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroll.set_max_content_height(MAX_HEIGHT);
+        scroll.set_propagate_natural_height(true);
+        scroll.add(&list);
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 5);
+        vbox.set_border_width(5);
+        vbox.pack_start(&search, false, true, 0);
+        vbox.pack_start(&scroll, true, true, 0);
+        vbox.show_all();
+        popover.add(&vbox);
+
+        let palette = Rc::new(CommandPalette {
+            popover,
+            state: Arc::new(UiMutex::new(State {
+                entries: vec![],
+                shown: vec![],
+                generation: 0,
+                list: list.clone(),
+                search: search.clone(),
+            })),
+            nvim,
+        });
+
+        let state_ref = palette.state.clone();
+        search.connect_search_changed(move |_| state_ref.borrow_mut().filter());
+
+        let palette_ref = palette.clone();
+        search.connect_activate(move |_| palette_ref.activate(0));
+
+        let palette_ref = palette.clone();
+        list.connect_row_activated(move |_, row| palette_ref.activate(row.get_index()));
+
+        palette.popover.connect_closed(move |_| {
+            drawing_area.grab_focus();
+        });
+
+        palette
+    }
+
+    pub fn show(&self) {
+        let generation = {
+            let mut state = self.state.borrow_mut();
+            state.generation += 1;
+            state.entries.clear();
+            state.search.set_text("");
+            state.filter();
+            state.generation
+        };
+
+        if let Some(widget) = self.popover.get_relative_to() {
+            let width = widget.get_allocated_width();
+            self.popover.set_pointing_to(&gdk::Rectangle {
+                x: width / 2,
+                y: 0,
+                width: 1,
+                height: 1,
+            });
+        }
+        self.popover.popup();
+        self.state.borrow().search.grab_focus();
+
+        self.request_entries(generation);
+    }
+
+    /// Data is requested on every open, so it is always up to date
+    fn request_entries(&self, generation: u64) {
+        let mut nvim = match self.nvim.nvim() {
+            Some(nvim) => nvim,
+            None => return,
+        };
+
+        let state = self.state.clone();
+        nvim.session
+            .call_async::<Value>(
+                "nvim_get_commands",
+                vec![Value::Map(vec![("builtin".into(), false.into())])],
+            )
+            .cb(move |res| {
+                let entries = res.ok_and_report().map_or_else(Vec::new, |v| parse_commands(&v));
+                loaded(state, generation, entries);
+            })
+            .call();
+
+        let state = self.state.clone();
+        nvim.session
+            .call_async::<Value>("nvim_get_keymap", vec!["n".into()])
+            .cb(move |res| {
+                let entries = res.ok_and_report().map_or_else(Vec::new, |v| parse_keymaps(&v));
+                loaded(state, generation, entries);
+            })
+            .call();
+    }
+
+    fn activate(&self, row: i32) {
+        let action = self.state.borrow().action(row);
+        self.popover.popdown();
+
+        let action = match action {
+            Some(action) => action,
+            None => return,
+        };
+
+        if let Some(mut nvim) = self.nvim.nvim() {
+            match action {
+                Action::Command(command) => nvim
+                    .command_async(&command)
+                    .cb(|r| r.report_err())
+                    .call(),
+                Action::Keys(keys) => nvim.input_async(&keys).cb(|r| r.report_err()).call(),
+            }
+        }
+    }
+}
+
+fn loaded(state: Arc<UiMutex<State>>, generation: u64, entries: Vec<Entry>) {
+    let mut entries = Some(entries);
+    glib::idle_add(move || {
+        if let Some(entries) = entries.take() {
+            state.borrow_mut().loaded(generation, entries);
+        }
+        glib::Continue(false)
+    });
+}
+
+fn entry_row(entry: &Entry) -> gtk::Box {
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+    row.set_border_width(3);
+
+    let name = gtk::Label::new(Some(entry.name.as_str()));
+    name.set_halign(gtk::Align::Start);
+    row.pack_start(&name, false, false, 0);
+
+    let definition = gtk::Label::new(Some(entry.definition.as_str()));
+    definition.set_halign(gtk::Align::End);
+    definition.set_ellipsize(pango::EllipsizeMode::End);
+    definition.set_max_width_chars(40);
+    if let Some(style_context) = definition.get_style_context() {
+        style_context.add_class("dim-label");
+    }
+    row.pack_end(&definition, false, false, 0);
+
+    row
+}
+
+/// `nvim_get_commands` result: map of command name to command info
+fn parse_commands(commands: &Value) -> Vec<Entry> {
+    let commands = match commands.as_map() {
+        Some(commands) => commands,
+        None => return vec![],
+    };
+
+    commands
+        .iter()
+        .filter_map(|&(ref name, ref info)| {
+            let name = name.as_str()?;
+            let info = info.as_map().and_then(|info| info.to_attrs_map().ok());
+            let definition = info
+                .as_ref()
+                .and_then(|info| info.get("definition"))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            let no_args = info
+                .as_ref()
+                .and_then(|info| info.get("nargs"))
+                .and_then(Value::as_str)
+                .map_or(true, |nargs| nargs == "0");
+
+            let action = if no_args {
+                Action::Command(name.to_owned())
+            } else {
+                // arguments are typed by user
+                Action::Keys(format!(":{} ", name))
+            };
+
+            Some(Entry {
+                name: format!(":{}", name),
+                definition: definition.to_owned(),
+                action,
+            })
+        })
+        .collect()
+}
+
+/// `nvim_get_keymap` result: list of mapping dicts
+fn parse_keymaps(keymaps: &Value) -> Vec<Entry> {
+    let keymaps = match keymaps.as_array() {
+        Some(keymaps) => keymaps,
+        None => return vec![],
+    };
+
+    keymaps
+        .iter()
+        .filter_map(|keymap| keymap.as_map().and_then(|map| map.to_attrs_map().ok()))
+        .filter_map(|keymap| {
+            let lhs = keymap.get("lhs").and_then(Value::as_str)?;
+            // <Plug> mappings can't be typed
+            if lhs.starts_with("<Plug>") {
+                return None;
+            }
+            let definition = keymap
+                .get("desc")
+                .or_else(|| keymap.get("rhs"))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+
+            Some(Entry {
+                name: lhs.to_owned(),
+                definition: definition.to_owned(),
+                action: Action::Keys(lhs.to_owned()),
+            })
+        })
+        .collect()
+}
+
+/// Indexes of matched entries, best matches first
+fn filter_entries(entries: &[Entry], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i32, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| {
+            let name_score = fuzzy_score(query, &entry.name);
+            let definition_score = fuzzy_score(query, &entry.definition).map(|s| s / 2);
+            name_score.max(definition_score).map(|score| (score, idx))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| entries[a.1].name.cmp(&entries[b.1].name))
+    });
+    scored.truncate(MAX_RESULTS);
+    scored.into_iter().map(|(_, idx)| idx).collect()
+}
+
+/// Case insensitive subsequence match, consecutive chars and word starts score more
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut text_chars = text.chars().flat_map(char::to_lowercase).enumerate();
+    let mut prev_match: Option<usize> = None;
+    let mut prev_char = ' ';
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        if q.is_whitespace() {
+            continue;
+        }
+
+        loop {
+            let (idx, ch) = text_chars.next()?;
+            let word_start = !prev_char.is_alphanumeric();
+            prev_char = ch;
+            if ch == q {
+                score += 1;
+                if prev_match.map_or(false, |prev| prev + 1 == idx) {
+                    score += 5;
+                }
+                if word_start {
+                    score += 3;
+                }
+                prev_match = Some(idx);
+                break;
+            }
+        }
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> Entry {
+        Entry {
+            name: name.to_owned(),
+            definition: String::new(),
+            action: Action::Command(name.to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(Some(0), fuzzy_score("", "abc"));
+        assert!(fuzzy_score("gs", ":GitStatus").is_some());
+        assert!(fuzzy_score("sg", ":GitStatus").is_none());
+        assert!(fuzzy_score("git", ":GitStatus") > fuzzy_score("git", ":GoImportTags"));
+    }
+
+    #[test]
+    fn test_filter_entries() {
+        let entries = vec![entry(":Make"), entry(":GitStatus"), entry(":Git")];
+        assert_eq!(vec![2, 1], filter_entries(&entries, "git"));
+        assert_eq!(vec![2, 1, 0], filter_entries(&entries, ""));
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let commands = Value::Map(vec![(
+            "Make".into(),
+            Value::Map(vec![
+                ("definition".into(), "make".into()),
+                ("nargs".into(), "*".into()),
+            ]),
+        )]);
+        let entries = parse_commands(&commands);
+        assert_eq!(1, entries.len());
+        assert_eq!(":Make", entries[0].name);
+        assert_eq!(Action::Keys(":Make ".to_owned()), entries[0].action);
+    }
+}
@@ -0,0 +1,389 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe, PanicInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use backtrace::Backtrace;
+use gdk;
+use glib;
+use gtk;
+use gtk::prelude::*;
+use gtk::{ButtonsType, MessageDialog, MessageType};
+
+use dirs;
+
+/// Count of last applied redraw events included in crash report and events dump
+const RECENT_EVENTS_COUNT: usize = 200;
+
+const REPORT_VIEW_HEIGHT: i32 = 250;
+
+const ISSUES_URL: &str = "https://github.com/daa84/neovim-gtk/issues";
+
+/// File in app data dir with path of report that is not shown yet
+const PENDING_REPORT_FILE: &str = "pending-crash";
+
+lazy_static! {
+    static ref NVIM_VERSION: Mutex<Option<String>> = Mutex::new(None);
+    static ref RECENT_EVENTS: Mutex<VecDeque<(&'static str, usize)>> =
+        Mutex::new(VecDeque::with_capacity(RECENT_EVENTS_COUNT));
+    /// Report of last panic and path where it is saved
+    static ref LAST_REPORT: Mutex<Option<(String, String)>> = Mutex::new(None);
+    static ref UI_THREAD: Mutex<Option<ThreadId>> = Mutex::new(None);
+}
+
+thread_local! {
+    /// Panic inside guard is reported after unwinding by guard itself
+    static GUARDED: Cell<bool> = Cell::new(false);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrashAction {
+    Continue,
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CrashDlg {
+    /// Panic is caught by guard, UI can continue
+    Guarded,
+    /// Panic of background thread, UI is still running
+    Background,
+    /// Report left by previous process
+    Previous,
+}
+
+/// Remember version of connected nvim to include it in crash report
+pub fn set_nvim_version(version: String) {
+    if let Ok(mut nvim_version) = NVIM_VERSION.lock() {
+        *nvim_version = Some(version);
+    }
+}
+
+/// Remember name and argument count of applied redraw events,
+/// last ones are included in crash report
+pub fn record_events<I: Iterator<Item = (&'static str, usize)>>(summaries: I) {
+    if let Ok(mut events) = RECENT_EVENTS.lock() {
+        for summary in summaries {
+            if events.len() == RECENT_EVENTS_COUNT {
+                events.pop_front();
+            }
+            events.push_back(summary);
+        }
+    }
+}
+
+/// Recorded redraw events, oldest first
+pub fn recent_events() -> Vec<String> {
+    RECENT_EVENTS
+        .lock()
+        .map(|events| format_events(&events))
+        .unwrap_or_default()
+}
+
+fn format_events(events: &VecDeque<(&'static str, usize)>) -> Vec<String> {
+    events
+        .iter()
+        .map(|&(name, args)| format!("{} {}", name, args))
+        .collect()
+}
+
+/// Replace default panic handler, so panic writes crash report,
+/// must be called from UI thread.
+///
+/// Hook must not touch GTK: report of guarded panic is shown by guard after unwinding,
+/// report of background thread panic is shown from main loop,
+/// report of unguarded UI thread panic is shown on next start as UI can't continue
+pub fn install_handler() {
+    if let Ok(mut ui_thread) = UI_THREAD.lock() {
+        *ui_thread = Some(thread::current().id());
+    }
+
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let message = panic_message(info);
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown".to_owned());
+        let backtrace = format!("{:?}", Backtrace::new());
+        let report = report(&message, &location, &backtrace);
+
+        let path = match write_report(&report) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Can't write crash report: {}", e);
+                return;
+            }
+        };
+
+        if GUARDED.with(|guarded| guarded.get()) {
+            if let Ok(mut last_report) = LAST_REPORT.lock() {
+                *last_report = Some((report, path.to_string_lossy().into_owned()));
+            }
+        } else if !is_ui_thread() {
+            let path = path.to_string_lossy().into_owned();
+            glib::idle_add(move || {
+                if run_crash_dlg(&report, &path, CrashDlg::Background) == CrashAction::Quit {
+                    gtk::main_quit();
+                }
+                glib::Continue(false)
+            });
+        } else if let Err(e) = set_pending_report(Some(&path)) {
+            eprintln!("Can't save pending crash report: {}", e);
+        }
+    }));
+}
+
+fn is_ui_thread() -> bool {
+    // lock can be poisoned or held by panicked thread
+    UI_THREAD
+        .try_lock()
+        .ok()
+        .and_then(|ui_thread| *ui_thread)
+        .map_or(false, |id| id == thread::current().id())
+}
+
+/// Show crash report left by previous process, call it once GTK is initialized
+pub fn show_pending_report() {
+    let path = match take_pending_report() {
+        Some(path) => path,
+        None => return,
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(report) => {
+            run_crash_dlg(&report, &path.to_string_lossy(), CrashDlg::Previous);
+        }
+        Err(e) => error!("Can't read crash report {}: {}", path.display(), e),
+    }
+}
+
+/// Run code on UI thread and catch its panic, user decides
+/// whether to continue or to quit after panic is reported
+pub fn guard<F, R>(f: F) -> Result<R, CrashAction>
+where
+    F: FnOnce() -> R,
+{
+    let was_guarded = GUARDED.with(|guarded| guarded.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    GUARDED.with(|guarded| guarded.set(was_guarded));
+
+    match result {
+        Ok(result) => Ok(result),
+        Err(_) if was_guarded => {
+            // outer guard reports it
+            panic::resume_unwind(Box::new("nested panic"))
+        }
+        Err(_) => {
+            let (report, path) = LAST_REPORT
+                .lock()
+                .ok()
+                .and_then(|mut last_report| last_report.take())
+                .unwrap_or_else(|| ("Unknown panic".to_owned(), "<not saved>".to_owned()));
+
+            let action = run_crash_dlg(&report, &path, CrashDlg::Guarded);
+            if action == CrashAction::Quit {
+                gtk::main_quit();
+            }
+            Err(action)
+        }
+    }
+}
+
+fn write_report(report: &str) -> Result<PathBuf, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut path = dirs::get_app_data_dir_create()?;
+    path.push(format!("crash-{}.log", timestamp));
+
+    let mut file = File::create(&path).map_err(|e| format!("{}", e))?;
+    file.write_all(report.as_bytes())
+        .map_err(|e| format!("{}", e))?;
+
+    Ok(path)
+}
+
+fn pending_report_file() -> Result<PathBuf, String> {
+    let mut path = dirs::get_app_data_dir_create()?;
+    path.push(PENDING_REPORT_FILE);
+    Ok(path)
+}
+
+fn set_pending_report(report_path: Option<&PathBuf>) -> Result<(), String> {
+    let marker = pending_report_file()?;
+
+    match report_path {
+        Some(report_path) => fs::write(&marker, report_path.to_string_lossy().as_bytes()),
+        None => fs::remove_file(&marker),
+    }.map_err(|e| format!("{}", e))
+}
+
+fn take_pending_report() -> Option<PathBuf> {
+    let marker = pending_report_file().ok()?;
+    let path = fs::read_to_string(&marker).ok()?;
+
+    if let Err(e) = set_pending_report(None) {
+        error!("Can't remove {}: {}", marker.display(), e);
+    }
+
+    Some(PathBuf::from(path.trim_right()))
+}
+
+fn panic_message(info: &PanicInfo) -> String {
+    if let Some(s) = info.payload().downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic payload".to_owned()
+    }
+}
+
+fn report(message: &str, location: &str, backtrace: &str) -> String {
+    // lock can be poisoned or held by panicked thread
+    let nvim_version = NVIM_VERSION
+        .try_lock()
+        .ok()
+        .and_then(|v| v.clone())
+        .unwrap_or_else(|| "unknown".to_owned());
+    let recent_events = RECENT_EVENTS
+        .try_lock()
+        .ok()
+        .map(|events| format_events(&events).join("\n  "))
+        .unwrap_or_default();
+
+    format!(
+        "nvim-gtk version: {}\n\
+         nvim version: {}\n\
+         thread: {}\n\
+         location: {}\n\
+         message: {}\n\
+         recent redraw events:\n  {}\n\
+         backtrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        nvim_version,
+        thread::current().name().unwrap_or("<unnamed>"),
+        location,
+        message,
+        recent_events,
+        backtrace,
+    )
+}
+
+fn issue_text(report: &str) -> String {
+    format!(
+        "<!-- {} -->\n\
+         **What happened**\n\n\n\
+         **Crash report**\n\
+         ```\n{}```\n",
+        ISSUES_URL, report
+    )
+}
+
+fn run_crash_dlg(report: &str, path: &str, kind: CrashDlg) -> CrashAction {
+    const COPY_RESPONSE: i32 = 1;
+    const CONTINUE_RESPONSE: i32 = 2;
+    const QUIT_RESPONSE: i32 = 3;
+
+    let title = match kind {
+        CrashDlg::Guarded | CrashDlg::Background => "nvim-gtk has crashed",
+        CrashDlg::Previous => "nvim-gtk has crashed last time",
+    };
+    let dlg = MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        MessageType::Error,
+        ButtonsType::None,
+        title,
+    );
+    let mut text = format!("Crash report saved to {}", path);
+    match kind {
+        CrashDlg::Guarded => {
+            text.push_str("\n\nContinue keeps nvim running, but UI can be broken, save your work.")
+        }
+        CrashDlg::Background => text.push_str(
+            "\n\nContinue keeps UI open, but connection to nvim can be lost, save your work.",
+        ),
+        CrashDlg::Previous => (),
+    }
+    dlg.set_property_secondary_text(Some(&text));
+
+    // report is long, so it is scrolled instead of stretching dialog
+    let report_view = gtk::TextView::new();
+    report_view.set_editable(false);
+    report_view.set_monospace(true);
+    if let Some(buffer) = report_view.get_buffer() {
+        buffer.set_text(report);
+    }
+
+    let scroll = gtk::ScrolledWindow::new(None, None);
+    scroll.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+    scroll.set_shadow_type(gtk::ShadowType::In);
+    scroll.set_size_request(-1, REPORT_VIEW_HEIGHT);
+    scroll.add(&report_view);
+
+    let content = dlg.get_content_area();
+    content.pack_start(&scroll, true, true, 0);
+    content.show_all();
+
+    dlg.add_button("Copy Issue Report", COPY_RESPONSE);
+    match kind {
+        CrashDlg::Guarded | CrashDlg::Background => {
+            dlg.add_button("Continue", CONTINUE_RESPONSE);
+            dlg.add_button("Quit", QUIT_RESPONSE);
+        }
+        CrashDlg::Previous => {
+            dlg.add_button("Close", CONTINUE_RESPONSE);
+        }
+    }
+
+    let action = loop {
+        match dlg.run() {
+            COPY_RESPONSE => {
+                let clipboard = gtk::Clipboard::get(&gdk::Atom::intern("CLIPBOARD"));
+                clipboard.set_text(&issue_text(report));
+            }
+            CONTINUE_RESPONSE => break CrashAction::Continue,
+            _ => break CrashAction::Quit,
+        }
+    };
+    dlg.destroy();
+
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        set_nvim_version("0.3.1".to_owned());
+
+        let report = report("test panic", "src/shell.rs:1:1", "0: crash::tests");
+        assert!(report.contains("nvim version: 0.3.1"));
+        assert!(report.contains("location: src/shell.rs:1:1"));
+        assert!(report.contains("message: test panic"));
+        assert!(report.contains("backtrace:\n0: crash::tests"));
+    }
+
+    #[test]
+    fn test_recent_events() {
+        record_events((0..RECENT_EVENTS_COUNT + 2).map(|i| ("put", i)));
+
+        let events = recent_events();
+        assert_eq!(RECENT_EVENTS_COUNT, events.len());
+        assert_eq!(Some(&"put 2".to_owned()), events.first());
+    }
+}
@@ -3,6 +3,7 @@ use color;
 use ui::UiMutex;
 use mode;
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use render;
 use render::CellMetrics;
 
@@ -68,7 +69,136 @@ impl<CB: CursorRedrawCb> State<CB> {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, t: f64) -> f64 {
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => if t < 0.5 {
+                4.0 * t * t * t
+            } else {
+                1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+            },
+        }
+    }
+}
+
+const MOVE_ANIMATION_DURATION_MS: u64 = 80;
+
+/// Slide cursor from previous cell to the new one
+struct MoveAnimation {
+    enabled: bool,
+    easing: Easing,
+    from: (f64, f64),
+    to: (f64, f64),
+    start: Option<Instant>,
+}
+
+impl MoveAnimation {
+    fn new() -> Self {
+        MoveAnimation {
+            enabled: false,
+            easing: Easing::EaseOut,
+            from: (0.0, 0.0),
+            to: (0.0, 0.0),
+            start: None,
+        }
+    }
+
+    fn duration() -> Duration {
+        Duration::from_millis(MOVE_ANIMATION_DURATION_MS)
+    }
+
+    fn progress(&self, now: Instant) -> f64 {
+        match self.start {
+            Some(start) if now > start => {
+                let elapsed = now - start;
+                let elapsed =
+                    elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_nanos()) / 1e6;
+                (elapsed / MOVE_ANIMATION_DURATION_MS as f64).min(1.0)
+            }
+            Some(_) => 0.0,
+            None => 1.0,
+        }
+    }
+
+    fn position_at(&self, progress: f64) -> (f64, f64) {
+        let t = self.easing.apply(progress);
+        (
+            self.from.0 + (self.to.0 - self.from.0) * t,
+            self.from.1 + (self.to.1 - self.from.1) * t,
+        )
+    }
+
+    /// Current interpolated (row, col) position
+    fn position(&self, now: Instant) -> (f64, f64) {
+        self.position_at(self.progress(now))
+    }
+
+    fn is_running(&self, now: Instant) -> bool {
+        self.start.is_some() && self.progress(now) < 1.0
+    }
+
+    /// Move target to the new cell
+    ///
+    /// In case animation is in progress new animation starts from current
+    /// interpolated position. Adjacent moves on same row (normal typing) are not animated.
+    /// Returns true if animation started.
+    fn move_to(&mut self, row: usize, col: usize, now: Instant) -> bool {
+        let target = (row as f64, col as f64);
+        let current = self.position(now);
+        self.to = target;
+
+        let adjacent = (current.0 - target.0).abs() < ::std::f64::EPSILON
+            && (current.1 - target.1).abs() <= 1.0;
+
+        if !self.enabled || adjacent {
+            self.from = target;
+            self.start = None;
+            false
+        } else {
+            self.from = current;
+            self.start = Some(now);
+            true
+        }
+    }
+
+    fn trail_position(&self, now: Instant) -> Option<(f64, f64)> {
+        if self.is_running(now) {
+            Some(self.position_at(self.progress(now) * 0.6))
+        } else {
+            None
+        }
+    }
+}
+
 pub trait Cursor {
+    /// Position of cursor in cells, can be fractional while animation is running
+    fn position(&self, row: usize, col: usize) -> (f64, f64) {
+        (row as f64, col as f64)
+    }
+
+    /// Position of cursor trail in cells, if any
+    fn trail_position(&self) -> Option<(f64, f64)> {
+        None
+    }
+
     fn draw(
         &self,
         ctx: &cairo::Context,
@@ -102,6 +232,9 @@ impl Cursor for EmptyCursor {
 pub struct BlinkCursor<CB: CursorRedrawCb> {
     state: Arc<UiMutex<State<CB>>>,
     mode_info: Option<mode::ModeInfo>,
+    /// Shape used in all modes instead of one from mode info
+    shape_override: Option<mode::CursorShape>,
+    move_animation: MoveAnimation,
 }
 
 impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
@@ -109,13 +242,39 @@ impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
         BlinkCursor {
             state: Arc::new(UiMutex::new(State::new(redraw_cb))),
             mode_info: None,
+            shape_override: None,
+            move_animation: MoveAnimation::new(),
+        }
+    }
+
+    pub fn set_move_animation(&mut self, enabled: bool) {
+        self.move_animation.enabled = enabled;
+        if !enabled {
+            self.move_animation.start = None;
         }
     }
 
+    pub fn set_move_animation_easing(&mut self, easing: Easing) {
+        self.move_animation.easing = easing;
+    }
+
+    /// Returns true if move animation started and cursor must be redrawn on every frame
+    pub fn move_to(&mut self, row: usize, col: usize) -> bool {
+        self.move_animation.move_to(row, col, Instant::now())
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.move_animation.is_running(Instant::now())
+    }
+
     pub fn set_mode_info(&mut self, mode_info: Option<mode::ModeInfo>) {
         self.mode_info = mode_info;
     }
 
+    pub fn set_shape_override(&mut self, shape_override: Option<mode::CursorShape>) {
+        self.shape_override = shape_override;
+    }
+
     pub fn start(&mut self) {
         let blinkwait = self.mode_info
             .as_ref()
@@ -159,6 +318,23 @@ impl<CB: CursorRedrawCb + 'static> BlinkCursor<CB> {
 }
 
 impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
+    fn position(&self, row: usize, col: usize) -> (f64, f64) {
+        let now = Instant::now();
+        if self.move_animation.is_running(now) {
+            self.move_animation.position(now)
+        } else {
+            (row as f64, col as f64)
+        }
+    }
+
+    fn trail_position(&self) -> Option<(f64, f64)> {
+        if self.state.borrow().anim_phase == AnimPhase::Busy {
+            return None;
+        }
+
+        self.move_animation.trail_position(Instant::now())
+    }
+
     fn draw(
         &self,
         ctx: &cairo::Context,
@@ -180,6 +356,7 @@ impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
 
         let (y, width, height) = cursor_rect(
             self.mode_info.as_ref(),
+            self.shape_override.as_ref(),
             font_ctx.cell_metrics(),
             line_y,
             double_width,
@@ -194,8 +371,13 @@ impl<CB: CursorRedrawCb> Cursor for BlinkCursor<CB> {
     }
 }
 
+/// Cell percentage of overridden shape, same as nvim 'guicursor' defaults
+const LINE_CELL_PERCENTAGE: u64 = 25;
+const UNDERLINE_CELL_PERCENTAGE: u64 = 20;
+
 fn cursor_rect(
     mode_info: Option<&mode::ModeInfo>,
+    shape_override: Option<&mode::CursorShape>,
     cell_metrics: &CellMetrics,
     line_y: f64,
     double_width: bool,
@@ -206,49 +388,45 @@ fn cursor_rect(
         ..
     } = cell_metrics;
 
-    if let Some(mode_info) = mode_info {
-        match mode_info.cursor_shape() {
-            None | Some(&mode::CursorShape::Unknown) | Some(&mode::CursorShape::Block) => {
-                let cursor_width = if double_width {
-                    char_width * 2.0
-                } else {
-                    char_width
-                };
-                (line_y, cursor_width, line_height)
-            }
-            Some(&mode::CursorShape::Vertical) => {
-                let cell_percentage = mode_info.cell_percentage();
-                let cursor_width = if cell_percentage > 0 {
-                    (char_width * cell_percentage as f64) / 100.0
-                } else {
-                    char_width
-                };
-                (line_y, cursor_width, line_height)
-            }
-            Some(&mode::CursorShape::Horizontal) => {
-                let cell_percentage = mode_info.cell_percentage();
-                let cursor_width = if double_width {
-                    char_width * 2.0
-                } else {
-                    char_width
-                };
-
-                if cell_percentage > 0 {
-                    let height = (line_height * cell_percentage as f64) / 100.0;
-                    (line_y + line_height - height, cursor_width, height)
-                } else {
-                    (line_y, cursor_width, line_height)
-                }
-            }
+    let mode_shape = mode_info.and_then(mode::ModeInfo::cursor_shape);
+    let shape = shape_override.or(mode_shape);
+    // size from mode info is kept while it has the same shape
+    let cell_percentage = match (shape_override, mode_info) {
+        (Some(shape), Some(mode_info)) if mode_shape == Some(shape) => {
+            mode_info.cell_percentage()
         }
+        (Some(&mode::CursorShape::Vertical), _) => LINE_CELL_PERCENTAGE,
+        (Some(&mode::CursorShape::Horizontal), _) => UNDERLINE_CELL_PERCENTAGE,
+        (_, Some(mode_info)) => mode_info.cell_percentage(),
+        (_, None) => 0,
+    };
+
+    let cursor_width = if double_width {
+        char_width * 2.0
     } else {
-        let cursor_width = if double_width {
-            char_width * 2.0
-        } else {
-            char_width
-        };
+        char_width
+    };
 
-        (line_y, cursor_width, line_height)
+    match shape {
+        None | Some(&mode::CursorShape::Unknown) | Some(&mode::CursorShape::Block) => {
+            (line_y, cursor_width, line_height)
+        }
+        Some(&mode::CursorShape::Vertical) => {
+            let cursor_width = if cell_percentage > 0 {
+                (char_width * cell_percentage as f64) / 100.0
+            } else {
+                char_width
+            };
+            (line_y, cursor_width, line_height)
+        }
+        Some(&mode::CursorShape::Horizontal) => {
+            if cell_percentage > 0 {
+                let height = (line_height * cell_percentage as f64) / 100.0;
+                (line_y + line_height - height, cursor_width, height)
+            } else {
+                (line_y, cursor_width, line_height)
+            }
+        }
     }
 }
 
@@ -314,6 +492,58 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
+    #[test]
+    fn test_move_animation_disabled() {
+        let now = Instant::now();
+        let mut anim = MoveAnimation::new();
+
+        assert!(!anim.move_to(10, 10, now));
+        assert_eq!((10.0, 10.0), anim.position(now));
+    }
+
+    #[test]
+    fn test_move_animation() {
+        let now = Instant::now();
+        let mut anim = MoveAnimation::new();
+        anim.enabled = true;
+        anim.easing = Easing::Linear;
+
+        assert!(anim.move_to(10, 0, now));
+        assert!(anim.is_running(now));
+        assert_eq!((0.0, 0.0), anim.position(now));
+
+        let half = now + MoveAnimation::duration() / 2;
+        assert_eq!((5.0, 0.0), anim.position(half));
+
+        let end = now + MoveAnimation::duration();
+        assert!(!anim.is_running(end));
+        assert_eq!((10.0, 0.0), anim.position(end));
+    }
+
+    #[test]
+    fn test_move_animation_retarget() {
+        let now = Instant::now();
+        let mut anim = MoveAnimation::new();
+        anim.enabled = true;
+        anim.easing = Easing::Linear;
+
+        anim.move_to(10, 0, now);
+        let half = now + MoveAnimation::duration() / 2;
+        assert!(anim.move_to(0, 0, half));
+        assert_eq!((5.0, 0.0), anim.position(half));
+    }
+
+    #[test]
+    fn test_move_animation_skip_adjacent() {
+        let now = Instant::now();
+        let mut anim = MoveAnimation::new();
+        anim.enabled = true;
+
+        assert!(!anim.move_to(0, 1, now));
+        assert!(!anim.move_to(0, 0, now));
+        assert!(anim.move_to(1, 0, now));
+    }
+
     #[test]
     fn test_cursor_rect_horizontal() {
         let mut mode_data = HashMap::new();
@@ -327,6 +557,7 @@ mod tests {
 
         let (y, width, height) = cursor_rect(
             mode_info.as_ref(),
+            None,
             &CellMetrics::new_hw(line_height, char_width),
             line_y,
             false,
@@ -349,6 +580,7 @@ mod tests {
 
         let (y, width, height) = cursor_rect(
             mode_info.as_ref(),
+            None,
             &CellMetrics::new_hw(line_height, char_width),
             line_y,
             true,
@@ -371,6 +603,7 @@ mod tests {
 
         let (y, width, height) = cursor_rect(
             mode_info.as_ref(),
+            None,
             &CellMetrics::new_hw(line_height, char_width),
             line_y,
             false,
@@ -379,4 +612,42 @@ mod tests {
         assert_eq!(char_width / 4.0, width);
         assert_eq!(line_height, height);
     }
+
+    #[test]
+    fn test_cursor_rect_override() {
+        let mut mode_data = HashMap::new();
+        mode_data.insert("cursor_shape".to_owned(), From::from("vertical"));
+        mode_data.insert("cell_percentage".to_owned(), From::from(10));
+
+        let mode_info = mode::ModeInfo::new(&mode_data).ok();
+        let cell_metrics = CellMetrics::new_hw(30.0, 50.0);
+
+        let block = cursor_rect(
+            mode_info.as_ref(),
+            Some(&mode::CursorShape::Block),
+            &cell_metrics,
+            0.0,
+            false,
+        );
+        assert_eq!((0.0, 50.0, 30.0), block);
+
+        // mode has same shape, its size is used
+        let line = cursor_rect(
+            mode_info.as_ref(),
+            Some(&mode::CursorShape::Vertical),
+            &cell_metrics,
+            0.0,
+            false,
+        );
+        assert_eq!((0.0, 5.0, 30.0), line);
+
+        let underline = cursor_rect(
+            mode_info.as_ref(),
+            Some(&mode::CursorShape::Horizontal),
+            &cell_metrics,
+            0.0,
+            false,
+        );
+        assert_eq!((24.0, 50.0, 6.0), underline);
+    }
 }
@@ -20,14 +20,85 @@ pub fn get_app_config_dir() -> Result<PathBuf, String> {
     Ok(config_dir)
 }
 
+pub fn get_app_data_dir_create() -> Result<PathBuf, String> {
+    let mut data_dir = get_xdg_data_dir()?;
+
+    data_dir.push("nvim-gtk");
+
+    std::fs::create_dir_all(&data_dir).map_err(|e| format!("{}", e))?;
+
+    Ok(data_dir)
+}
+
+/// Directory for sockets, only user has access to it
+pub fn get_app_runtime_dir_create() -> Result<PathBuf, String> {
+    let mut runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(std::env::temp_dir);
+
+    runtime_dir.push("nvim-gtk");
+
+    std::fs::create_dir_all(&runtime_dir).map_err(|e| format!("{}", e))?;
+    set_user_only_permissions(&runtime_dir)?;
+
+    Ok(runtime_dir)
+}
+
+#[cfg(unix)]
+fn set_user_only_permissions(path: &PathBuf) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))
+        .map_err(|e| format!("{}", e))
+}
+
+#[cfg(not(unix))]
+fn set_user_only_permissions(_path: &PathBuf) -> Result<(), String> {
+    Ok(())
+}
+
+fn get_xdg_data_dir() -> Result<PathBuf, String> {
+    xdg_dir(std::env::var("XDG_DATA_HOME").ok(), &[".local", "share"])
+}
+
 fn get_xdg_config_dir() -> Result<PathBuf, String> {
-    if let Ok(config_path) = std::env::var("XDG_CONFIG_HOME") {
-        return Ok(PathBuf::from(config_path));
+    xdg_dir(std::env::var("XDG_CONFIG_HOME").ok(), &[".config"])
+}
+
+/// Empty or relative XDG variable is invalid and must be ignored,
+/// default dir inside home is used then
+fn xdg_dir(env_value: Option<String>, home_default: &[&str]) -> Result<PathBuf, String> {
+    if let Some(path) = env_value.map(PathBuf::from) {
+        if path.is_absolute() {
+            return Ok(path);
+        }
     }
 
     let mut home_dir = env_dirs::home_dir().ok_or(
         "Impossible to get your home dir!",
     )?;
-    home_dir.push(".config");
+    for part in home_default {
+        home_dir.push(part);
+    }
     Ok(home_dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xdg_dir() {
+        assert_eq!(
+            PathBuf::from("/xdg/config"),
+            xdg_dir(Some("/xdg/config".to_owned()), &[".config"]).unwrap()
+        );
+
+        let default = xdg_dir(None, &[".config"]).unwrap();
+        assert!(default.ends_with(".config"));
+        assert_eq!(default, xdg_dir(Some("".to_owned()), &[".config"]).unwrap());
+        assert_eq!(default, xdg_dir(Some("relative".to_owned()), &[".config"]).unwrap());
+    }
+}
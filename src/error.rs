@@ -1,9 +1,12 @@
+use std::cell::Cell;
 use std::ops::Deref;
+use std::rc::Rc;
 
 use htmlescape::encode_minimal;
 
 use gtk;
 use gtk::prelude::*;
+use glib;
 
 use gtk_sys;
 
@@ -54,6 +57,112 @@ impl ErrorArea {
     }
 }
 
+/// Transient errors are hidden after this time by default
+const TRANSIENT_TIMEOUT_MS: u32 = 5000;
+
+/// Non fatal errors, nvim keeps running under this bar
+pub struct ErrorBar {
+    base: gtk::InfoBar,
+    label: gtk::Label,
+    /// Incremented on every message, so timer of replaced transient message does nothing
+    generation: Rc<Cell<u64>>,
+    /// Set by `GtkGuiMessageTimeout`, 0 keeps message until next key press
+    transient_timeout_ms: Cell<u32>,
+    /// Transient message waits for key press
+    transient_shown: Cell<bool>,
+}
+
+impl ErrorBar {
+    pub fn new() -> Self {
+        let base = gtk::InfoBar::new();
+        base.set_show_close_button(true);
+        base.get_style_context().unwrap().add_class("nvim-error-bar");
+        base.set_no_show_all(true);
+        base.connect_response(|base, _| base.hide());
+
+        let label = gtk::Label::new(None);
+        label.set_line_wrap(true);
+        label.set_halign(gtk::Align::Start);
+        if let Some(content) = base
+            .get_content_area()
+            .and_then(|w| w.downcast::<gtk::Container>().ok())
+        {
+            content.add(&label);
+        }
+        label.show();
+
+        ErrorBar {
+            base,
+            label,
+            generation: Rc::new(Cell::new(0)),
+            transient_timeout_ms: Cell::new(TRANSIENT_TIMEOUT_MS),
+            transient_shown: Cell::new(false),
+        }
+    }
+
+    fn show_message(&self, message_type: gtk::MessageType, text: &str) -> u64 {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+        self.transient_shown.set(false);
+
+        self.base.set_message_type(message_type);
+        self.label.set_text(text);
+        self.base.show();
+        generation
+    }
+
+    pub fn show_error(&self, err: &str) {
+        error!("{}", err);
+        self.show_message(gtk::MessageType::Error, err);
+    }
+
+    pub fn show_warning(&self, warn: &str) {
+        warn!("{}", warn);
+        self.show_message(gtk::MessageType::Warning, warn);
+    }
+
+    /// Error that does not need user action, e.g. failed async nvim call
+    pub fn show_transient_error(&self, err: &str) {
+        error!("{}", err);
+        let shown = self.show_message(gtk::MessageType::Error, err);
+
+        let timeout_ms = self.transient_timeout_ms.get();
+        if timeout_ms == 0 {
+            self.transient_shown.set(true);
+            return;
+        }
+
+        let base = self.base.clone();
+        let generation = self.generation.clone();
+        gtk::timeout_add(timeout_ms, move || {
+            if generation.get() == shown {
+                base.hide();
+            }
+            glib::Continue(false)
+        });
+    }
+
+    pub fn set_transient_timeout(&self, timeout_ms: u32) {
+        self.transient_timeout_ms.set(timeout_ms);
+    }
+
+    /// Hide transient message kept by zero timeout, called on key press
+    pub fn dismiss_transient(&self) {
+        if self.transient_shown.get() {
+            self.transient_shown.set(false);
+            self.base.hide();
+        }
+    }
+}
+
+impl Deref for ErrorBar {
+    type Target = gtk::InfoBar;
+
+    fn deref(&self) -> &gtk::InfoBar {
+        &self.base
+    }
+}
+
 impl Deref for ErrorArea {
     type Target = gtk::Box;
 
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use settings::SettingsLoader;
+
+/// User GUI configuration from `config.toml`
+///
+/// Named `[profile.<name>]` tables override top-level keys when the
+/// profile is selected with `--profile <name>`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct GuiConfig {
+    pub font: Option<String>,
+    pub font_features: Option<String>,
+    pub font_bold: Option<String>,
+    pub font_italic: Option<String>,
+    pub font_bold_italic: Option<String>,
+    pub line_space: Option<i64>,
+    pub antialiasing: Option<String>,
+    pub padding: Option<String>,
+    pub cursor_animation: Option<bool>,
+    pub cursor_animation_easing: Option<String>,
+    pub cursor_shape: Option<String>,
+    pub scroll_without_mouse: Option<bool>,
+    pub restore_session: Option<bool>,
+    pub fullscreen_hide_tabline: Option<bool>,
+    pub show_status_bar: Option<bool>,
+    pub dim_inactive: Option<u64>,
+    pub export_cursor_rect: Option<bool>,
+    pub overscroll: Option<bool>,
+
+    #[serde(default)]
+    profile: BTreeMap<String, toml::Value>,
+}
+
+impl GuiConfig {
+    /// Location of config file, `config_path` comes from `--config`
+    pub fn path(config_path: Option<&str>) -> Result<PathBuf, String> {
+        match config_path {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => GuiConfig::settings_path(),
+        }
+    }
+
+    pub fn load_profile(config_path: Option<&str>, profile: Option<&str>) -> Self {
+        let config = match config_path {
+            Some(path) => GuiConfig::load_from(Path::new(path)),
+            None => GuiConfig::load(),
+        };
+
+        if let Some(profile) = profile {
+            match config.apply_profile(profile) {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("{}", e);
+                    config
+                }
+            }
+        } else {
+            config
+        }
+    }
+
+    fn apply_profile(&self, name: &str) -> Result<Self, String> {
+        let overrides = match self.profile.get(name) {
+            Some(toml::Value::Table(overrides)) => overrides,
+            Some(_) => return Err(format!("Profile {} must be a table", name)),
+            None => return Err(format!("Profile {} not found", name)),
+        };
+
+        let mut base = match toml::Value::try_from(self).map_err(|e| format!("{}", e))? {
+            toml::Value::Table(base) => base,
+            _ => unreachable!(),
+        };
+
+        for (key, value) in overrides {
+            if key == "profile" {
+                warn!("Nested profile in profile {} ignored", name);
+                continue;
+            }
+            base.insert(key.clone(), value.clone());
+        }
+
+        toml::Value::Table(base)
+            .try_into()
+            .map_err(|e| format!("Profile {}: {}", name, e))
+    }
+}
+
+impl SettingsLoader for GuiConfig {
+    const SETTINGS_FILE: &'static str = "config.toml";
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        toml::from_str(&s).map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+font = "Monospace 11"
+line_space = 2
+
+[profile.presentation]
+font = "Monospace 20"
+"#;
+
+    #[test]
+    fn test_profile_overrides_top_level() {
+        let config = GuiConfig::from_str(CONFIG).unwrap();
+        let config = config.apply_profile("presentation").unwrap();
+
+        assert_eq!(Some("Monospace 20".to_owned()), config.font);
+        assert_eq!(Some(2), config.line_space);
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let config = GuiConfig::from_str(CONFIG).unwrap();
+        assert!(config.apply_profile("unknown").is_err());
+    }
+
+    #[test]
+    fn test_no_profile() {
+        let config = GuiConfig::from_str(CONFIG).unwrap();
+        assert_eq!(Some("Monospace 11".to_owned()), config.font);
+        assert_eq!(None, config.font_features);
+    }
+}
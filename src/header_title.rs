@@ -0,0 +1,168 @@
+use std::ops::Deref;
+
+use gio;
+use gtk;
+use gtk::prelude::*;
+use htmlescape::encode_minimal;
+use pango;
+
+use misc;
+
+/// Expressions evaluated by header title subscription, in `update` arguments order
+pub const TITLE_ARGS: &[&str] = &["expand('%:t')", "expand('%:p:h')", "&modified"];
+
+const ICON_SIZE: i32 = 16;
+
+#[derive(Default, PartialEq)]
+struct Title {
+    name: String,
+    dir: String,
+    modified: bool,
+}
+
+impl Title {
+    fn from_args(args: &[String]) -> Self {
+        Title {
+            name: args[0].clone(),
+            dir: misc::shorten_home(&args[1]),
+            modified: args[2] == "1",
+        }
+    }
+
+    fn name_markup(&self) -> String {
+        let name = if self.name.is_empty() {
+            "[No Name]"
+        } else {
+            &self.name
+        };
+
+        if self.modified {
+            format!("<b>{}</b> \u{2022}", encode_minimal(name))
+        } else {
+            format!("<b>{}</b>", encode_minimal(name))
+        }
+    }
+}
+
+/// Header bar title: file type icon, file name, modified mark and directory
+///
+/// Only changed parts are updated, so quick buffer switching doesn't relayout header bar
+pub struct HeaderTitle {
+    container: gtk::Box,
+    icon: gtk::Image,
+    name: gtk::Label,
+    dir: gtk::Label,
+    title: Title,
+    content_type: Option<String>,
+}
+
+impl HeaderTitle {
+    pub fn new() -> Self {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        container.set_valign(gtk::Align::Center);
+
+        let name_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        name_box.set_halign(gtk::Align::Center);
+
+        let icon = gtk::Image::new();
+        icon.set_no_show_all(true);
+        name_box.pack_start(&icon, false, false, 0);
+
+        let name = gtk::Label::new(None);
+        name.set_ellipsize(pango::EllipsizeMode::Middle);
+        name_box.pack_start(&name, false, false, 0);
+        container.pack_start(&name_box, false, false, 0);
+
+        let dir = gtk::Label::new(None);
+        dir.set_ellipsize(pango::EllipsizeMode::Start);
+        dir.get_style_context().map(|c| c.add_class("dim-label"));
+        container.pack_start(&dir, false, false, 0);
+
+        container.show_all();
+
+        HeaderTitle {
+            container,
+            icon,
+            name,
+            dir,
+            title: Title::default(),
+            content_type: None,
+        }
+    }
+
+    pub fn update(&mut self, args: &[String]) {
+        let title = Title::from_args(args);
+        if title == self.title {
+            return;
+        }
+
+        if title.name != self.title.name || title.modified != self.title.modified {
+            self.name.set_markup(&title.name_markup());
+        }
+        if title.dir != self.title.dir {
+            self.dir
+                .set_markup(&format!("<small>{}</small>", encode_minimal(&title.dir)));
+        }
+        if title.name != self.title.name {
+            self.update_icon(&title.name);
+        }
+
+        self.title = title;
+    }
+
+    fn update_icon(&mut self, name: &str) {
+        let content_type = if name.is_empty() {
+            None
+        } else {
+            Some(gio::content_type_guess(Some(name), &[]).0)
+        };
+        if content_type == self.content_type {
+            return;
+        }
+
+        // plain text title when icon theme has no icon for mime type
+        let icon = content_type
+            .as_ref()
+            .and_then(|content_type| gio::content_type_get_icon(content_type))
+            .filter(|icon| {
+                gtk::IconTheme::get_default()
+                    .and_then(|theme| {
+                        theme.lookup_by_gicon(icon, ICON_SIZE, gtk::IconLookupFlags::empty())
+                    })
+                    .is_some()
+            });
+
+        match icon {
+            Some(icon) => {
+                self.icon.set_from_gicon(&icon, gtk::IconSize::Menu);
+                self.icon.show();
+            }
+            None => self.icon.hide(),
+        }
+
+        self.content_type = content_type;
+    }
+}
+
+impl Deref for HeaderTitle {
+    type Target = gtk::Box;
+
+    fn deref(&self) -> &gtk::Box {
+        &self.container
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_markup() {
+        let mut title = Title::default();
+        assert_eq!("<b>[No Name]</b>", title.name_markup());
+
+        title.name = "a&b.rs".to_owned();
+        title.modified = true;
+        assert_eq!("<b>a&amp;b.rs</b> \u{2022}", title.name_markup());
+    }
+}
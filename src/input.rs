@@ -3,7 +3,9 @@ use gtk::prelude::*;
 use gdk;
 use gdk::EventKey;
 use phf;
-use neovim_lib::{Neovim, NeovimApi};
+use neovim_lib::{Neovim, NeovimApiAsync};
+
+use nvim::ErrorReport;
 
 include!(concat!(env!("OUT_DIR"), "/key_map_table.rs"));
 
@@ -31,8 +33,8 @@ pub fn keyval_to_input_string(in_str: &str, in_state: gdk::ModifierType) -> Stri
     if chars.len() == 1 {
         let ch = chars[0];
 
-        // Remove SHIFT
-        if ch.is_ascii() && !ch.is_alphanumeric() {
+        // Remove SHIFT, keyval already contains shifted character
+        if !ch.is_ascii() || !ch.is_alphanumeric() {
             state.remove(gdk::ModifierType::SHIFT_MASK);
         }
     }
@@ -56,8 +58,46 @@ pub fn keyval_to_input_string(in_str: &str, in_state: gdk::ModifierType) -> Stri
     if input.chars().count() > 1 {
         format!("<{}>", input)
     } else {
-        input
+        escape_input(&input)
+    }
+}
+
+/// Modifiers of `nvim_input_mouse`, same letters as in key notation
+pub fn mouse_modifiers(state: gdk::ModifierType) -> String {
+    let mut modifiers = String::new();
+
+    if state.contains(gdk::ModifierType::SHIFT_MASK) {
+        modifiers.push_str("S-");
+    }
+    if state.contains(gdk::ModifierType::CONTROL_MASK) {
+        modifiers.push_str("C-");
     }
+    if state.contains(gdk::ModifierType::MOD1_MASK) {
+        modifiers.push_str("A-");
+    }
+
+    modifiers
+}
+
+/// Escape text for `nvim_input`
+///
+/// `<` is sent as `<lt>`, control and CSI range characters as `<Char-N>`
+/// so raw K_SPECIAL bytes never reach nvim input parser,
+/// all other characters are passed unmodified.
+pub fn escape_input(text: &str) -> String {
+    let mut input = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '<' => input.push_str("<lt>"),
+            '\u{0}'..='\u{1f}' | '\u{7f}'..='\u{9f}' => {
+                input.push_str(&format!("<Char-{}>", ch as u32))
+            }
+            ch => input.push(ch),
+        }
+    }
+
+    input
 }
 
 pub fn convert_key(ev: &EventKey) -> Option<String> {
@@ -76,24 +116,186 @@ pub fn convert_key(ev: &EventKey) -> Option<String> {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zoom {
+    In,
+    Out,
+    Reset,
+}
+
+/// Ctrl++, Ctrl+- and Ctrl+0 change font size instead of going to nvim
+pub fn zoom_shortcut(ev: &EventKey) -> Option<Zoom> {
+    gdk::keyval_name(ev.get_keyval()).and_then(|name| zoom_for_key(&name, ev.get_state()))
+}
+
+fn zoom_for_key(keyval_name: &str, state: gdk::ModifierType) -> Option<Zoom> {
+    if !state.contains(gdk::ModifierType::CONTROL_MASK)
+        || state.contains(gdk::ModifierType::MOD1_MASK)
+    {
+        return None;
+    }
+
+    match keyval_name {
+        "plus" | "equal" | "KP_Add" => Some(Zoom::In),
+        "minus" | "KP_Subtract" => Some(Zoom::Out),
+        "0" | "KP_0" => Some(Zoom::Reset),
+        _ => None,
+    }
+}
+
 pub fn im_input(nvim: &mut Neovim, input: &str) {
     debug!("nvim_input -> {}", input);
 
-    let input: String = input
-        .chars()
-        .map(|ch| {
-            keyval_to_input_string(&ch.to_string(), gdk::ModifierType::empty())
-        })
-        .collect();
-    nvim.input(&input).expect("Error run input command to nvim");
+    nvim.input_async(&escape_input(input))
+        .cb(|r| r.report_err())
+        .call();
+}
+
+/// Composition state of input method.
+///
+/// IM modules emit `preedit-changed` either before or after `commit`,
+/// so commit always ends composition and only following preedit text starts new one.
+#[derive(Default, Debug)]
+pub struct ImPreedit {
+    active: bool,
+}
+
+impl ImPreedit {
+    /// Returns true when composition is started or finished
+    pub fn preedit_changed(&mut self, preedit: &str) -> bool {
+        self.set_active(!preedit.is_empty())
+    }
+
+    /// Returns true when composition is finished
+    pub fn commit(&mut self) -> bool {
+        self.set_active(false)
+    }
+
+    fn set_active(&mut self, active: bool) -> bool {
+        let changed = self.active != active;
+        self.active = active;
+        changed
+    }
+
+    /// Rows popup menu is moved down by, so IM preedit window does not cover it
+    pub fn popup_shift_rows(&self) -> i32 {
+        if self.active {
+            1
+        } else {
+            0
+        }
+    }
 }
 
 pub fn gtk_key_press(nvim: &mut Neovim, ev: &EventKey) -> Inhibit {
     if let Some(input) = convert_key(ev) {
         debug!("nvim_input -> {}", input);
-        nvim.input(&input).expect("Error run input command to nvim");
+        nvim.input_async(&input).cb(|r| r.report_err()).call();
         Inhibit(true)
     } else {
         Inhibit(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mouse_modifiers() {
+        assert_eq!("", mouse_modifiers(gdk::ModifierType::empty()));
+        assert_eq!(
+            "S-C-",
+            mouse_modifiers(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK)
+        );
+        assert_eq!(
+            "A-",
+            mouse_modifiers(gdk::ModifierType::MOD1_MASK | gdk::ModifierType::BUTTON1_MASK)
+        );
+    }
+
+    #[test]
+    fn test_im_preedit_before_commit() {
+        let mut preedit = ImPreedit::default();
+
+        assert!(preedit.preedit_changed("n"));
+        assert!(!preedit.preedit_changed("に"));
+        assert_eq!(1, preedit.popup_shift_rows());
+
+        // preedit cleared, then commit
+        assert!(preedit.preedit_changed(""));
+        assert!(!preedit.commit());
+        assert_eq!(0, preedit.popup_shift_rows());
+    }
+
+    #[test]
+    fn test_im_preedit_after_commit() {
+        let mut preedit = ImPreedit::default();
+
+        preedit.preedit_changed("に");
+        assert!(preedit.commit());
+        assert_eq!(0, preedit.popup_shift_rows());
+
+        // stale empty preedit after commit keeps popup in place
+        assert!(!preedit.preedit_changed(""));
+        assert_eq!(0, preedit.popup_shift_rows());
+
+        // next composition starts right after commit
+        assert!(preedit.preedit_changed("h"));
+        assert_eq!(1, preedit.popup_shift_rows());
+    }
+
+    #[test]
+    fn test_escape_input() {
+        assert_eq!("<lt>", escape_input("<"));
+        assert_eq!("a<lt>b>", escape_input("a<b>"));
+        assert_eq!("|", escape_input("|"));
+        assert_eq!("<Char-155>", escape_input("\u{9b}"));
+        assert_eq!("<Char-128>", escape_input("\u{80}"));
+        assert_eq!("<Char-27>", escape_input("\u{1b}"));
+        assert_eq!("\u{a0}", escape_input("\u{a0}"));
+        assert_eq!("привет", escape_input("привет"));
+        assert_eq!("😀👍", escape_input("😀👍"));
+    }
+
+    #[test]
+    fn test_keyval_to_input_string() {
+        let none = gdk::ModifierType::empty();
+        let ctrl = gdk::ModifierType::CONTROL_MASK;
+        let shift = gdk::ModifierType::SHIFT_MASK;
+
+        assert_eq!("<lt>", keyval_to_input_string("<", none));
+        assert_eq!("<lt>", keyval_to_input_string("<", shift));
+        assert_eq!("<C-lt>", keyval_to_input_string("<", ctrl));
+        assert_eq!("|", keyval_to_input_string("|", shift));
+        assert_eq!("<C-]>", keyval_to_input_string("]", ctrl));
+        assert_eq!("<C-^>", keyval_to_input_string("6", ctrl));
+        assert_eq!("<C-@>", keyval_to_input_string("2", ctrl));
+        assert_eq!("<Char-155>", keyval_to_input_string("\u{9b}", none));
+        assert_eq!("ж", keyval_to_input_string("ж", none));
+        assert_eq!("Ж", keyval_to_input_string("Ж", shift));
+        assert_eq!("<kPlus>", keyval_to_input_string("kPlus", none));
+        assert_eq!("<C-k0>", keyval_to_input_string("k0", ctrl));
+        assert_eq!("<S-kEnter>", keyval_to_input_string("kEnter", shift));
+    }
+
+    #[test]
+    fn test_zoom_for_key() {
+        let ctrl = gdk::ModifierType::CONTROL_MASK;
+        let ctrl_shift = ctrl | gdk::ModifierType::SHIFT_MASK;
+
+        assert_eq!(Some(Zoom::In), zoom_for_key("plus", ctrl_shift));
+        assert_eq!(Some(Zoom::In), zoom_for_key("equal", ctrl));
+        assert_eq!(Some(Zoom::Out), zoom_for_key("KP_Subtract", ctrl));
+        assert_eq!(Some(Zoom::Reset), zoom_for_key("0", ctrl));
+        assert_eq!(None, zoom_for_key("0", gdk::ModifierType::empty()));
+        assert_eq!(None, zoom_for_key("a", ctrl));
+    }
+
+    #[test]
+    fn test_numpad_keys() {
+        assert_eq!(Some(&"kEnter"), KEYVAL_MAP.get("KP_Enter"));
+        assert_eq!(Some(&"kPlus"), KEYVAL_MAP.get("KP_Add"));
+        assert_eq!(Some(&"k0"), KEYVAL_MAP.get("KP_0"));
+    }
+}
@@ -1,5 +1,6 @@
 #![windows_subsystem = "windows"]
 
+extern crate atk_sys;
 extern crate cairo;
 #[macro_use]
 extern crate clap;
@@ -33,6 +34,7 @@ extern crate unicode_segmentation;
 extern crate unicode_width;
 
 extern crate atty;
+extern crate backtrace;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -50,22 +52,32 @@ mod ui_model;
 mod value;
 #[macro_use]
 mod ui;
+mod accessible;
 mod cmd_line;
+mod command_palette;
+mod crash;
 mod cursor;
 mod error;
 mod file_browser;
+mod gui_config;
+mod header_title;
 mod input;
 mod misc;
+mod overscroll;
 mod nvim;
 mod plug_manager;
 mod popup_menu;
 mod project;
 mod render;
+mod session;
 mod settings;
 mod shell;
 mod shell_dlg;
+mod status_bar;
 mod subscriptions;
 mod tabline;
+mod throttle;
+mod version;
 
 use gio::prelude::*;
 use std::cell::RefCell;
@@ -73,11 +85,15 @@ use std::io::Read;
 #[cfg(unix)]
 use unix_daemonize::{daemonize_redirect, ChdirMode};
 
+use gui_config::GuiConfig;
 use ui::Ui;
 
 use clap::{App, Arg, ArgMatches};
 use shell::ShellOptions;
 
+const APP_ID: &str = "org.daa.NeovimGtk";
+const DEBUG_APP_ID: &str = "org.daa.NeovimGtkDebug";
+
 fn main() {
     env_logger::init();
 
@@ -91,6 +107,38 @@ fn main() {
         .arg(Arg::with_name("disable-win-restore")
              .long("disable-win-restore")
              .help("Don't restore window size at start"))
+        .arg(Arg::with_name("geometry")
+             .long("geometry")
+             .help("Initial grid size and window position, COLUMNSxROWS[+X+Y]")
+             .takes_value(true)
+             .validator(|geometry| misc::Geometry::parse(&geometry).map(|_| ())))
+        .arg(Arg::with_name("no-restore")
+             .long("no-restore")
+             .help("Don't restore previous session at start"))
+        .arg(Arg::with_name("config")
+             .long("config")
+             .help("Path to GUI settings file, default is $XDG_CONFIG_HOME/nvim-gtk/config.toml")
+             .takes_value(true))
+        .arg(Arg::with_name("print-config-path")
+             .long("print-config-path")
+             .help("Print path of GUI settings file and exit"))
+        .arg(Arg::with_name("print-server-address")
+             .long("print-server-address")
+             .help("Print --listen address of nvim when it is started, keeps console attached"))
+        .arg(Arg::with_name("role")
+             .long("role")
+             .help("X11 window role, used by window manager rules")
+             .takes_value(true))
+        .arg(Arg::with_name("app-id")
+             .long("app-id")
+             .help("Suffix appended to application id, e.g. 'notes' gives org.daa.NeovimGtk.notes \
+                    Wayland app id, windows with same suffix share one D-Bus instance group")
+             .takes_value(true)
+             .validator(|suffix| misc::app_id(APP_ID, Some(&suffix)).map(|_| ())))
+        .arg(Arg::with_name("profile")
+             .long("profile")
+             .help("Apply named [profile.<name>] section of config.toml")
+             .takes_value(true))
         .arg(Arg::with_name("timeout")
              .long("timeout")
              .default_value("10")
@@ -100,6 +148,24 @@ fn main() {
             Arg::with_name("enable-swap")
                 .long("enable-swap")
                 .help("Enable swap files"),
+        ).arg(
+            Arg::with_name("record-events")
+                .long("record-events")
+                .help("Write all incoming redraw events to given file, overwriting it")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("replay-events")
+                .long("replay-events")
+                .help("Don't start nvim, replay redraw events recorded with --record-events")
+                .takes_value(true)
+                .conflicts_with("record-events"),
+        ).arg(
+            Arg::with_name("replay-speed")
+                .long("replay-speed")
+                .help("Replay speed, 'max' can be used as rendering benchmark")
+                .possible_values(&["original", "max"])
+                .default_value("original")
+                .takes_value(true),
         ).arg(Arg::with_name("files").help("Files to open").multiple(true))
         .arg(
             Arg::with_name("nvim-bin-path")
@@ -113,12 +179,25 @@ fn main() {
                 .multiple(true),
         ).get_matches();
 
+    if matches.is_present("print-config-path") {
+        match GuiConfig::path(matches.value_of("config")) {
+            Ok(path) => println!("{}", path.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    crash::install_handler();
+
     let input_data = RefCell::new(read_piped_input());
 
     #[cfg(unix)]
     {
         // fork to background by default
-        if !matches.is_present("no-fork") {
+        if !matches.is_present("no-fork") && !matches.is_present("print-server-address") {
             daemonize_redirect(
                 Some("/tmp/nvim-gtk_stdout.log"),
                 Some("/tmp/nvim-gtk_stderr.log"),
@@ -131,14 +210,21 @@ fn main() {
 
     glib::set_program_name(Some("NeovimGtk"));
 
-    let app = if cfg!(debug_assertions) {
-        gtk::Application::new(Some("org.daa.NeovimGtkDebug"), app_flags)
+    let base_app_id = if cfg!(debug_assertions) {
+        DEBUG_APP_ID
     } else {
-        gtk::Application::new(Some("org.daa.NeovimGtk"), app_flags)
-    }.expect("Failed to initialize GTK application");
+        APP_ID
+    };
+    // already checked by argument validator
+    let app_id = misc::app_id(base_app_id, matches.value_of("app-id")).unwrap();
+
+    let app = gtk::Application::new(Some(app_id.as_str()), app_flags)
+        .expect("Failed to initialize GTK application");
+
+    app.connect_startup(|_| crash::show_pending_report());
 
     let matches_copy = matches.clone();
-    app.connect_activate(move |app| activate(app, &matches_copy, input_data.replace(None)));
+    app.connect_activate(move |app| activate(app, &matches_copy, input_data.replace(None), true));
 
     let matches_copy = matches.clone();
     app.connect_open(move |app, files, _| open(app, files, &matches_copy));
@@ -146,7 +232,7 @@ fn main() {
     let app_ref = app.clone();
     let matches_copy = matches.clone();
     let new_window_action = gio::SimpleAction::new("new-window", None);
-    new_window_action.connect_activate(move |_, _| activate(&app_ref, &matches_copy, None));
+    new_window_action.connect_activate(move |_, _| activate(&app_ref, &matches_copy, None, false));
     app.add_action(&new_window_action);
 
     gtk::Window::set_default_icon_name("org.daa.NeovimGtk");
@@ -170,15 +256,48 @@ fn open(app: &gtk::Application, files: &[gio::File], matches: &ArgMatches) {
         .filter_map(|f| f.get_path()?.to_str().map(str::to_owned))
         .collect();
 
-    let mut ui = Ui::new(ShellOptions::new(matches, files_list, None));
+    let mut ui = Ui::new(
+        ShellOptions::new(matches, files_list, None),
+        GuiConfig::load_profile(matches.value_of("config"), matches.value_of("profile")),
+    );
 
-    ui.init(app, !matches.is_present("disable-win-restore"));
+    ui.init(
+        app,
+        !matches.is_present("disable-win-restore"),
+        geometry(matches),
+        matches.value_of("role"),
+    );
 }
 
-fn activate(app: &gtk::Application, matches: &ArgMatches, input_data: Option<String>) {
-    let mut ui = Ui::new(ShellOptions::new(matches, Vec::new(), input_data));
+fn activate(
+    app: &gtk::Application,
+    matches: &ArgMatches,
+    input_data: Option<String>,
+    restore_session: bool,
+) {
+    let mut options = ShellOptions::new(matches, Vec::new(), input_data);
+    if !restore_session {
+        options = options.without_session_restore();
+    }
+
+    let mut ui = Ui::new(
+        options,
+        GuiConfig::load_profile(matches.value_of("config"), matches.value_of("profile")),
+    );
+
+    ui.init(
+        app,
+        !matches.is_present("disable-win-restore"),
+        geometry(matches),
+        matches.value_of("role"),
+    );
+}
 
-    ui.init(app, !matches.is_present("disable-win-restore"));
+fn geometry(matches: &ArgMatches) -> Option<misc::Geometry> {
+    // already checked by argument validator
+    matches
+        .value_of("geometry")
+        .map(|geometry| misc::Geometry::parse(geometry).unwrap())
 }
 
 fn read_piped_input() -> Option<String> {
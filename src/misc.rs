@@ -1,5 +1,12 @@
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::mem;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use env_dirs;
+use gtk;
+use gtk::prelude::*;
 
 use percent_encoding::percent_decode;
 use regex::Regex;
@@ -34,6 +41,109 @@ pub fn split_at_comma(source: &str) -> Vec<String> {
     items
 }
 
+/// Font entry of 'guifont' option
+#[derive(Debug, PartialEq)]
+pub struct GuiFont {
+    pub font: String,
+    pub features: Option<String>,
+}
+
+impl GuiFont {
+    /// Features to apply with this font, font without them resets previous ones
+    pub fn features_to_apply(&self) -> String {
+        self.features.clone().unwrap_or_default()
+    }
+}
+
+/// Parse 'guifont' value like `Iosevka:h12:ss01,calt=0,DejaVu Sans Mono 11`
+///
+/// Font name is in pango format, optionally followed by `:hN` size and
+/// OpenType features list. Lowercase feature tags after comma continue
+/// the features list of previous font.
+pub fn parse_guifont(source: &str) -> Vec<GuiFont> {
+    let mut fonts: Vec<GuiFont> = Vec::new();
+
+    for item in split_at_comma(source) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        if let Some(features) = fonts.last_mut().and_then(|f| f.features.as_mut()) {
+            if is_font_feature(item) && !item.chars().any(char::is_uppercase) {
+                features.push(',');
+                features.push_str(item);
+                continue;
+            }
+        }
+
+        let mut parts = item.split(':');
+        let mut font = parts.next().unwrap_or("").trim().to_owned();
+        let mut features: Option<String> = None;
+
+        for part in parts.map(str::trim).filter(|p| !p.is_empty()) {
+            if part.starts_with('h') && part[1..].parse::<f64>().is_ok() {
+                font = format!("{} {}", font, &part[1..]);
+            } else {
+                features = Some(match features {
+                    Some(features) => features + "," + part,
+                    None => part.to_owned(),
+                });
+            }
+        }
+
+        fonts.push(GuiFont { font, features });
+    }
+
+    fonts
+}
+
+/// Check OpenType feature in `tag`, `+tag`, `-tag` or `tag=N` form
+pub fn is_font_feature(feature: &str) -> bool {
+    lazy_static! {
+        static ref FEATURE: Regex = Regex::new(r"^[+-]?[[:alnum:]]{4}(=\d+)?$").unwrap();
+    }
+    FEATURE.is_match(feature)
+}
+
+/// Check that one of comma separated `family` names is installed,
+/// `installed` names are lowercase. Generic names are resolved by fontconfig.
+pub fn is_font_family_known(installed: &HashSet<String>, family: &str) -> bool {
+    const GENERIC_FAMILIES: &[&str] = &["monospace", "sans", "sans-serif", "serif", "system-ui"];
+
+    family
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .any(|name| GENERIC_FAMILIES.contains(&name.as_str()) || installed.contains(&name))
+}
+
+/// Style class unique in process, scopes screen wide css to widgets of one window
+pub fn unique_style_class(prefix: &str) -> String {
+    static STYLE_CLASS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    format!("{}-{}", prefix, STYLE_CLASS_COUNT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Add css provider to screen of `widget`, it is removed when widget is destroyed
+pub fn add_screen_provider<W: IsA<gtk::Widget>>(widget: &W, provider: &gtk::CssProvider) {
+    let screen = match widget.get_screen() {
+        Some(screen) => screen,
+        None => return,
+    };
+
+    gtk::StyleContext::add_provider_for_screen(
+        &screen,
+        provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+
+    let provider = provider.clone();
+    widget.connect_destroy(move |_| {
+        gtk::StyleContext::remove_provider_for_screen(&screen, &provider);
+    });
+}
+
 /// Escape special ASCII characters with a backslash.
 pub fn escape_filename<'t>(filename: &'t str) -> Cow<'t, str> {
     lazy_static! {
@@ -68,6 +178,101 @@ pub fn decode_uri(uri: &str) -> Option<String> {
     }
 }
 
+/// Replace home directory prefix with `~`
+pub fn shorten_home(path: &str) -> String {
+    match env_dirs::home_dir() {
+        Some(home) => replace_home(path, &home),
+        None => path.to_owned(),
+    }
+}
+
+fn replace_home(path: &str, home: &Path) -> String {
+    match Path::new(path).strip_prefix(home) {
+        Ok(rel) if rel.as_os_str().is_empty() => "~".to_owned(),
+        Ok(rel) => Path::new("~").join(rel).to_string_lossy().into_owned(),
+        Err(_) => path.to_owned(),
+    }
+}
+
+/// Window offset from `--geometry`, negative values are from right or bottom edge
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GeometryOffset {
+    pub x: i32,
+    pub y: i32,
+    pub from_right: bool,
+    pub from_bottom: bool,
+}
+
+/// Grid size and window position in X geometry syntax `COLUMNSxROWS[+X+Y]`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Geometry {
+    pub columns: usize,
+    pub rows: usize,
+    pub offset: Option<GeometryOffset>,
+}
+
+impl Geometry {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        lazy_static! {
+            static ref GEOMETRY: Regex =
+                Regex::new(r"^(\d+)x(\d+)(([+-])(\d+)([+-])(\d+))?$").unwrap();
+        }
+
+        let invalid = || format!("Invalid geometry {}, expected COLUMNSxROWS[+X+Y]", source);
+
+        let caps = GEOMETRY.captures(source).ok_or_else(invalid)?;
+        let number = |idx: usize| -> Result<usize, String> {
+            caps[idx].parse().map_err(|_| invalid())
+        };
+
+        let columns = number(1)?;
+        let rows = number(2)?;
+        if columns == 0 || rows == 0 {
+            return Err(invalid());
+        }
+
+        let offset = if caps.get(3).is_some() {
+            Some(GeometryOffset {
+                x: number(5)? as i32,
+                y: number(7)? as i32,
+                from_right: &caps[4] == "-",
+                from_bottom: &caps[6] == "-",
+            })
+        } else {
+            None
+        };
+
+        Ok(Geometry {
+            columns,
+            rows,
+            offset,
+        })
+    }
+}
+
+/// Application id with `--app-id` suffix, it is also Wayland app id and D-Bus name,
+/// so suffix must be valid D-Bus name element
+pub fn app_id(base: &str, suffix: Option<&str>) -> Result<String, String> {
+    let suffix = match suffix {
+        Some(suffix) => suffix,
+        None => return Ok(base.to_owned()),
+    };
+
+    let valid = suffix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && suffix.chars().next().map_or(false, |c| !c.is_ascii_digit());
+    if valid {
+        Ok(format!("{}.{}", base, suffix))
+    } else {
+        Err(format!(
+            "Wrong app id suffix '{}', letters, digits, '_' and '-' are allowed, \
+             first character can't be digit",
+            suffix
+        ))
+    }
+}
+
 /// info text
 pub fn about_comments() -> String {
     format!(
@@ -81,6 +286,32 @@ pub fn about_comments() -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_font_family_known() {
+        let installed: HashSet<String> = ["dejavu sans mono", "fira code"]
+            .iter()
+            .map(|name| (*name).to_owned())
+            .collect();
+
+        assert!(is_font_family_known(&installed, "DejaVu Sans Mono"));
+        assert!(is_font_family_known(&installed, "Hack, Fira Code"));
+        assert!(is_font_family_known(&installed, "Monospace"));
+        assert!(!is_font_family_known(&installed, "DejaVu Sans Mon"));
+        assert!(!is_font_family_known(&installed, ""));
+    }
+
+    #[test]
+    fn test_app_id() {
+        assert_eq!(Ok("org.daa.NeovimGtk".to_owned()), app_id("org.daa.NeovimGtk", None));
+        assert_eq!(
+            Ok("org.daa.NeovimGtk.notes".to_owned()),
+            app_id("org.daa.NeovimGtk", Some("notes"))
+        );
+        assert!(app_id("org.daa.NeovimGtk", Some("1notes")).is_err());
+        assert!(app_id("org.daa.NeovimGtk", Some("my.notes")).is_err());
+        assert!(app_id("org.daa.NeovimGtk", Some("")).is_err());
+    }
+
     #[test]
     fn test_comma_split() {
         let res = split_at_comma("a,b");
@@ -93,4 +324,75 @@ mod tests {
         assert_eq!("a", res[0]);
         assert_eq!("b,c", res[1]);
     }
+
+    #[test]
+    fn test_parse_geometry() {
+        assert_eq!(
+            Geometry {
+                columns: 120,
+                rows: 40,
+                offset: None,
+            },
+            Geometry::parse("120x40").unwrap()
+        );
+
+        let offset = Geometry::parse("80x24-10+20").unwrap().offset.unwrap();
+        assert_eq!((10, 20), (offset.x, offset.y));
+        assert!(offset.from_right);
+        assert!(!offset.from_bottom);
+
+        assert!(Geometry::parse("80x").is_err());
+        assert!(Geometry::parse("0x24").is_err());
+        assert!(Geometry::parse("80x24+10").is_err());
+    }
+
+    #[test]
+    fn test_parse_guifont() {
+        let res = parse_guifont("Iosevka:h12:ss01,calt=0,DejaVu Sans Mono 11");
+        assert_eq!(
+            vec![
+                GuiFont {
+                    font: "Iosevka 12".to_owned(),
+                    features: Some("ss01,calt=0".to_owned()),
+                },
+                GuiFont {
+                    font: "DejaVu Sans Mono 11".to_owned(),
+                    features: None,
+                },
+            ],
+            res
+        );
+
+        let res = parse_guifont("Iosevka:ss01,Hack 10");
+        assert_eq!(2, res.len());
+        assert_eq!("Hack 10", res[1].font);
+    }
+
+    #[test]
+    fn test_guifont_features_reset() {
+        let with_features = parse_guifont("Iosevka:h12:ss01");
+        assert_eq!("ss01", with_features[0].features_to_apply());
+
+        let without_features = parse_guifont("Iosevka:h12");
+        assert_eq!("", without_features[0].features_to_apply());
+    }
+
+    #[test]
+    fn test_font_feature() {
+        assert!(is_font_feature("ss01"));
+        assert!(is_font_feature("-calt"));
+        assert!(is_font_feature("calt=0"));
+        assert!(!is_font_feature("calt off"));
+        assert!(!is_font_feature("liga=x"));
+        assert!(!is_font_feature("ss"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_replace_home() {
+        let home = Path::new("/home/user");
+        assert_eq!("~/project", replace_home("/home/user/project", home));
+        assert_eq!("~", replace_home("/home/user", home));
+        assert_eq!("/tmp", replace_home("/tmp", home));
+    }
 }
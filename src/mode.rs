@@ -60,6 +60,20 @@ pub enum CursorShape {
 }
 
 impl CursorShape {
+    /// Shape set by `GtkGuiCursorShape`, `auto` means shape from mode info
+    pub fn from_override_name(name: &str) -> Result<Option<CursorShape>, String> {
+        match name {
+            "auto" => Ok(None),
+            "block" => Ok(Some(CursorShape::Block)),
+            "line" => Ok(Some(CursorShape::Vertical)),
+            "underline" => Ok(Some(CursorShape::Horizontal)),
+            _ => Err(format!(
+                "Unknown cursor shape {}, use block, line, underline or auto",
+                name
+            )),
+        }
+    }
+
     fn new(shape_code: &Value) -> Result<CursorShape, String> {
         let str_code = shape_code
             .as_str()
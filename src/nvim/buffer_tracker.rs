@@ -0,0 +1,206 @@
+use neovim_lib::{Neovim, Value};
+
+use super::ErrorReport;
+
+/// Change of buffer lines from `nvim_buf_lines_event`
+pub struct LinesEvent {
+    pub buf: Value,
+    pub firstline: i64,
+    pub lastline: i64,
+    pub linedata: Vec<String>,
+}
+
+impl LinesEvent {
+    pub fn parse(params: Vec<Value>) -> Result<Self, String> {
+        if params.len() < 5 {
+            return Err("Wrong nvim_buf_lines_event arguments count".to_owned());
+        }
+
+        let mut params = params.into_iter();
+        let buf = params.next().unwrap();
+        // changedtick
+        params.next();
+        let firstline = params
+            .next()
+            .unwrap()
+            .as_i64()
+            .ok_or_else(|| "Can't convert firstline to int".to_owned())?;
+        let lastline = params
+            .next()
+            .unwrap()
+            .as_i64()
+            .ok_or_else(|| "Can't convert lastline to int".to_owned())?;
+        let linedata = match params.next().unwrap() {
+            Value::Array(lines) => lines
+                .into_iter()
+                .map(|l| match l {
+                    Value::String(s) => Ok(s.into_str().unwrap_or_default()),
+                    _ => Err("Can't convert line to string".to_owned()),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("Can't convert linedata to array".to_owned()),
+        };
+
+        Ok(LinesEvent {
+            buf,
+            firstline,
+            lastline,
+            linedata,
+        })
+    }
+}
+
+/// Content of current buffer, kept in sync with `nvim_buf_attach` notifications
+///
+/// Char and word counts are updated with each change, so they are cheap to read.
+pub struct BufferTracker {
+    buf: Option<Value>,
+    lines: Vec<String>,
+    chars: usize,
+    words: usize,
+}
+
+impl BufferTracker {
+    pub fn new() -> Self {
+        BufferTracker {
+            buf: None,
+            lines: vec![],
+            chars: 0,
+            words: 0,
+        }
+    }
+
+    /// Detach from previous buffer and attach to current one,
+    /// attach sends whole buffer as first lines event
+    pub fn attach_current(&mut self, nvim: &mut Neovim) {
+        if let Some(buf) = self.buf.take() {
+            nvim.session
+                .call_async::<Value>("nvim_buf_detach", vec![buf])
+                .cb(|r| r.report_err())
+                .call();
+        }
+        self.clear();
+
+        let args = vec![0.into(), true.into(), Value::Map(vec![])];
+        nvim.session
+            .call_async::<Value>("nvim_buf_attach", args)
+            .cb(|r| r.report_err())
+            .call();
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.chars = 0;
+        self.words = 0;
+    }
+
+    pub fn on_lines(&mut self, event: LinesEvent) {
+        // initial event contains whole buffer content
+        if event.lastline == -1 {
+            self.buf = Some(event.buf);
+            self.clear();
+        } else if self.buf.as_ref() != Some(&event.buf) {
+            return;
+        }
+
+        let len = self.lines.len();
+        let first = (event.firstline.max(0) as usize).min(len);
+        let last = if event.lastline < 0 {
+            len
+        } else {
+            (event.lastline as usize).min(len).max(first)
+        };
+
+        for line in &event.linedata {
+            self.chars += char_count(line);
+            self.words += word_count(line);
+        }
+        for line in self.lines.splice(first..last, event.linedata) {
+            self.chars -= char_count(&line);
+            self.words -= word_count(&line);
+        }
+    }
+
+    pub fn on_detach(&mut self, buf: &Value) {
+        if self.buf.as_ref() == Some(buf) {
+            self.buf = None;
+            self.clear();
+        }
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.chars
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words
+    }
+}
+
+fn char_count(line: &str) -> usize {
+    line.chars().count()
+}
+
+fn word_count(line: &str) -> usize {
+    line.split_whitespace().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines_event(firstline: i64, lastline: i64, lines: &[&str]) -> LinesEvent {
+        LinesEvent {
+            buf: 1.into(),
+            firstline,
+            lastline,
+            linedata: lines.iter().map(|&l| l.to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_lines_update() {
+        let mut tracker = BufferTracker::new();
+        tracker.on_lines(lines_event(0, -1, &["one two", "three"]));
+        assert_eq!(3, tracker.word_count());
+        assert_eq!(12, tracker.char_count());
+
+        tracker.on_lines(lines_event(1, 2, &["four five", "six"]));
+        assert_eq!(vec!["one two", "four five", "six"], tracker.lines);
+        assert_eq!(4, tracker.word_count());
+
+        tracker.on_lines(lines_event(0, 1, &[]));
+        assert_eq!(vec!["four five", "six"], tracker.lines);
+        assert_eq!(3, tracker.word_count());
+        assert_eq!(12, tracker.char_count());
+    }
+
+    #[test]
+    fn test_other_buffer_ignored() {
+        let mut tracker = BufferTracker::new();
+        tracker.on_lines(lines_event(0, -1, &["one"]));
+
+        let mut event = lines_event(0, 1, &[]);
+        event.buf = 2.into();
+        tracker.on_lines(event);
+        assert_eq!(1, tracker.word_count());
+    }
+
+    #[test]
+    fn test_parse_lines_event() {
+        let params = vec![
+            1.into(),
+            5.into(),
+            0.into(),
+            (-1).into(),
+            Value::Array(vec!["line".into()]),
+            false.into(),
+        ];
+        let event = LinesEvent::parse(params).unwrap();
+        assert_eq!(0, event.firstline);
+        assert_eq!(-1, event.lastline);
+        assert_eq!(vec!["line".to_owned()], event.linedata);
+
+        assert!(LinesEvent::parse(vec![1.into()]).is_err());
+    }
+}
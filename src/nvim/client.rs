@@ -1,24 +1,85 @@
 use std::cell::{Cell, RefCell, RefMut};
+use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use super::ErrorReport;
 use neovim_lib::{Neovim, NeovimApi};
 
-#[derive(Clone, Copy, PartialEq)]
-enum NeovimClientState {
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NeovimClientState {
     Uninitialized,
     InitInProgress,
     Initialized,
     Error,
 }
 
+impl fmt::Display for NeovimClientState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match *self {
+            NeovimClientState::Uninitialized => "uninitialized",
+            NeovimClientState::InitInProgress => "initialization in progress",
+            NeovimClientState::Initialized => "initialized",
+            NeovimClientState::Error => "initialization error",
+        };
+        write!(f, "{}", state)
+    }
+}
+
 pub enum NeovimRef<'a> {
     SingleThreaded(RefMut<'a, Neovim>),
     MultiThreaded(MutexGuard<'a, Option<Neovim>>),
 }
 
+/// Result of nvim access, tells why nvim is not available
+pub enum NvimAccess<'a> {
+    Ready(NeovimRef<'a>),
+    /// Not started yet, still starting or already exited
+    NotReady(NeovimClientState),
+    /// Start failed
+    Error,
+}
+
+impl<'a> NvimAccess<'a> {
+    pub fn ready(self) -> Option<NeovimRef<'a>> {
+        match self {
+            NvimAccess::Ready(nvim) => Some(nvim),
+            _ => None,
+        }
+    }
+
+    /// Nvim reference or message that can be shown to user
+    pub fn into_result(self) -> Result<NeovimRef<'a>, String> {
+        match self {
+            NvimAccess::Ready(nvim) => Ok(nvim),
+            NvimAccess::NotReady(state) => Err(format!("Nvim not available: {}", state)),
+            NvimAccess::Error => Err("Nvim not available: start failed".to_owned()),
+        }
+    }
+}
+
 impl<'a> NeovimRef<'a> {
+    /// Access nvim of client, reference is available
+    /// during initialization too, so startup requests can be sent
+    pub fn try_from_client(client: &'a NeovimClient) -> NvimAccess<'a> {
+        let state = client.state();
+        let nvim = client.nvim.borrow_mut();
+        let nvim = if nvim.is_some() {
+            Some(NeovimRef::from_nvim(RefMut::map(nvim, |n| {
+                n.as_mut().unwrap()
+            })))
+        } else {
+            client.nvim_async.borrow()
+        };
+
+        match (nvim, state) {
+            (Some(nvim), _) => NvimAccess::Ready(nvim),
+            (None, NeovimClientState::Error) => NvimAccess::Error,
+            (None, state) => NvimAccess::NotReady(state),
+        }
+    }
+
     fn from_nvim(nvim: RefMut<'a, Neovim>) -> Self {
         NeovimRef::SingleThreaded(nvim)
     }
@@ -77,15 +138,48 @@ impl<'a> DerefMut for NeovimRef<'a> {
 
 pub struct NeovimClientAsync {
     nvim: Arc<Mutex<Option<Neovim>>>,
+    api_level: Arc<AtomicUsize>,
+    nvim_version: Arc<Mutex<Option<(u32, u32, u32)>>>,
+    channel_id: Arc<Mutex<Option<u64>>>,
 }
 
 impl NeovimClientAsync {
     fn new() -> Self {
         NeovimClientAsync {
             nvim: Arc::new(Mutex::new(None)),
+            api_level: Arc::new(AtomicUsize::new(0)),
+            nvim_version: Arc::new(Mutex::new(None)),
+            channel_id: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// API level of nvim, detected with `nvim_get_api_info` on start
+    pub fn set_api_level(&self, api_level: u64) {
+        self.api_level.store(api_level as usize, Ordering::Relaxed);
+    }
+
+    pub fn api_level(&self) -> u64 {
+        self.api_level.load(Ordering::Relaxed) as u64
+    }
+
+    /// Major, minor and patch version of nvim from `nvim_get_api_info`
+    pub fn set_nvim_version(&self, version: (u32, u32, u32)) {
+        *self.nvim_version.lock().unwrap() = Some(version);
+    }
+
+    pub fn nvim_version(&self) -> Option<(u32, u32, u32)> {
+        *self.nvim_version.lock().unwrap()
+    }
+
+    /// Channel of this ui connection, first item of `nvim_get_api_info`
+    pub fn set_channel_id(&self, channel_id: u64) {
+        *self.channel_id.lock().unwrap() = Some(channel_id);
+    }
+
+    pub fn channel_id(&self) -> Option<u64> {
+        *self.channel_id.lock().unwrap()
+    }
+
     pub fn borrow(&self) -> Option<NeovimRef> {
         NeovimRef::from_nvim_async(self)
     }
@@ -99,6 +193,9 @@ impl Clone for NeovimClientAsync {
     fn clone(&self) -> Self {
         NeovimClientAsync {
             nvim: self.nvim.clone(),
+            api_level: self.api_level.clone(),
+            nvim_version: self.nvim_version.clone(),
+            channel_id: self.channel_id.clone(),
         }
     }
 }
@@ -150,6 +247,35 @@ impl NeovimClient {
         self.state.set(NeovimClientState::InitInProgress);
     }
 
+    pub fn api_level(&self) -> u64 {
+        self.nvim_async.api_level()
+    }
+
+    /// Version of connected nvim, known after initialization
+    pub fn connected_neovim_version(&self) -> Option<(u32, u32, u32)> {
+        self.nvim_async.nvim_version()
+    }
+
+    /// Channel id for `rpcnotify()` calls targeting this GUI,
+    /// `None` until client reaches `Initialized` state
+    pub fn channel_id(&self) -> Option<u64> {
+        if self.is_initialized() {
+            self.nvim_async.channel_id()
+        } else {
+            None
+        }
+    }
+
+    /// Check connected nvim version, false while version is unknown
+    pub fn nvim_version_at_least(&self, min_version: (u32, u32, u32)) -> bool {
+        self.connected_neovim_version()
+            .map_or(false, |version| version >= min_version)
+    }
+
+    pub fn state(&self) -> NeovimClientState {
+        self.state.get()
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.state.get() == NeovimClientState::Initialized
     }
@@ -175,14 +301,12 @@ impl NeovimClient {
         }
     }
 
+    pub fn try_access(&self) -> NvimAccess {
+        NeovimRef::try_from_client(self)
+    }
+
+    /// Shortcut for callers that don't report why nvim is not available
     pub fn nvim(&self) -> Option<NeovimRef> {
-        let nvim = self.nvim.borrow_mut();
-        if nvim.is_some() {
-            Some(NeovimRef::from_nvim(RefMut::map(nvim, |n| {
-                n.as_mut().unwrap()
-            })))
-        } else {
-            self.nvim_async.borrow()
-        }
+        self.try_access().ready()
     }
 }
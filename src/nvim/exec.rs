@@ -0,0 +1,118 @@
+use neovim_lib::{Neovim, NeovimApi, Value};
+
+use super::client::NeovimClient;
+
+/// First API level with `nvim_exec2`, nvim 0.9
+const NVIM_EXEC2_API_LEVEL: u64 = 11;
+/// First API level with `nvim_exec`, nvim 0.5
+const NVIM_EXEC_API_LEVEL: u64 = 7;
+
+/// Way to capture command output, newest one supported by nvim
+#[derive(Debug, PartialEq)]
+enum ExecMethod {
+    Exec2,
+    Exec,
+    Execute,
+}
+
+impl ExecMethod {
+    fn from_api_level(api_level: u64) -> Self {
+        if api_level >= NVIM_EXEC2_API_LEVEL {
+            ExecMethod::Exec2
+        } else if api_level >= NVIM_EXEC_API_LEVEL {
+            ExecMethod::Exec
+        } else {
+            ExecMethod::Execute
+        }
+    }
+}
+
+/// Execute ex command and return its output
+#[allow(dead_code)]
+pub fn nvim_execute(nvim_client: &NeovimClient, cmd: &str) -> Result<String, String> {
+    let api_level = nvim_client.api_level();
+    let mut nvim = nvim_client.try_access().into_result()?;
+
+    execute(&mut *nvim, api_level, cmd)
+}
+
+/// Capture output with best method supported by given API level
+fn execute(nvim: &mut Neovim, api_level: u64, cmd: &str) -> Result<String, String> {
+    let output = match ExecMethod::from_api_level(api_level) {
+        ExecMethod::Exec2 => {
+            let opts = Value::Map(vec![("output".into(), true.into())]);
+            let result = nvim
+                .session
+                .call("nvim_exec2", vec![cmd.into(), opts])
+                .map_err(|e| e.to_string())?;
+
+            result
+                .as_map()
+                .and_then(|map| {
+                    map.iter()
+                        .find(|&&(ref k, _)| k.as_str() == Some("output"))
+                        .map(|&(_, ref v)| v.clone())
+                })
+                .unwrap_or_else(|| "".into())
+        }
+        ExecMethod::Exec => nvim
+            .session
+            .call("nvim_exec", vec![cmd.into(), true.into()])
+            .map_err(|e| e.to_string())?,
+        ExecMethod::Execute => nvim
+            .call_function("execute", vec![cmd.into()])
+            .map_err(|e| e.to_string())?,
+    };
+
+    output
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| format!("Wrong output of command {}: {:?}", cmd, output))
+}
+
+/// API level from `nvim_get_api_info` metadata
+pub fn api_level(metadata: &Value) -> Option<u64> {
+    metadata
+        .as_map()?
+        .iter()
+        .find(|&&(ref k, _)| k.as_str() == Some("version"))?
+        .1
+        .as_map()?
+        .iter()
+        .find(|&&(ref k, _)| k.as_str() == Some("api_level"))?
+        .1
+        .as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_level() {
+        let metadata = Value::Map(vec![(
+            "version".into(),
+            Value::Map(vec![("api_level".into(), 11.into())]),
+        )]);
+        assert_eq!(Some(11), api_level(&metadata));
+        assert_eq!(None, api_level(&Value::Map(vec![])));
+    }
+
+    #[test]
+    fn test_exec_method_fallback() {
+        let metadata = |level: u64| {
+            Value::Map(vec![(
+                "version".into(),
+                Value::Map(vec![("api_level".into(), level.into())]),
+            )])
+        };
+        let method = |level| ExecMethod::from_api_level(api_level(&metadata(level)).unwrap());
+
+        assert_eq!(ExecMethod::Exec2, method(12));
+        assert_eq!(ExecMethod::Exec2, method(11));
+        assert_eq!(ExecMethod::Exec, method(10));
+        assert_eq!(ExecMethod::Exec, method(7));
+        assert_eq!(ExecMethod::Execute, method(6));
+        assert_eq!(ExecMethod::Execute, method(0));
+    }
+}
@@ -1,6 +1,7 @@
+use std::fmt;
 use std::result;
 
-use neovim_lib::CallError;
+use neovim_lib::{CallError, Value};
 
 pub trait ErrorReport<T> {
     fn report_err(&self);
@@ -20,3 +21,52 @@ impl<T> ErrorReport<T> for result::Result<T, CallError> {
         self.ok()
     }
 }
+
+/// `nvim_error_event` notification, nvim sends it when call made without
+/// waiting for reply (e.g. `nvim_ui_try_resize` notification) fails
+#[derive(Debug, PartialEq)]
+pub struct ErrorEvent {
+    pub kind: u64,
+    pub message: String,
+}
+
+impl ErrorEvent {
+    pub fn parse(params: Vec<Value>) -> result::Result<Self, String> {
+        let mut params = params.into_iter();
+        let kind = params
+            .next()
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| "Error type expected".to_owned())?;
+        let message = params
+            .next()
+            .and_then(|v| v.as_str().map(str::to_owned))
+            .ok_or_else(|| "Error message expected".to_owned())?;
+
+        Ok(ErrorEvent { kind, message })
+    }
+}
+
+impl fmt::Display for ErrorEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            0 => "Exception",
+            1 => "Validation",
+            _ => "Unknown",
+        };
+        write!(f, "{} error: {}", kind, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_event_parse() {
+        let event = ErrorEvent::parse(vec![1.into(), "Invalid buffer id".into()]).unwrap();
+        assert_eq!(1, event.kind);
+        assert_eq!("Validation error: Invalid buffer id", event.to_string());
+
+        assert!(ErrorEvent::parse(vec!["wrong".into()]).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use neovim_lib::{Neovim, Value};
+
+/// Names of other GUIs that are found in their plugins autocommands
+const GUI_PLUGINS: &[&str] = &[
+    "nvim-qt",
+    "nvim_qt",
+    "neovide",
+    "goneovim",
+    "gnvim",
+    "fvim",
+    "nvui",
+    "firenvim",
+];
+
+/// Other GUIs plugins that setup itself on VimEnter
+pub fn conflicting_gui_plugins(nvim: &mut Neovim) -> Result<Vec<&'static str>, String> {
+    let opts = Value::Map(vec![("event".into(), "VimEnter".into())]);
+    let autocmds = nvim
+        .session
+        .call("nvim_get_autocmds", vec![opts])
+        .map_err(|e| e.to_string())?;
+
+    autocmds
+        .as_array()
+        .map(|autocmds| find_gui_plugins(autocmds))
+        .ok_or_else(|| format!("Wrong nvim_get_autocmds response: {:?}", autocmds))
+}
+
+fn find_gui_plugins(autocmds: &[Value]) -> Vec<&'static str> {
+    let texts: Vec<String> = autocmds
+        .iter()
+        .filter_map(Value::as_map)
+        .flat_map(|autocmd| autocmd.iter())
+        .filter(|&&(ref k, _)| match k.as_str() {
+            Some("group_name") | Some("desc") | Some("command") | Some("callback") => true,
+            _ => false,
+        })
+        .filter_map(|&(_, ref v)| v.as_str())
+        .map(str::to_lowercase)
+        .collect();
+
+    GUI_PLUGINS
+        .iter()
+        .cloned()
+        .filter(|plugin| texts.iter().any(|text| text.contains(plugin)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_gui_plugins() {
+        let autocmd = |key: &str, val: &str| Value::Map(vec![(key.into(), val.into())]);
+        let autocmds = vec![
+            autocmd("group_name", "Neovide"),
+            autocmd("command", "call s:restore()"),
+            autocmd("pattern", "firenvim"),
+        ];
+
+        assert_eq!(vec!["neovide"], find_gui_plugins(&autocmds));
+    }
+}
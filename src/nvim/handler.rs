@@ -1,19 +1,131 @@
+use std::collections::VecDeque;
+use std::mem;
 use std::result;
-use std::sync::{mpsc, Arc};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use neovim_lib::{Handler, Value};
 
 use ui::UiMutex;
+use crash::{self, CrashAction};
 use shell;
 use glib;
 
+use super::buffer_tracker::LinesEvent;
+use super::ext::ErrorEvent;
+use super::recorder::EventRecorder;
+use super::rpc_log::RpcLog;
 use super::repaint_mode::RepaintMode;
-use super::redraw_handler;
+use super::redraw_handler::{self, RedrawEvent};
+
+/// Max time spent to apply redraw batches in one main loop iteration
+const REDRAW_BUDGET: Duration = Duration::from_millis(8);
+/// Max time nvim waits for gui request reply, e.g. clipboard content
+const GUI_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Batch is a set of events that ends with `flush`, grid is consistent
+/// only between batches, so paint never happens in the middle of batch
+struct RedrawQueue {
+    batches: VecDeque<Vec<RedrawEvent>>,
+    /// Events received after last flush
+    incomplete: Vec<RedrawEvent>,
+    /// Old nvim does not send flush, end of notification is end of batch then
+    flush_supported: bool,
+    scheduled: bool,
+    skipped_events: usize,
+}
+
+impl RedrawQueue {
+    fn new() -> Self {
+        RedrawQueue {
+            batches: VecDeque::new(),
+            incomplete: Vec::new(),
+            flush_supported: false,
+            scheduled: false,
+            skipped_events: 0,
+        }
+    }
+
+    /// Push events of redraw notification,
+    /// return true in case queue processing must be scheduled
+    fn push(&mut self, events: Vec<RedrawEvent>) -> bool {
+        if !self.flush_supported {
+            self.flush_supported = events.iter().any(RedrawEvent::is_flush);
+            if !self.flush_supported {
+                return self.push_batch(events);
+            }
+        }
+
+        let mut schedule = false;
+        for event in events {
+            if event.is_flush() {
+                if !self.incomplete.is_empty() {
+                    let batch = mem::replace(&mut self.incomplete, Vec::new());
+                    schedule |= self.push_batch(batch);
+                }
+            } else {
+                self.incomplete.push(event);
+            }
+        }
+        schedule
+    }
+
+    fn push_batch(&mut self, batch: Vec<RedrawEvent>) -> bool {
+        if supersedes(&batch) {
+            while self.batches.back().map_or(false, |b| is_overwrite_only(b)) {
+                let dropped = self.batches.pop_back().unwrap();
+                self.skipped_events += dropped.len();
+                debug!("Redraw events skipped: {}", self.skipped_events);
+            }
+        }
+
+        self.batches.push_back(batch);
+
+        if self.scheduled {
+            false
+        } else {
+            self.scheduled = true;
+            true
+        }
+    }
+
+    /// Drop pending batches, whole grid is requested again
+    fn clear(&mut self) {
+        self.batches.clear();
+        self.incomplete.clear();
+        self.scheduled = false;
+    }
+
+    fn pop(&mut self) -> Option<Vec<RedrawEvent>> {
+        let batch = self.batches.pop_front();
+        if batch.is_none() {
+            self.scheduled = false;
+        }
+        batch
+    }
+}
+
+/// Batch that contains only grid content changes,
+/// so it can be skipped in case next batch overwrite whole grid
+fn is_overwrite_only(batch: &[RedrawEvent]) -> bool {
+    batch.iter().all(RedrawEvent::is_overwrite_only)
+}
+
+/// Batch clears whole grid and set cursor position before any other grid change
+fn supersedes(batch: &[RedrawEvent]) -> bool {
+    match (batch.get(0), batch.get(1)) {
+        (Some(&RedrawEvent::Clear), Some(&RedrawEvent::CursorGoto(..))) => true,
+        _ => false,
+    }
+}
 
 pub struct NvimHandler {
     shell: Arc<UiMutex<shell::State>>,
 
     delayed_redraw_event_id: Arc<UiMutex<Option<glib::SourceId>>>,
+    redraw_queue: Arc<Mutex<RedrawQueue>>,
+    recorder: Option<EventRecorder>,
+    rpc_log: Option<RpcLog>,
 }
 
 impl NvimHandler {
@@ -21,10 +133,17 @@ impl NvimHandler {
         NvimHandler {
             shell,
             delayed_redraw_event_id: Arc::new(UiMutex::new(None)),
+            redraw_queue: Arc::new(Mutex::new(RedrawQueue::new())),
+            recorder: None,
+            rpc_log: RpcLog::from_env(),
         }
     }
 
-    pub fn schedule_redraw_event(&self, event: Value) {
+    pub fn set_recorder(&mut self, recorder: EventRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn schedule_redraw_event(&self, event: RedrawEvent) {
         let shell = self.shell.clone();
         let delayed_redraw_event_id = self.delayed_redraw_event_id.clone();
 
@@ -34,9 +153,7 @@ impl NvimHandler {
                 clone!(shell, event, delayed_redraw_event_id => move || {
                 delayed_redraw_event_id.replace(None);
 
-                if let Err(msg) = call_redraw_handler(vec![event.clone()], &shell) {
-                    error!("Error call function: {}", msg);
-                }
+                call_redraw_handler(vec![event.clone()], &shell);
 
                 glib::Continue(false)
             }),
@@ -60,12 +177,20 @@ impl NvimHandler {
         });
     }
 
-    fn nvim_cb(&self, method: &str, mut params: Vec<Value>) {
+    fn nvim_cb(&self, method: &str, params: Vec<Value>) {
         match method {
             "redraw" => {
-                redraw_handler::remove_or_delay_uneeded_events(self, &mut params);
+                if let Some(ref recorder) = self.recorder {
+                    recorder.record(&params);
+                }
+                let mut events = redraw_handler::parse_redraw(params);
+                redraw_handler::remove_or_delay_uneeded_events(self, &mut events);
 
-                self.safe_call(move |ui| call_redraw_handler(params, ui));
+                if self.redraw_queue.lock().unwrap().push(events) {
+                    let shell = self.shell.clone();
+                    let redraw_queue = self.redraw_queue.clone();
+                    glib::idle_add(move || process_redraw_queue(&shell, &redraw_queue));
+                }
             }
             "Gui" => {
                 if !params.is_empty() {
@@ -73,16 +198,18 @@ impl NvimHandler {
                     if let Some(ev_name) = params_iter.next() {
                         if let Value::String(ev_name) = ev_name {
                             let args = params_iter.collect();
-                            self.safe_call(move |ui| {
-                                let ui = &mut ui.borrow_mut();
-                                redraw_handler::call_gui_event(
-                                    ui,
-                                    ev_name
-                                        .as_str()
-                                        .ok_or_else(|| "Event name does not exists")?,
-                                    args,
-                                )?;
-                                ui.on_redraw(&RepaintMode::All);
+                            self.safe_call(move |ui_arc| {
+                                {
+                                    let ui = &mut ui_arc.borrow_mut();
+                                    redraw_handler::call_gui_event(
+                                        ui,
+                                        ev_name
+                                            .as_str()
+                                            .ok_or_else(|| "Event name does not exists")?,
+                                        args,
+                                    )?;
+                                }
+                                schedule_repaint(ui_arc, RepaintMode::All);
                                 Ok(())
                             });
                         } else {
@@ -95,6 +222,33 @@ impl NvimHandler {
                     error!("Unsupported event {:?}", params);
                 }
             }
+            "nvim_buf_lines_event" => match LinesEvent::parse(params) {
+                Ok(event) => self.safe_call(move |ui| {
+                    let ui = ui.borrow();
+                    ui.buffer_tracker().on_lines(event);
+                    ui.notify_buffer_stats();
+                    Ok(())
+                }),
+                Err(msg) => error!("Error parse nvim_buf_lines_event: {}", msg),
+            },
+            "nvim_buf_detach_event" => {
+                if let Some(buf) = params.into_iter().next() {
+                    self.safe_call(move |ui| {
+                        let ui = ui.borrow();
+                        ui.buffer_tracker().on_detach(&buf);
+                        ui.notify_buffer_stats();
+                        Ok(())
+                    });
+                }
+            }
+            "nvim_buf_changedtick_event" => (),
+            "nvim_error_event" => match ErrorEvent::parse(params) {
+                Ok(event) => self.safe_call(move |ui| {
+                    ui.borrow().show_nvim_error(&event);
+                    Ok(())
+                }),
+                Err(msg) => error!("Error parse nvim_error_event: {}", msg),
+            },
             "subscription" => {
                 self.safe_call(move |ui| {
                     let ui = &ui.borrow();
@@ -117,38 +271,45 @@ impl NvimHandler {
                             let args = params_iter.collect();
                             let (sender, receiver) = mpsc::channel();
                             self.safe_call(move |ui| {
-                                sender
-                                    .send(redraw_handler::call_gui_request(
-                                        &ui.clone(),
-                                        req_name
-                                            .as_str()
-                                            .ok_or_else(|| "Event name does not exists")?,
-                                        &args,
-                                    ))
-                                    .unwrap();
-                                {
-                                    let ui = &mut ui.borrow_mut();
-                                    ui.on_redraw(&RepaintMode::All);
-                                }
+                                redraw_handler::reply_gui_request(
+                                    &ui.clone(),
+                                    req_name
+                                        .as_str()
+                                        .ok_or_else(|| "Event name does not exists")?,
+                                    args,
+                                    sender,
+                                );
+                                schedule_repaint(ui, RepaintMode::All);
                                 Ok(())
                             });
-                            Ok(receiver.recv().unwrap()?)
+                            // sender is dropped without reply in case request can't be called,
+                            // nvim must not hang on unresponsive clipboard owner
+                            match receiver.recv_timeout(GUI_REQUEST_TIMEOUT) {
+                                Ok(result) => result,
+                                Err(mpsc::RecvTimeoutError::Timeout) => {
+                                    warn!("Gui request timed out");
+                                    Err(Value::from("Request timed out"))
+                                }
+                                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                                    Err(Value::from("Request was not processed"))
+                                }
+                            }
                         } else {
                             error!("Unsupported request");
-                            Err(Value::Nil)
+                            Err(Value::from("Request name must be a string"))
                         }
                     } else {
                         error!("Request name does not exist");
-                        Err(Value::Nil)
+                        Err(Value::from("Request name does not exist"))
                     }
                 } else {
                     error!("Unsupported request {:?}", params);
-                    Err(Value::Nil)
+                    Err(Value::from("Request name does not exist"))
                 }
             }
             _ => {
                 error!("Request {}({:?})", method, params);
-                Err(Value::Nil)
+                Err(Value::from(format!("Unsupported request {}", method)))
             }
         }
     }
@@ -161,41 +322,86 @@ impl NvimHandler {
     }
 }
 
-fn call_redraw_handler(
-    params: Vec<Value>,
+fn process_redraw_queue(
+    ui: &Arc<UiMutex<shell::State>>,
+    redraw_queue: &Mutex<RedrawQueue>,
+) -> glib::Continue {
+    match crash::guard(|| apply_redraw_queue(ui, redraw_queue)) {
+        Ok(has_more) => glib::Continue(has_more),
+        Err(CrashAction::Continue) => {
+            redraw_queue.lock().unwrap().clear();
+            ui.borrow_mut().recover_after_panic();
+            glib::Continue(false)
+        }
+        Err(CrashAction::Quit) => glib::Continue(false),
+    }
+}
+
+/// Return true in case queue has more batches
+fn apply_redraw_queue(
     ui: &Arc<UiMutex<shell::State>>,
-) -> result::Result<(), String> {
+    redraw_queue: &Mutex<RedrawQueue>,
+) -> bool {
     let ui = &mut ui.borrow_mut();
     let mut repaint_mode = RepaintMode::Nothing;
+    let start = Instant::now();
 
-    for ev in params {
-        if let Value::Array(ev_args) = ev {
-            let mut args_iter = ev_args.into_iter();
-            let ev_name = args_iter.next();
-            if let Some(ev_name) = ev_name {
-                if let Some(ev_name) = ev_name.as_str() {
-                    for local_args in args_iter {
-                        let args = match local_args {
-                            Value::Array(ar) => ar,
-                            _ => vec![],
-                        };
-                        let call_reapint_mode = redraw_handler::call(ui, &ev_name, args)?;
-                        repaint_mode = repaint_mode.join(call_reapint_mode);
-                    }
-                } else {
-                    error!("Unsupported event");
-                }
-            } else {
-                error!("Event name does not exists");
+    let mut has_more = true;
+    while start.elapsed() < REDRAW_BUDGET {
+        let batch = redraw_queue.lock().unwrap().pop();
+        match batch {
+            Some(batch) => repaint_mode = repaint_mode.join(apply_redraw_events(ui, batch)),
+            None => {
+                has_more = false;
+                break;
             }
-        } else {
-            error!("Unsupported event type {:?}", ev);
         }
     }
 
+    if let Some(pending) = ui.take_pending_repaint() {
+        repaint_mode = repaint_mode.join(pending);
+    }
     ui.on_redraw(&repaint_mode);
     ui.redraw_handler_finish();
-    Ok(())
+
+    has_more
+}
+
+/// Repaint requested by several gui events in a row is applied once,
+/// or together with next redraw batch
+fn schedule_repaint(ui: &Arc<UiMutex<shell::State>>, mode: RepaintMode) {
+    if ui.borrow_mut().queue_repaint(mode) {
+        let ui = ui.clone();
+        glib::idle_add(move || {
+            ui.borrow_mut().flush_repaint();
+            glib::Continue(false)
+        });
+    }
+}
+
+fn call_redraw_handler(events: Vec<RedrawEvent>, ui: &Arc<UiMutex<shell::State>>) {
+    let res = crash::guard(|| {
+        let ui = &mut ui.borrow_mut();
+        let repaint_mode = apply_redraw_events(ui, events);
+
+        ui.on_redraw(&repaint_mode);
+        ui.redraw_handler_finish();
+    });
+
+    if let Err(CrashAction::Continue) = res {
+        ui.borrow_mut().recover_after_panic();
+    }
+}
+
+fn apply_redraw_events(ui: &mut shell::State, events: Vec<RedrawEvent>) -> RepaintMode {
+    // recorded before apply, so event that panics is in crash report
+    crash::record_events(events.iter().map(RedrawEvent::summary));
+
+    events
+        .into_iter()
+        .fold(RepaintMode::Nothing, |repaint_mode, event| {
+            repaint_mode.join(redraw_handler::call(ui, event))
+        })
 }
 
 fn safe_call<F>(shell: Arc<UiMutex<shell::State>>, cb: F)
@@ -204,8 +410,11 @@ where
 {
     let mut cb = Some(cb);
     glib::idle_add(move || {
-        if let Err(msg) = cb.take().unwrap()(&shell) {
-            error!("Error call function: {}", msg);
+        let cb = cb.take().unwrap();
+        match crash::guard(|| cb(&shell)) {
+            Ok(Err(msg)) => error!("Error call function: {}", msg),
+            Ok(Ok(())) | Err(CrashAction::Quit) => (),
+            Err(CrashAction::Continue) => shell.borrow_mut().recover_after_panic(),
         }
         glib::Continue(false)
     });
@@ -213,10 +422,93 @@ where
 
 impl Handler for NvimHandler {
     fn handle_notify(&mut self, name: &str, args: Vec<Value>) {
+        if let Some(ref rpc_log) = self.rpc_log {
+            rpc_log.notification(name, &args);
+        }
         self.nvim_cb(name, args);
     }
 
     fn handle_request(&mut self, name: &str, args: Vec<Value>) -> result::Result<Value, Value> {
-        self.nvim_cb_req(name, args)
+        match self.rpc_log.clone() {
+            Some(rpc_log) => {
+                rpc_log.request(name, &args);
+                let result = self.nvim_cb_req(name, args);
+                rpc_log.response(name, &result);
+                result
+            }
+            None => self.nvim_cb_req(name, args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_overwritten_batches() {
+        let mut queue = RedrawQueue::new();
+
+        assert!(queue.push(vec![
+            RedrawEvent::HighlightSet(Default::default()),
+            RedrawEvent::Put("a".to_owned()),
+        ]));
+        assert!(!queue.push(vec![
+            RedrawEvent::CursorGoto(0, 0),
+            RedrawEvent::Put("a".to_owned()),
+            RedrawEvent::Put("b".to_owned()),
+        ]));
+        assert!(!queue.push(vec![
+            RedrawEvent::Clear,
+            RedrawEvent::CursorGoto(0, 0),
+            RedrawEvent::Put("a".to_owned()),
+        ]));
+
+        assert_eq!(2, queue.batches.len());
+        assert_eq!(3, queue.skipped_events);
+    }
+
+    #[test]
+    fn test_keep_state_batches() {
+        let mut queue = RedrawQueue::new();
+
+        queue.push(vec![
+            RedrawEvent::Put("a".to_owned()),
+            RedrawEvent::ModeChange("normal".to_owned(), 0),
+        ]);
+        queue.push(vec![RedrawEvent::Clear, RedrawEvent::Put("a".to_owned())]);
+        queue.push(vec![RedrawEvent::Clear, RedrawEvent::CursorGoto(0, 0)]);
+
+        assert_eq!(2, queue.batches.len());
+        assert_eq!(2, queue.skipped_events);
+    }
+
+    #[test]
+    fn test_batch_ends_with_flush() {
+        let mut queue = RedrawQueue::new();
+
+        assert!(queue.push(vec![
+            RedrawEvent::Put("a".to_owned()),
+            RedrawEvent::Flush,
+            RedrawEvent::CursorGoto(1, 0),
+        ]));
+        assert_eq!(1, queue.batches.len());
+
+        // rest of batch comes in next notification
+        assert!(!queue.push(vec![RedrawEvent::Put("b".to_owned())]));
+        assert_eq!(1, queue.batches.len());
+        assert!(!queue.push(vec![RedrawEvent::Flush, RedrawEvent::Flush]));
+        assert_eq!(2, queue.batches.len());
+        assert_eq!(2, queue.batches[1].len());
+    }
+
+    #[test]
+    fn test_pop_resets_schedule() {
+        let mut queue = RedrawQueue::new();
+
+        assert!(queue.push(vec![RedrawEvent::Clear]));
+        assert!(queue.pop().is_some());
+        assert!(queue.pop().is_none());
+        assert!(queue.push(vec![RedrawEvent::Clear]));
     }
 }
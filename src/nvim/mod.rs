@@ -1,14 +1,31 @@
+mod buffer_tracker;
 mod client;
+mod exec;
+mod gui_plugins;
 mod handler;
+mod mouse;
+mod paste;
+mod recorder;
 mod redraw_handler;
+mod rpc_log;
 mod repaint_mode;
+mod server;
 mod ext;
 
+pub use self::buffer_tracker::{BufferTracker, LinesEvent};
 pub use self::redraw_handler::{CompleteItem, NvimCommand};
 pub use self::repaint_mode::RepaintMode;
-pub use self::client::{NeovimClient, NeovimClientAsync, NeovimRef};
-pub use self::ext::ErrorReport;
+pub use self::client::{
+    NeovimClient, NeovimClientAsync, NeovimClientState, NeovimRef, NvimAccess,
+};
+pub use self::gui_plugins::conflicting_gui_plugins;
+pub use self::exec::nvim_execute;
+pub use self::ext::{ErrorEvent, ErrorReport};
 pub use self::handler::NvimHandler;
+pub use self::mouse::{input_mouse, NVIM_INPUT_MOUSE_VERSION};
+pub use self::paste::{paste, NVIM_PASTE_VERSION};
+pub use self::recorder::{EventRecorder, EventReplay};
+pub use self::server::{new_server_address, remove_server_socket};
 
 use std::error;
 use std::fmt;
@@ -17,8 +34,9 @@ use std::process::{Command, Stdio};
 use std::result;
 use std::time::Duration;
 
-use neovim_lib::{Neovim, NeovimApi, NeovimApiAsync, Session, UiAttachOptions};
+use neovim_lib::{Neovim, NeovimApi, NeovimApiAsync, Session, Value};
 
+use crash;
 use misc::escape_filename;
 use nvim_config::NvimConfig;
 
@@ -86,6 +104,7 @@ pub fn start(
     timeout: Option<Duration>,
     args_for_neovim: Vec<String>,
     enable_swap: bool,
+    server_address: Option<&str>,
 ) -> result::Result<Neovim, NvimInitError> {
     let mut cmd = if let Some(path) = nvim_bin_path {
         Command::new(path)
@@ -118,6 +137,13 @@ pub fn start(
         cmd.arg("--cmd").arg("let &rtp.=',runtime'");
     }
 
+    if let Some(address) = server_address {
+        cmd.arg("--listen")
+            .arg(address)
+            .arg("--cmd")
+            .arg(format!("let g:gui_server_address = '{}'", address.replace('\'', "''")));
+    }
+
     if let Some(nvim_config) = NvimConfig::config_path() {
         if let Some(path) = nvim_config.to_str() {
             cmd.arg("--cmd").arg(format!("source {}", path));
@@ -144,41 +170,83 @@ pub fn start(
     Ok(nvim)
 }
 
+/// Attach ui and return requested ui options that nvim does not support
 pub fn post_start_init(
     nvim: NeovimClientAsync,
     open_paths: Vec<String>,
     cols: u64,
     rows: u64,
     input_data: Option<String>,
-) -> result::Result<(), NvimInitError> {
-    nvim.borrow()
+) -> result::Result<Vec<String>, NvimInitError> {
+    // attach fails on unknown option, so supported ones must be known before attach
+    let api_info = nvim
+        .borrow()
         .unwrap()
-        .ui_attach(
-            cols,
-            rows,
-            UiAttachOptions::new()
-                .set_popupmenu_external(true)
-                .set_tabline_external(true),
-        )
+        .get_api_info()
         .map_err(NvimInitError::new_post_init)?;
 
-    nvim.borrow()
+    if let Some(channel_id) = api_info.get(0).and_then(Value::as_u64) {
+        info!("Nvim channel id {}", channel_id);
+        nvim.set_channel_id(channel_id);
+    }
+    if let Some(version) = api_info.get(1).and_then(nvim_version) {
+        crash::set_nvim_version(format!("{}.{}.{}", version.0, version.1, version.2));
+        nvim.set_nvim_version(version);
+    }
+    let api_level = api_info.get(1).and_then(exec::api_level).unwrap_or(0);
+    nvim.set_api_level(api_level);
+
+    let ui_options = api_info.get(1).and_then(supported_ui_options);
+    let (attach_options, dropped_options) =
+        filter_ui_options(ui_attach_options(), ui_options.as_ref().map(Vec::as_slice));
+    for option in &dropped_options {
+        warn!("Ui option {} is not supported by nvim", option);
+    }
+
+    // rest of initialization calls are sent in one batch to save round-trips
+    let mut calls = vec![atomic_call(
+        "nvim_ui_attach",
+        vec![cols.into(), rows.into(), attach_options],
+    )];
+    // registered before ginit.vim, so it can be used there
+    if api_level >= NVIM_LUA_FN_API_LEVEL {
+        calls.push(atomic_call(
+            "nvim_exec_lua",
+            vec![version_function_lua().into(), Value::Array(vec![])],
+        ));
+    }
+    calls.push(atomic_call("nvim_command", vec!["runtime! ginit.vim".into()]));
+    calls.push(atomic_call("nvim_set_client_info", client_info()));
+    let calls_count = calls.len();
+
+    let response = nvim
+        .borrow()
         .unwrap()
-        .command("runtime! ginit.vim")
+        .call_atomic(calls)
         .map_err(NvimInitError::new_post_init)?;
 
+    let mut response = response.into_iter();
+    match response.next() {
+        Some(Value::Array(_)) => (),
+        _ => {
+            return Err(NvimInitError::new_post_init(
+                "Wrong nvim_call_atomic response format",
+            ))
+        }
+    };
+
+    if let Some(err) = response.next().and_then(|e| AtomicCallError::from(&e)) {
+        // nvim_set_client_info is the last call in batch and it is
+        // not supported by old nvim versions, so this error can be skipped
+        if err.idx + 1 == calls_count {
+            warn!("Can't set client info: {}", err.message);
+        } else {
+            return Err(NvimInitError::new_post_init(err.message));
+        }
+    }
+
     if !open_paths.is_empty() {
-        let command = open_paths
-            .iter()
-            .fold(":ar".to_owned(), |command, filename| {
-                let filename = escape_filename(filename);
-                command + " " + &filename
-            });
-        nvim.borrow()
-            .unwrap()
-            .command_async(&command)
-            .cb(|r| r.report_err())
-            .call();
+        send_startup_files(&mut *nvim.borrow().unwrap(), &open_paths);
     } else {
         if let Some(input_data) = input_data {
             let mut nvim = nvim.borrow().unwrap();
@@ -196,5 +264,247 @@ pub fn post_start_init(
         }
     }
 
-    Ok(())
+    Ok(dropped_options)
+}
+
+/// Open first file in current window and each next one in new tab
+fn send_startup_files(nvim: &mut Neovim, open_paths: &[String]) {
+    for command in startup_files_commands(open_paths) {
+        nvim.command_async(&command)
+            .cb(|r| r.report_err())
+            .call();
+    }
+}
+
+fn startup_files_commands(open_paths: &[String]) -> Vec<String> {
+    open_paths
+        .iter()
+        .enumerate()
+        .map(|(idx, filename)| {
+            let command = if idx == 0 { "edit" } else { "tabedit" };
+            format!("{} {}", command, escape_filename(filename))
+        }).collect()
+}
+
+/// Commands for dropped `(path, is_dir)` list, first one is opened in current window
+/// and others in new tabs, directories are opened with netrw
+pub fn dropped_files_commands(paths: &[(String, bool)]) -> Vec<String> {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(idx, &(ref path, is_dir))| {
+            let command = match (idx == 0, is_dir) {
+                (true, false) => "edit",
+                (false, false) => "tabedit",
+                (true, true) => "Explore",
+                (false, true) => "Texplore",
+            };
+            format!("{} {}", command, escape_filename(path))
+        }).collect()
+}
+
+/// Major, minor and patch version from `nvim_get_api_info` metadata
+fn nvim_version(metadata: &Value) -> Option<(u32, u32, u32)> {
+    let version = metadata
+        .as_map()?
+        .iter()
+        .find(|&&(ref k, _)| k.as_str() == Some("version"))?
+        .1
+        .as_map()?;
+
+    let part = |name| {
+        version
+            .iter()
+            .find(|&&(ref k, _)| k.as_str() == Some(name))
+            .and_then(|&(_, ref v)| v.as_u64())
+            .unwrap_or(0) as u32
+    };
+
+    Some((part("major"), part("minor"), part("patch")))
+}
+
+/// `ui_options` from `nvim_get_api_info` metadata, not reported by old nvim
+fn supported_ui_options(metadata: &Value) -> Option<Vec<String>> {
+    metadata
+        .as_map()?
+        .iter()
+        .find(|&&(ref k, _)| k.as_str() == Some("ui_options"))?
+        .1
+        .as_array()?
+        .iter()
+        .map(|option| option.as_str().map(str::to_owned))
+        .collect()
+}
+
+/// Split requested options into supported ones and names of dropped ones,
+/// nothing is dropped in case supported options are unknown
+fn filter_ui_options(options: Value, supported: Option<&[String]>) -> (Value, Vec<String>) {
+    let (options, supported) = match (options, supported) {
+        (Value::Map(options), Some(supported)) => (options, supported),
+        (options, _) => return (options, vec![]),
+    };
+
+    let (kept, dropped): (Vec<_>, Vec<_>) = options.into_iter().partition(|&(ref k, _)| {
+        k.as_str()
+            .map_or(false, |k| supported.iter().any(|s| s == k))
+    });
+
+    let dropped = dropped
+        .into_iter()
+        .filter_map(|(k, _)| k.as_str().map(str::to_owned))
+        .collect();
+
+    (Value::Map(kept), dropped)
+}
+
+/// First nvim version that supports ui option
+pub fn ui_option_min_version(option: &str) -> &'static str {
+    match option {
+        "ext_popupmenu" | "ext_tabline" => "0.2.1",
+        "ext_cmdline" | "ext_wildmenu" => "0.2.2",
+        _ => "0.4.0",
+    }
+}
+
+fn atomic_call(method: &str, args: Vec<Value>) -> Value {
+    Value::Array(vec![method.into(), Value::Array(args)])
+}
+
+pub fn ui_attach_options() -> Value {
+    Value::Map(vec![
+        ("rgb".into(), true.into()),
+        ("ext_popupmenu".into(), true.into()),
+        ("ext_tabline".into(), true.into()),
+    ])
+}
+
+/// Version of nvim-gtk split by cargo
+const VERSION_MAJOR: &str = env!("CARGO_PKG_VERSION_MAJOR");
+const VERSION_MINOR: &str = env!("CARGO_PKG_VERSION_MINOR");
+const VERSION_PATCH: &str = env!("CARGO_PKG_VERSION_PATCH");
+
+/// First API level with `vim.fn` and `nvim_exec_lua`, nvim 0.5
+const NVIM_LUA_FN_API_LEVEL: u64 = 7;
+
+/// `vim.fn.nvim_gtk_version()` for plugins, returns `{major, minor, patch}` table
+fn version_function_lua() -> String {
+    format!(
+        "vim.fn.nvim_gtk_version = function() \
+         return {{ major = {}, minor = {}, patch = {} }} end",
+        VERSION_MAJOR, VERSION_MINOR, VERSION_PATCH
+    )
+}
+
+fn client_info() -> Vec<Value> {
+    let version = Value::Map(vec![
+        ("major".into(), VERSION_MAJOR.parse::<u64>().unwrap_or(0).into()),
+        ("minor".into(), VERSION_MINOR.parse::<u64>().unwrap_or(0).into()),
+        ("patch".into(), VERSION_PATCH.parse::<u64>().unwrap_or(0).into()),
+    ]);
+
+    vec![
+        "nvim-gtk".into(),
+        version,
+        "ui".into(),
+        Value::Map(vec![]),
+        Value::Map(vec![]),
+    ]
+}
+
+/// Error part of nvim_call_atomic response: [index, type, message]
+struct AtomicCallError {
+    idx: usize,
+    message: String,
+}
+
+impl AtomicCallError {
+    fn from(val: &Value) -> Option<Self> {
+        let err = val.as_array()?;
+        let idx = err.get(0).and_then(Value::as_u64)? as usize;
+        let message = err
+            .get(2)
+            .and_then(Value::as_str)
+            .unwrap_or("Unknown error")
+            .to_owned();
+
+        Some(AtomicCallError { idx, message })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nvim_version() {
+        let metadata = Value::Map(vec![(
+            "version".into(),
+            Value::Map(vec![
+                ("major".into(), 0.into()),
+                ("minor".into(), 9.into()),
+                ("patch".into(), 5.into()),
+            ]),
+        )]);
+
+        assert_eq!(Some((0, 9, 5)), nvim_version(&metadata));
+        assert_eq!(None, nvim_version(&Value::Map(vec![])));
+    }
+
+    #[test]
+    fn test_version_function_lua() {
+        let lua = version_function_lua();
+        assert!(lua.starts_with("vim.fn.nvim_gtk_version = function()"));
+        assert!(lua.contains(&format!("major = {},", VERSION_MAJOR)));
+    }
+
+    #[test]
+    fn test_filter_ui_options() {
+        let options = Value::Map(vec![
+            ("rgb".into(), true.into()),
+            ("ext_tabline".into(), true.into()),
+            ("ext_messages".into(), true.into()),
+        ]);
+        let supported = vec!["rgb".to_owned(), "ext_tabline".to_owned()];
+
+        let (kept, dropped) = filter_ui_options(options.clone(), Some(&supported));
+        assert_eq!(
+            Value::Map(vec![
+                ("rgb".into(), true.into()),
+                ("ext_tabline".into(), true.into()),
+            ]),
+            kept
+        );
+        assert_eq!(vec!["ext_messages".to_owned()], dropped);
+
+        let (kept, dropped) = filter_ui_options(options.clone(), None);
+        assert_eq!(options, kept);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn test_startup_files_commands() {
+        let paths = vec!["file1.rs".to_owned(), "my file.rs".to_owned()];
+        assert_eq!(
+            vec!["edit file1.rs".to_owned(), "tabedit my\\ file.rs".to_owned()],
+            startup_files_commands(&paths)
+        );
+        assert!(startup_files_commands(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_dropped_files_commands() {
+        let paths = vec![
+            ("/src".to_owned(), true),
+            ("/a.rs".to_owned(), false),
+            ("/doc".to_owned(), true),
+        ];
+        assert_eq!(
+            vec![
+                "Explore /src".to_owned(),
+                "tabedit /a.rs".to_owned(),
+                "Texplore /doc".to_owned(),
+            ],
+            dropped_files_commands(&paths)
+        );
+    }
 }
@@ -0,0 +1,66 @@
+use neovim_lib::{Neovim, Value};
+
+use super::ErrorReport;
+
+/// First nvim version with `nvim_input_mouse`
+pub const NVIM_INPUT_MOUSE_VERSION: (u32, u32, u32) = (0, 4, 0);
+
+/// Send mouse event with `nvim_input_mouse`, `input` is legacy key name like `LeftMouse`
+///
+/// Unlike `<LeftMouse><col,row>` keys, event keeps screen position,
+/// so click handlers of statusline, winbar and tabline are called.
+/// Returns false for input that has no button and action.
+pub fn input_mouse(nvim: &mut Neovim, input: &str, modifiers: &str, row: u64, col: u64) -> bool {
+    let (button, action) = match button_action(input) {
+        Some(button_action) => button_action,
+        None => return false,
+    };
+
+    // grid is ignored without ext_multigrid
+    let args = vec![
+        Value::from(button),
+        Value::from(action),
+        Value::from(modifiers),
+        Value::from(0),
+        Value::from(row),
+        Value::from(col),
+    ];
+    nvim.session
+        .call_async::<Value>("nvim_input_mouse", args)
+        .cb(|r| r.report_err())
+        .call();
+    true
+}
+
+fn button_action(input: &str) -> Option<(&'static str, &'static str)> {
+    let button_action = match input {
+        "LeftMouse" => ("left", "press"),
+        "LeftDrag" => ("left", "drag"),
+        "LeftRelease" => ("left", "release"),
+        "RightMouse" => ("right", "press"),
+        "RightDrag" => ("right", "drag"),
+        "RightRelease" => ("right", "release"),
+        "MiddleMouse" => ("middle", "press"),
+        "MiddleDrag" => ("middle", "drag"),
+        "MiddleRelease" => ("middle", "release"),
+        "ScrollWheelUp" => ("wheel", "up"),
+        "ScrollWheelDown" => ("wheel", "down"),
+        "ScrollWheelLeft" => ("wheel", "left"),
+        "ScrollWheelRight" => ("wheel", "right"),
+        _ => return None,
+    };
+    Some(button_action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_action() {
+        assert_eq!(Some(("left", "press")), button_action("LeftMouse"));
+        assert_eq!(Some(("middle", "release")), button_action("MiddleRelease"));
+        assert_eq!(Some(("wheel", "down")), button_action("ScrollWheelDown"));
+        assert_eq!(None, button_action("X1Mouse"));
+    }
+}
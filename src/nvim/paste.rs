@@ -0,0 +1,87 @@
+use neovim_lib::{Neovim, Value};
+
+use super::ErrorReport;
+
+/// First nvim version with `nvim_paste`
+pub const NVIM_PASTE_VERSION: (u32, u32, u32) = (0, 4, 0);
+/// Bigger content is streamed to nvim in several `nvim_paste` calls
+const PASTE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Paste text with `nvim_paste`, respects bracketed paste and paste redirection
+///
+/// Small text is sent in one asynchronous call. Bigger one is streamed
+/// chunk by chunk waiting for each reply, so paste cancelled by nvim stops streaming.
+pub fn paste(nvim: &mut Neovim, text: &str) {
+    if text.len() <= PASTE_CHUNK_SIZE {
+        let args = vec![Value::from(text), Value::from(true), Value::from(-1)];
+        nvim.session
+            .call_async::<Value>("nvim_paste", args)
+            .cb(|r| r.report_err())
+            .call();
+        return;
+    }
+
+    for (chunk, phase) in paste_chunks(text, PASTE_CHUNK_SIZE) {
+        let args = vec![Value::from(chunk), Value::from(true), Value::from(phase)];
+        match nvim.session.call("nvim_paste", args) {
+            Ok(Value::Boolean(false)) => {
+                info!("Paste is cancelled by nvim");
+                break;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                error!("{}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Split text to chunks with `nvim_paste` phase:
+/// -1 for single call, 1 for first, 2 for middle and 3 for last chunk
+fn paste_chunks(text: &str, chunk_size: usize) -> Vec<(&str, i64)> {
+    let mut chunks = vec![];
+    let mut rest = text;
+
+    while rest.len() > chunk_size {
+        let mut end = chunk_size;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks.push(rest);
+
+    let last = chunks.len() - 1;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let phase = match idx {
+                _ if last == 0 => -1,
+                0 => 1,
+                idx if idx == last => 3,
+                _ => 2,
+            };
+            (chunk, phase)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_chunks() {
+        assert_eq!(vec![("abc", -1)], paste_chunks("abc", 3));
+        assert_eq!(
+            vec![("ab", 1), ("cd", 2), ("e", 3)],
+            paste_chunks("abcde", 2)
+        );
+        // chunks are split on char boundary
+        assert_eq!(vec![("a", 1), ("é", 3)], paste_chunks("aé", 2));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use neovim_lib::{Handler, Value};
+use rmpv;
+
+use super::handler::NvimHandler;
+
+/// Writes incoming redraw batches to file, existing file is overwritten.
+///
+/// File format is a msgpack stream: header map with grid size and attach options,
+/// followed by `[timestamp_ms, batch]` arrays.
+#[derive(Clone)]
+pub struct EventRecorder {
+    writer: Arc<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+
+impl EventRecorder {
+    pub fn new(path: &str, cols: u64, rows: u64, options: Value) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| format!("Can't open events record file {}: {}", path, e))?;
+
+        let mut writer = BufWriter::new(file);
+        let header = Value::Map(vec![
+            ("cols".into(), cols.into()),
+            ("rows".into(), rows.into()),
+            ("options".into(), options),
+        ]);
+        rmpv::encode::write_value(&mut writer, &header).map_err(|e| e.to_string())?;
+
+        Ok(EventRecorder {
+            writer: Arc::new(Mutex::new(writer)),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&self, params: &[Value]) {
+        let elapsed = self.start.elapsed();
+        let timestamp = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        let record = Value::Array(vec![timestamp.into(), Value::Array(params.to_vec())]);
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = rmpv::encode::write_value(&mut *writer, &record) {
+            error!("Can't record redraw events: {}", e);
+        }
+        if let Err(e) = writer.flush() {
+            error!("Can't record redraw events: {}", e);
+        }
+    }
+}
+
+/// Recorded events stream
+pub struct EventReplay {
+    reader: BufReader<File>,
+    pub cols: u64,
+    pub rows: u64,
+}
+
+impl EventReplay {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|e| format!("Can't open events file {}: {}", path, e))?;
+        let mut reader = BufReader::new(file);
+
+        let header = rmpv::decode::read_value(&mut reader).map_err(|e| e.to_string())?;
+        let header = header
+            .as_map()
+            .ok_or_else(|| "Wrong events file header".to_owned())?;
+        let get = |key: &str| {
+            header
+                .iter()
+                .find(|&&(ref k, _)| k.as_str() == Some(key))
+                .and_then(|&(_, ref v)| v.as_u64())
+                .ok_or_else(|| format!("No {} in events file header", key))
+        };
+        let cols = get("cols")?;
+        let rows = get("rows")?;
+
+        Ok(EventReplay { reader, cols, rows })
+    }
+
+    /// Feed recorded stream to handler in separate thread
+    ///
+    /// In case `max_speed` is false, original timing between batches is preserved
+    pub fn start(mut self, mut handler: NvimHandler, max_speed: bool) {
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut batches = 0;
+
+            while let Ok(record) = rmpv::decode::read_value(&mut self.reader) {
+                let mut record = match record {
+                    Value::Array(record) => record.into_iter(),
+                    _ => {
+                        error!("Wrong event record format");
+                        break;
+                    }
+                };
+
+                let timestamp = record.next().and_then(|t| t.as_u64()).unwrap_or(0);
+                let params = match record.next() {
+                    Some(Value::Array(params)) => params,
+                    _ => {
+                        error!("Wrong event record format");
+                        break;
+                    }
+                };
+
+                if !max_speed {
+                    let target = Duration::from_millis(timestamp);
+                    let elapsed = start.elapsed();
+                    if target > elapsed {
+                        thread::sleep(target - elapsed);
+                    }
+                }
+
+                handler.handle_notify("redraw", params);
+                batches += 1;
+            }
+
+            info!("Replayed {} redraw batches in {:?}", batches, start.elapsed());
+        });
+    }
+}
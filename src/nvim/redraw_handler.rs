@@ -1,10 +1,12 @@
-use std::num::ParseFloatError;
+use std::collections::{HashMap, HashSet};
+use std::num::{ParseFloatError, ParseIntError};
 use std::result;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
 
 use neovim_lib::neovim_api::Tabpage;
 use neovim_lib::{UiOption, Value};
 
+use color::DefaultColors;
 use gtk::ClipboardExt;
 use shell;
 use ui::UiMutex;
@@ -91,6 +93,9 @@ macro_rules! try_arg {
             _ => Err("Can't convert to string".to_owned()),
         }?
     };
+    ($value:expr,attrs) => {
+        attrs_map($value)?
+    };
     ($value:expr,ext) => {
         rmpv::ext::from_value($value).map_err(|e| e.to_string())?
     };
@@ -110,10 +115,43 @@ macro_rules! call {
     )
 }
 
+fn arg(args: &[Value], idx: usize) -> result::Result<&Value, String> {
+    args.get(idx)
+        .ok_or_else(|| format!("No argument with index {}", idx))
+}
+
+/// Opacity from 0.0 (transparent) to 1.0 (opaque)
+fn parse_opacity(value: &str) -> result::Result<f64, String> {
+    let opacity: f64 = value
+        .trim()
+        .parse()
+        .map_err(|e: ParseFloatError| e.to_string())?;
+    if opacity >= 0.0 && opacity <= 1.0 {
+        Ok(opacity)
+    } else {
+        Err(format!("Opacity must be from 0.0 to 1.0, got {}", value))
+    }
+}
+
+/// Window border radius in pixels from 0 to 32
+fn parse_window_border(value: &str) -> result::Result<u64, String> {
+    let border: u64 = value
+        .trim()
+        .parse()
+        .map_err(|e: ParseIntError| e.to_string())?;
+    if border <= 32 {
+        Ok(border)
+    } else {
+        Err(format!("Window border must be from 0 to 32, got {}", value))
+    }
+}
+
 pub enum NvimCommand {
     ToggleSidebar,
     Transparency(f64, f64),
     PreferDarkTheme(bool),
+    WindowBorder(u64),
+    Suspend,
 }
 
 pub fn call_gui_event(
@@ -124,39 +162,52 @@ pub fn call_gui_event(
     match method {
         "Font" => call!(ui->set_font(args: str)),
         "FontFeatures" => call!(ui->set_font_features(args: str)),
+        "FontVariant" => call!(ui->set_font_variant(args: str, str)),
+        "AntiAliasing" => ui.set_antialiasing(try_str!(arg(&args, 0)?)),
         "Linespace" => call!(ui->set_line_space(args: str)),
-        "Clipboard" => match try_str!(args[0]) {
-            "Set" => match try_str!(args[1]) {
-                "*" => ui.clipboard_primary_set(try_str!(args[2])),
-                _ => ui.clipboard_clipboard_set(try_str!(args[2])),
+        "Screenshot" => call!(ui->save_screenshot(args: str)),
+        "DumpEvents" => ui.dump_events(),
+        "Clipboard" => match try_str!(arg(&args, 0)?) {
+            "Set" => match try_str!(arg(&args, 1)?) {
+                "*" => ui.clipboard_primary_set(try_str!(arg(&args, 2)?)),
+                _ => ui.clipboard_clipboard_set(try_str!(arg(&args, 2)?)),
             },
             opt => error!("Unknown option {}", opt),
         },
-        "Option" => match try_str!(args[0]) {
-            "Popupmenu" => ui.nvim()
-                .ok_or_else(|| "Nvim not initialized".to_owned())
+        "Option" => match try_str!(arg(&args, 0)?) {
+            "Popupmenu" => ui.try_nvim_access()
+                .into_result()
                 .and_then(|mut nvim| {
-                    nvim.set_option(UiOption::ExtPopupmenu(try_uint!(args[1]) == 1))
+                    nvim.set_option(UiOption::ExtPopupmenu(try_uint!(arg(&args, 1)?) == 1))
                         .map_err(|e| e.to_string())
                 })?,
-            "Tabline" => ui.nvim()
-                .ok_or_else(|| "Nvim not initialized".to_owned())
+            "Tabline" => ui.try_nvim_access()
+                .into_result()
                 .and_then(|mut nvim| {
-                    nvim.set_option(UiOption::ExtTabline(try_uint!(args[1]) == 1))
+                    nvim.set_option(UiOption::ExtTabline(try_uint!(arg(&args, 1)?) == 1))
                         .map_err(|e| e.to_string())
                 })?,
-            "Cmdline" => ui.nvim()
-                .ok_or_else(|| "Nvim not initialized".to_owned())
+            "Cmdline" => ui.try_nvim_access()
+                .into_result()
                 .and_then(|mut nvim| {
-                    nvim.set_option(UiOption::ExtCmdline(try_uint!(args[1]) == 1))
+                    nvim.set_option(UiOption::ExtCmdline(try_uint!(arg(&args, 1)?) == 1))
                         .map_err(|e| e.to_string())?;
-                    nvim.set_option(UiOption::ExtWildmenu(try_uint!(args[1]) == 1))
+                    nvim.set_option(UiOption::ExtWildmenu(try_uint!(arg(&args, 1)?) == 1))
                         .map_err(|e| e.to_string())
                 })?,
+            "CursorAnimation" => ui.set_cursor_animation(try_uint!(arg(&args, 1)?) == 1),
+            "Padding" => ui.set_padding(try_str!(arg(&args, 1)?)),
+            "ScrollLines" => ui.set_scroll_lines(try_uint!(arg(&args, 1)?)),
+            "DimInactive" => ui.set_dim_inactive(try_uint!(arg(&args, 1)?)),
+            "MessageTimeout" => ui.set_message_timeout(try_uint!(arg(&args, 1)?)),
+            "HorizontalScroll" => ui.set_horizontal_scroll(try_uint!(arg(&args, 1)?) == 1),
+            "NowrapIndicator" => ui.set_nowrap_indicator(try_uint!(arg(&args, 1)?) == 1),
+            "CursorShape" => ui.set_cursor_shape(try_str!(arg(&args, 1)?)),
+            "ImModule" => ui.set_im_module(try_str!(arg(&args, 1)?).to_owned()),
             opt => error!("Unknown option {}", opt),
         },
         "Command" => {
-            match try_str!(args[0]) {
+            match try_str!(arg(&args, 0)?) {
                 "ToggleSidebar" => ui.on_command(NvimCommand::ToggleSidebar),
                 "Transparency" => ui.on_command(NvimCommand::Transparency(
                     try_str!(args.get(1).cloned().unwrap_or("1.0".into()))
@@ -166,6 +217,15 @@ pub fn call_gui_event(
                         .parse()
                         .map_err(|e: ParseFloatError| e.to_string())?,
                 )),
+                // cells and window background get same alpha, text stays opaque
+                "OpacityBG" => {
+                    let opacity = parse_opacity(try_str!(arg(&args, 1)?))?;
+                    ui.on_command(NvimCommand::Transparency(opacity, opacity))
+                }
+                "WindowBorder" => {
+                    let border = parse_window_border(try_str!(arg(&args, 1)?))?;
+                    ui.on_command(NvimCommand::WindowBorder(border))
+                }
                 "PreferDarkTheme" => {
                     let prefer_dark_theme = match try_str!(args.get(1).cloned().unwrap_or(Value::from("off"))) {
                         "on" => true,
@@ -174,6 +234,22 @@ pub fn call_gui_event(
 
                     ui.on_command(NvimCommand::PreferDarkTheme(prefer_dark_theme))
                 },
+                "Tabline" => {
+                    let enabled = match try_str!(args.get(1).cloned().unwrap_or(Value::from("on"))) {
+                        "off" => false,
+                        _ => true,
+                    };
+
+                    ui.set_tabline_enabled(enabled)
+                }
+                "FullscreenHideTabline" => {
+                    let hide = match try_str!(args.get(1).cloned().unwrap_or(Value::from("on"))) {
+                        "off" => false,
+                        _ => true,
+                    };
+
+                    ui.set_fullscreen_hide_tabline(hide)
+                }
                 _ => error!("Unknown command"),
             };
         }
@@ -182,126 +258,439 @@ pub fn call_gui_event(
     Ok(())
 }
 
+pub type GuiRequestReply = mpsc::Sender<result::Result<Value, Value>>;
+
+/// Reply to gui request, clipboard requests are replied later from main loop
+/// as clipboard owner is another application
+pub fn reply_gui_request(
+    ui: &Arc<UiMutex<shell::State>>,
+    method: &str,
+    args: Vec<Value>,
+    reply: GuiRequestReply,
+) {
+    if method == "Clipboard" {
+        match args.get(0).and_then(Value::as_str) {
+            Some("Get") => request_clipboard_text(&*ui.borrow(), &args, reply),
+            opt => {
+                error!("Unknown clipboard option {:?}", opt);
+                reply
+                    .send(Err(Value::from(format!("Unknown clipboard option {:?}", opt))))
+                    .ok();
+            }
+        }
+        return;
+    }
+
+    // receiver is gone in case request timed out
+    reply.send(call_gui_request(ui, method, &args)).ok();
+}
+
+/// Clipboard content is fetched only when nvim reads register,
+/// main loop is not blocked while owner prepares it
+fn request_clipboard_text(ui: &shell::State, args: &[Value], reply: GuiRequestReply) {
+    let clipboard = match args.get(1).and_then(Value::as_str) {
+        Some("*") => &ui.clipboard_primary,
+        _ => &ui.clipboard_clipboard,
+    };
+
+    clipboard.request_text(move |_, text| {
+        let text = text.unwrap_or_else(String::new);
+        reply
+            .send(Ok(Value::Array(
+                text.split('\n').map(Value::from).collect(),
+            )))
+            .ok();
+    });
+}
+
 pub fn call_gui_request(
     ui: &Arc<UiMutex<shell::State>>,
     method: &str,
     args: &Vec<Value>,
 ) -> result::Result<Value, Value> {
     match method {
-        "Clipboard" => {
-            match try_str!(args[0]) {
-                "Get" => {
-                    // NOTE: wait_for_text waits on the main loop. We can't have the ui borrowed
-                    // while it runs, otherwise ui callbacks will get called and try to borrow
-                    // mutably twice!
-                    let clipboard = {
-                        let ui = &mut ui.borrow_mut();
-                        match try_str!(args[1]) {
-                            "*" => ui.clipboard_primary.clone(),
-                            _ => ui.clipboard_clipboard.clone(),
+        "Font" => Ok(Value::from(ui.borrow().font_desc())),
+        "Version" => Ok(ui.borrow().version_info().to_value()),
+        "GridSize" => {
+            let ui = ui.borrow();
+            Ok(Value::Array(vec![
+                Value::from(ui.model.columns as u64),
+                Value::from(ui.model.rows as u64),
+            ]))
+        }
+        "Color" => {
+            let name = args
+                .get(0)
+                .and_then(Value::as_str)
+                .ok_or_else(|| Value::from("Color name expected"))?;
+            ui.borrow()
+                .resolve_color(name)
+                .map(|color| Value::from(color.to_hex()))
+                .map_err(Value::from)
+        }
+        _ => Err(Value::String(
+            format!("Unsupported request {}({:?})", method, args).into(),
+        )),
+    }
+}
+
+#[derive(Clone)]
+pub enum RedrawEvent {
+    CursorGoto(u64, u64),
+    Put(String),
+    Clear,
+    Resize(u64, u64),
+    HighlightSet(HashMap<String, Value>),
+    EolClear,
+    SetScrollRegion(u64, u64, u64, u64),
+    Scroll(i64),
+    UpdateBg(i64),
+    UpdateFg(i64),
+    UpdateSp(i64),
+    DefaultColorsSet(DefaultColors),
+    ModeChange(String, u64),
+    Mouse(bool),
+    Busy(bool),
+    Suspend,
+    PopupmenuShow(Vec<Vec<String>>, i64, u64, u64),
+    PopupmenuHide,
+    PopupmenuSelect(i64),
+    TablineUpdate(Tabpage, Vec<(Tabpage, Option<String>)>),
+    ModeInfoSet(bool, Vec<HashMap<String, Value>>),
+    OptionSet(String, Value),
+    CmdlineShow(
+        Vec<(HashMap<String, Value>, String)>,
+        u64,
+        String,
+        String,
+        u64,
+        u64,
+    ),
+    CmdlineBlockShow(Vec<Vec<(HashMap<String, Value>, String)>>),
+    CmdlineBlockAppend(Vec<(HashMap<String, Value>, String)>),
+    CmdlineHide(u64),
+    CmdlineBlockHide,
+    CmdlinePos(u64, u64),
+    CmdlineSpecialChar(String, bool, u64),
+    WildmenuShow(Vec<String>),
+    WildmenuHide,
+    WildmenuSelect(i64),
+    Flush,
+}
+
+macro_rules! parse {
+    ($args:ident => $ev:ident ( $($arg_type:ident),+ )) => (
+        {
+            let mut iter = $args.into_iter();
+            RedrawEvent::$ev($(
+                try_arg!(iter.next()
+                             .ok_or_else(|| format!("No such argument for {}", stringify!($ev)))?,
+                         $arg_type
+                        )
+            ),+ )
+        }
+    )
+}
+
+impl RedrawEvent {
+    /// Convert raw redraw notification arguments to typed event
+    ///
+    /// Return `None` for events that is not handled by ui
+    pub fn parse(method: &str, args: Vec<Value>) -> result::Result<Option<Self>, String> {
+        let event = match method {
+            "cursor_goto" => parse!(args => CursorGoto(uint, uint)),
+            "put" => parse!(args => Put(str)),
+            "clear" => RedrawEvent::Clear,
+            "resize" => parse!(args => Resize(uint, uint)),
+            "highlight_set" => parse!(args => HighlightSet(attrs)),
+            "eol_clear" => RedrawEvent::EolClear,
+            "set_scroll_region" => parse!(args => SetScrollRegion(uint, uint, uint, uint)),
+            "scroll" => parse!(args => Scroll(int)),
+            "update_bg" => parse!(args => UpdateBg(int)),
+            "update_fg" => parse!(args => UpdateFg(int)),
+            "update_sp" => parse!(args => UpdateSp(int)),
+            "default_colors_set" => {
+                let mut iter = args.into_iter().map(|arg| arg.as_i64());
+                let mut next = || {
+                    iter.next()
+                        .and_then(|arg| arg)
+                        .ok_or_else(|| "Can't convert default color to int".to_owned())
+                };
+                RedrawEvent::DefaultColorsSet(DefaultColors::new(
+                    next()?,
+                    next()?,
+                    next()?,
+                    next()?,
+                    next()?,
+                ))
+            }
+            "mode_change" => parse!(args => ModeChange(str, uint)),
+            "mouse_on" => RedrawEvent::Mouse(true),
+            "mouse_off" => RedrawEvent::Mouse(false),
+            "busy_start" => RedrawEvent::Busy(true),
+            "busy_stop" => RedrawEvent::Busy(false),
+            "suspend" => RedrawEvent::Suspend,
+            "popupmenu_show" => {
+                if args.len() < 4 {
+                    return Err("Wrong popupmenu_show arguments count".to_owned());
+                }
+
+                let menu_items = map_array!(args[0], "Error get menu list array", |item| {
+                    map_array!(item, "Error get menu item array", |col| col
+                        .as_str()
+                        .map(str::to_owned)
+                        .ok_or("Error get menu column"))
+                    .and_then(|item| {
+                        if item.len() < 4 {
+                            Err("Wrong menu item columns count")
+                        } else {
+                            Ok(item)
                         }
-                    };
-                    let t = clipboard.wait_for_text().unwrap_or_else(|| String::new());
-                    Ok(Value::Array(
-                        t.split("\n").map(|s| s.into()).collect::<Vec<Value>>(),
-                    ))
+                    })
+                })?;
+
+                RedrawEvent::PopupmenuShow(
+                    menu_items,
+                    try_int!(args[1]),
+                    try_uint!(args[2]),
+                    try_uint!(args[3]),
+                )
+            }
+            "popupmenu_hide" => RedrawEvent::PopupmenuHide,
+            "popupmenu_select" => parse!(args => PopupmenuSelect(int)),
+            "tabline_update" => {
+                if args.len() < 2 {
+                    return Err("Wrong tabline_update arguments count".to_owned());
+                }
+
+                let tabs_out = map_array!(
+                    args[1],
+                    "Error get tabline list".to_owned(),
+                    |tab| tab
+                        .as_map()
+                        .ok_or_else(|| "Error get map for tab".to_owned())
+                        .and_then(|tab_map| tab_map.to_attrs_map())
+                        .and_then(|tab_attrs| {
+                            let name_attr = tab_attrs
+                                .get("name")
+                                .and_then(|n| n.as_str().map(|s| s.to_owned()));
+                            let tab_attr = tab_attrs
+                                .get("tab")
+                                .map(|&tab_id| Tabpage::new(tab_id.clone()))
+                                .ok_or_else(|| "Error get tab id".to_owned())?;
+
+                            Ok((tab_attr, name_attr))
+                        })
+                )?;
+                RedrawEvent::TablineUpdate(Tabpage::new(args[0].clone()), tabs_out)
+            }
+            "mode_info_set" => parse!(args => ModeInfoSet(bool, ext)),
+            "option_set" => parse!(args => OptionSet(str, val)),
+            "cmdline_show" => parse!(args => CmdlineShow(ext, uint, str, str, uint, uint)),
+            "cmdline_block_show" => parse!(args => CmdlineBlockShow(ext)),
+            "cmdline_block_append" => parse!(args => CmdlineBlockAppend(ext)),
+            "cmdline_hide" => parse!(args => CmdlineHide(uint)),
+            "cmdline_block_hide" => RedrawEvent::CmdlineBlockHide,
+            "cmdline_pos" => parse!(args => CmdlinePos(uint, uint)),
+            "cmdline_special_char" => parse!(args => CmdlineSpecialChar(str, bool, uint)),
+            "wildmenu_show" => parse!(args => WildmenuShow(ext)),
+            "wildmenu_hide" => RedrawEvent::WildmenuHide,
+            "wildmenu_select" => parse!(args => WildmenuSelect(int)),
+            "flush" => RedrawEvent::Flush,
+            _ => {
+                warn_unknown_event(method);
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(event))
+    }
+
+    /// Event changes only grid content or cursor position
+    pub fn is_overwrite_only(&self) -> bool {
+        match *self {
+            RedrawEvent::Put(_)
+            | RedrawEvent::CursorGoto(..)
+            | RedrawEvent::EolClear
+            | RedrawEvent::Scroll(_)
+            | RedrawEvent::Clear => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_flush(&self) -> bool {
+        match *self {
+            RedrawEvent::Flush => true,
+            _ => false,
+        }
+    }
+
+    /// Event name and count of its arguments for event history,
+    /// cheap enough to be recorded for every event
+    pub fn summary(&self) -> (&'static str, usize) {
+        match *self {
+            RedrawEvent::CursorGoto(..) => ("cursor_goto", 2),
+            RedrawEvent::Put(..) => ("put", 1),
+            RedrawEvent::Clear => ("clear", 0),
+            RedrawEvent::Resize(..) => ("resize", 2),
+            RedrawEvent::HighlightSet(..) => ("highlight_set", 1),
+            RedrawEvent::EolClear => ("eol_clear", 0),
+            RedrawEvent::SetScrollRegion(..) => ("set_scroll_region", 4),
+            RedrawEvent::Scroll(..) => ("scroll", 1),
+            RedrawEvent::UpdateBg(..) => ("update_bg", 1),
+            RedrawEvent::UpdateFg(..) => ("update_fg", 1),
+            RedrawEvent::UpdateSp(..) => ("update_sp", 1),
+            RedrawEvent::DefaultColorsSet(..) => ("default_colors_set", 5),
+            RedrawEvent::ModeChange(..) => ("mode_change", 2),
+            RedrawEvent::Mouse(true) => ("mouse_on", 0),
+            RedrawEvent::Mouse(false) => ("mouse_off", 0),
+            RedrawEvent::Busy(true) => ("busy_start", 0),
+            RedrawEvent::Busy(false) => ("busy_stop", 0),
+            RedrawEvent::Suspend => ("suspend", 0),
+            RedrawEvent::PopupmenuShow(..) => ("popupmenu_show", 4),
+            RedrawEvent::PopupmenuHide => ("popupmenu_hide", 0),
+            RedrawEvent::PopupmenuSelect(..) => ("popupmenu_select", 1),
+            RedrawEvent::TablineUpdate(..) => ("tabline_update", 2),
+            RedrawEvent::ModeInfoSet(..) => ("mode_info_set", 2),
+            RedrawEvent::OptionSet(..) => ("option_set", 2),
+            RedrawEvent::CmdlineShow(..) => ("cmdline_show", 6),
+            RedrawEvent::CmdlineBlockShow(..) => ("cmdline_block_show", 1),
+            RedrawEvent::CmdlineBlockAppend(..) => ("cmdline_block_append", 1),
+            RedrawEvent::CmdlineHide(..) => ("cmdline_hide", 1),
+            RedrawEvent::CmdlineBlockHide => ("cmdline_block_hide", 0),
+            RedrawEvent::CmdlinePos(..) => ("cmdline_pos", 2),
+            RedrawEvent::CmdlineSpecialChar(..) => ("cmdline_special_char", 3),
+            RedrawEvent::WildmenuShow(..) => ("wildmenu_show", 1),
+            RedrawEvent::WildmenuHide => ("wildmenu_hide", 0),
+            RedrawEvent::WildmenuSelect(..) => ("wildmenu_select", 1),
+            RedrawEvent::Flush => ("flush", 0),
+        }
+    }
+}
+
+lazy_static! {
+    static ref UNKNOWN_EVENTS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Report unsupported event only once, new nvim versions can send it very often
+fn warn_unknown_event(method: &str) {
+    let mut unknown_events = UNKNOWN_EVENTS.lock().unwrap();
+    if !unknown_events.contains(method) {
+        warn!("Unsupported event {}", method);
+        unknown_events.insert(method.to_owned());
+    }
+}
+
+/// Convert attributes map, entries with non string keys are skipped
+fn attrs_map(value: Value) -> result::Result<HashMap<String, Value>, String> {
+    match value {
+        Value::Map(map) => Ok(map
+            .into_iter()
+            .filter_map(|(key, val)| match key {
+                Value::String(key) => key.into_str().map(|key| (key, val)),
+                key => {
+                    warn!("Attribute key {:?} is not a string", key);
+                    None
                 }
-                opt => {
-                    error!("Unknown option {}", opt);
-                    Err(Value::Nil)
+            })
+            .collect()),
+        _ => Err("Can't convert argument to map".to_owned()),
+    }
+}
+
+/// Parse redraw notification batch
+///
+/// This is called in rpc thread, so ui thread receive only
+/// validated events. Malformed events are logged and skipped.
+pub fn parse_redraw(params: Vec<Value>) -> Vec<RedrawEvent> {
+    let mut events = Vec::with_capacity(params.len());
+
+    for ev in params {
+        if let Value::Array(ev_args) = ev {
+            let mut args_iter = ev_args.into_iter();
+            let ev_name = args_iter.next();
+            if let Some(ev_name) = ev_name {
+                if let Some(ev_name) = ev_name.as_str() {
+                    for local_args in args_iter {
+                        let args = match local_args {
+                            Value::Array(ar) => ar,
+                            _ => vec![],
+                        };
+                        match RedrawEvent::parse(ev_name, args) {
+                            Ok(Some(event)) => events.push(event),
+                            Ok(None) => (),
+                            Err(msg) => error!("Error parse event {}: {}", ev_name, msg),
+                        }
+                    }
+                } else {
+                    error!("Unsupported event");
                 }
+            } else {
+                error!("Event name does not exists");
             }
+        } else {
+            error!("Unsupported event type {:?}", ev);
         }
-        _ => Err(Value::String(
-            format!("Unsupported request {}({:?})", method, args).into(),
-        )),
     }
+
+    events
 }
 
-pub fn call(
-    ui: &mut shell::State,
-    method: &str,
-    args: Vec<Value>,
-) -> result::Result<RepaintMode, String> {
-    let repaint_mode = match method {
-        "cursor_goto" => call!(ui->on_cursor_goto(args: uint, uint)),
-        "put" => call!(ui->on_put(args: str)),
-        "clear" => ui.on_clear(),
-        "resize" => call!(ui->on_resize(args: uint, uint)),
-        "highlight_set" => {
-            call!(ui->on_highlight_set(args: ext));
+pub fn call(ui: &mut shell::State, event: RedrawEvent) -> RepaintMode {
+    match event {
+        RedrawEvent::CursorGoto(row, col) => ui.on_cursor_goto(row, col),
+        RedrawEvent::Put(text) => ui.on_put(text),
+        RedrawEvent::Clear => ui.on_clear(),
+        RedrawEvent::Resize(columns, rows) => ui.on_resize(columns, rows),
+        RedrawEvent::HighlightSet(attrs) => {
+            ui.on_highlight_set(attrs);
             RepaintMode::Nothing
         }
-        "eol_clear" => ui.on_eol_clear(),
-        "set_scroll_region" => {
-            call!(ui->on_set_scroll_region(args: uint, uint, uint, uint));
+        RedrawEvent::EolClear => ui.on_eol_clear(),
+        RedrawEvent::SetScrollRegion(top, bot, left, right) => {
+            ui.on_set_scroll_region(top, bot, left, right);
             RepaintMode::Nothing
         }
-        "scroll" => call!(ui->on_scroll(args: int)),
-        "update_bg" => call!(ui->on_update_bg(args: int)),
-        "update_fg" => call!(ui->on_update_fg(args: int)),
-        "update_sp" => call!(ui->on_update_sp(args: int)),
-        "mode_change" => call!(ui->on_mode_change(args: str, uint)),
-        "mouse_on" => ui.on_mouse(true),
-        "mouse_off" => ui.on_mouse(false),
-        "busy_start" => ui.on_busy(true),
-        "busy_stop" => ui.on_busy(false),
-        "popupmenu_show" => {
-            let menu_items = map_array!(args[0], "Error get menu list array", |item| map_array!(
-                item,
-                "Error get menu item array",
-                |col| col.as_str().ok_or("Error get menu column")
-            ))?;
-
-            ui.popupmenu_show(
-                &CompleteItem::map(&menu_items),
-                try_int!(args[1]),
-                try_uint!(args[2]),
-                try_uint!(args[3]),
-            )
+        RedrawEvent::Scroll(count) => ui.on_scroll(count),
+        RedrawEvent::UpdateBg(bg) => ui.on_update_bg(bg),
+        RedrawEvent::UpdateFg(fg) => ui.on_update_fg(fg),
+        RedrawEvent::UpdateSp(sp) => ui.on_update_sp(sp),
+        RedrawEvent::DefaultColorsSet(colors) => ui.on_default_colors_set(colors),
+        RedrawEvent::ModeChange(mode, idx) => ui.on_mode_change(mode, idx),
+        RedrawEvent::Mouse(on) => ui.on_mouse(on),
+        RedrawEvent::Busy(busy) => ui.on_busy(busy),
+        RedrawEvent::Suspend => {
+            // there is no terminal to return to, so just minimize window
+            ui.on_command(NvimCommand::Suspend);
+            RepaintMode::Nothing
         }
-        "popupmenu_hide" => ui.popupmenu_hide(),
-        "popupmenu_select" => call!(ui->popupmenu_select(args: int)),
-        "tabline_update" => {
-            let tabs_out = map_array!(
-                args[1],
-                "Error get tabline list".to_owned(),
-                |tab| tab.as_map()
-                    .ok_or_else(|| "Error get map for tab".to_owned())
-                    .and_then(|tab_map| tab_map.to_attrs_map())
-                    .map(|tab_attrs| {
-                        let name_attr = tab_attrs
-                            .get("name")
-                            .and_then(|n| n.as_str().map(|s| s.to_owned()));
-                        let tab_attr = tab_attrs
-                            .get("tab")
-                            .map(|&tab_id| Tabpage::new(tab_id.clone()))
-                            .unwrap();
-
-                        (tab_attr, name_attr)
-                    })
-            )?;
-            ui.tabline_update(Tabpage::new(args[0].clone()), tabs_out)
+        RedrawEvent::PopupmenuShow(menu_items, selected, row, col) => {
+            ui.popupmenu_show(&CompleteItem::map(&menu_items), selected, row, col)
         }
-        "mode_info_set" => call!(ui->mode_info_set(args: bool, ext)),
-        "option_set" => call!(ui->option_set(args: str, val)),
-        "cmdline_show" => call!(ui->cmdline_show(args: ext, uint, str, str, uint, uint)),
-        "cmdline_block_show" => call!(ui->cmdline_block_show(args: ext)),
-        "cmdline_block_append" => call!(ui->cmdline_block_append(args: ext)),
-        "cmdline_hide" => call!(ui->cmdline_hide(args: uint)),
-        "cmdline_block_hide" => ui.cmdline_block_hide(),
-        "cmdline_pos" => call!(ui->cmdline_pos(args: uint, uint)),
-        "cmdline_special_char" => call!(ui->cmdline_special_char(args: str, bool, uint)),
-        "wildmenu_show" => call!(ui->wildmenu_show(args: ext)),
-        "wildmenu_hide" => ui.wildmenu_hide(),
-        "wildmenu_select" => call!(ui->wildmenu_select(args: int)),
-        _ => {
-            warn!("Event {}({:?})", method, args);
-            RepaintMode::Nothing
+        RedrawEvent::PopupmenuHide => ui.popupmenu_hide(),
+        RedrawEvent::PopupmenuSelect(selected) => ui.popupmenu_select(selected),
+        RedrawEvent::TablineUpdate(selected, tabs) => ui.tabline_update(selected, tabs),
+        RedrawEvent::ModeInfoSet(cursor_style_enabled, mode_infos) => {
+            ui.mode_info_set(cursor_style_enabled, mode_infos)
         }
-    };
-
-    Ok(repaint_mode)
+        RedrawEvent::OptionSet(name, val) => ui.option_set(name, val),
+        RedrawEvent::CmdlineShow(content, pos, firstc, prompt, indent, level) => {
+            ui.cmdline_show(content, pos, firstc, prompt, indent, level)
+        }
+        RedrawEvent::CmdlineBlockShow(content) => ui.cmdline_block_show(content),
+        RedrawEvent::CmdlineBlockAppend(content) => ui.cmdline_block_append(content),
+        RedrawEvent::CmdlineHide(level) => ui.cmdline_hide(level),
+        RedrawEvent::CmdlineBlockHide => ui.cmdline_block_hide(),
+        RedrawEvent::CmdlinePos(pos, level) => ui.cmdline_pos(pos, level),
+        RedrawEvent::CmdlineSpecialChar(c, shift, level) => {
+            ui.cmdline_special_char(c, shift, level)
+        }
+        RedrawEvent::WildmenuShow(items) => ui.wildmenu_show(items),
+        RedrawEvent::WildmenuHide => ui.wildmenu_hide(),
+        RedrawEvent::WildmenuSelect(selected) => ui.wildmenu_select(selected),
+        // batches are split by flush in redraw queue
+        RedrawEvent::Flush => RepaintMode::Nothing,
+    }
 }
 
 // Here two cases processed:
@@ -312,33 +701,31 @@ pub fn call(
 //
 // 2. postpone hide event when "show" event come bit later
 // but in new event batch
-pub fn remove_or_delay_uneeded_events(handler: &NvimHandler, params: &mut Vec<Value>) {
+pub fn remove_or_delay_uneeded_events(handler: &NvimHandler, events: &mut Vec<RedrawEvent>) {
     let mut show_popup_finded = false;
     let mut to_remove = Vec::new();
     let mut delayed_hide_event = None;
 
-    for (idx, val) in params.iter().enumerate().rev() {
-        if let Some(args) = val.as_array() {
-            match args[0].as_str() {
-                Some("popupmenu_show") => {
-                    show_popup_finded = true;
-                    handler.remove_scheduled_redraw_event();
-                }
-                Some("popupmenu_hide") if !show_popup_finded && delayed_hide_event.is_none() => {
-                    to_remove.push(idx);
-                    delayed_hide_event = Some(idx);
-                    handler.remove_scheduled_redraw_event();
-                }
-                Some("popupmenu_hide") => {
-                    to_remove.push(idx);
-                }
-                _ => (),
+    for (idx, event) in events.iter().enumerate().rev() {
+        match *event {
+            RedrawEvent::PopupmenuShow(..) => {
+                show_popup_finded = true;
+                handler.remove_scheduled_redraw_event();
+            }
+            RedrawEvent::PopupmenuHide if !show_popup_finded && delayed_hide_event.is_none() => {
+                to_remove.push(idx);
+                delayed_hide_event = Some(idx);
+                handler.remove_scheduled_redraw_event();
             }
+            RedrawEvent::PopupmenuHide => {
+                to_remove.push(idx);
+            }
+            _ => (),
         }
     }
 
     to_remove.iter().for_each(|&idx| {
-        let ev = params.remove(idx);
+        let ev = events.remove(idx);
         if let Some(delayed_hide_event_idx) = delayed_hide_event {
             if delayed_hide_event_idx == idx {
                 handler.schedule_redraw_event(ev);
@@ -355,14 +742,258 @@ pub struct CompleteItem<'a> {
 }
 
 impl<'a> CompleteItem<'a> {
-    fn map(menu: &'a [Vec<&str>]) -> Vec<Self> {
+    fn map(menu: &'a [Vec<String>]) -> Vec<Self> {
         menu.iter()
             .map(|menu| CompleteItem {
-                word: menu[0],
-                kind: menu[1],
-                menu: menu[2],
-                info: menu[3],
+                word: &menu[0],
+                kind: &menu[1],
+                menu: &menu[2],
+                info: &menu[3],
             })
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color::Color;
+
+    #[test]
+    fn test_parse_window_border() {
+        assert_eq!(Ok(4), parse_window_border("4"));
+        assert_eq!(Ok(32), parse_window_border("32"));
+        assert!(parse_window_border("33").is_err());
+        assert!(parse_window_border("-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_opacity() {
+        assert_eq!(Ok(0.8), parse_opacity("0.8"));
+        assert_eq!(Ok(1.0), parse_opacity(" 1 "));
+        assert!(parse_opacity("1.5").is_err());
+        assert!(parse_opacity("-0.1").is_err());
+        assert!(parse_opacity("abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_default_colors_set() {
+        let args = vec![
+            0xffffff.into(),
+            (-1).into(),
+            (-1).into(),
+            0.into(),
+            1.into(),
+        ];
+        match RedrawEvent::parse("default_colors_set", args) {
+            Ok(Some(RedrawEvent::DefaultColorsSet(colors))) => {
+                assert_eq!(Some(Color(1.0, 1.0, 1.0)), colors.fg);
+                assert_eq!(Some(Color(0.0, 0.0, 0.0)), colors.bg);
+                assert_eq!(None, colors.sp);
+            }
+            _ => panic!("default_colors_set not parsed"),
+        }
+
+        assert!(RedrawEvent::parse("default_colors_set", vec![1.into()]).is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(("cursor_goto", 2), RedrawEvent::CursorGoto(1, 2).summary());
+        assert_eq!(("put", 1), RedrawEvent::Put("aé".to_owned()).summary());
+        assert_eq!(("mouse_off", 0), RedrawEvent::Mouse(false).summary());
+    }
+
+    #[test]
+    fn test_parse_cursor_goto() {
+        match RedrawEvent::parse("cursor_goto", vec![1.into(), 2.into()]) {
+            Ok(Some(RedrawEvent::CursorGoto(1, 2))) => (),
+            _ => panic!("cursor_goto parsed wrong"),
+        }
+    }
+
+    #[test]
+    fn test_parse_missing_args() {
+        assert!(RedrawEvent::parse("cursor_goto", vec![1.into()]).is_err());
+        assert!(RedrawEvent::parse("popupmenu_show", vec![]).is_err());
+        assert!(RedrawEvent::parse("tabline_update", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_parse_wrong_type() {
+        assert!(RedrawEvent::parse("put", vec![1.into()]).is_err());
+        assert!(RedrawEvent::parse("scroll", vec!["1".into()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_short_menu_item() {
+        let items = Value::Array(vec![Value::Array(vec!["word".into(), "kind".into()])]);
+        let args = vec![items, 0.into(), 0.into(), 0.into()];
+        assert!(RedrawEvent::parse("popupmenu_show", args).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        match RedrawEvent::parse("unknown_event", vec![]) {
+            Ok(None) => (),
+            _ => panic!("unknown event must be skipped"),
+        }
+    }
+
+    const HANDLED_EVENTS: &[&str] = &[
+        "cursor_goto",
+        "put",
+        "clear",
+        "resize",
+        "highlight_set",
+        "eol_clear",
+        "set_scroll_region",
+        "scroll",
+        "update_bg",
+        "update_fg",
+        "update_sp",
+        "mode_change",
+        "mouse_on",
+        "mouse_off",
+        "busy_start",
+        "busy_stop",
+        "suspend",
+        "popupmenu_show",
+        "popupmenu_hide",
+        "popupmenu_select",
+        "tabline_update",
+        "mode_info_set",
+        "option_set",
+        "cmdline_show",
+        "cmdline_block_show",
+        "cmdline_block_append",
+        "cmdline_hide",
+        "cmdline_block_hide",
+        "cmdline_pos",
+        "cmdline_special_char",
+        "wildmenu_show",
+        "wildmenu_hide",
+        "wildmenu_select",
+    ];
+
+    /// Events that carry no arguments, so any payload is accepted
+    const NO_ARG_EVENTS: &[&str] = &[
+        "clear",
+        "eol_clear",
+        "mouse_on",
+        "mouse_off",
+        "busy_start",
+        "busy_stop",
+        "suspend",
+        "popupmenu_hide",
+        "cmdline_block_hide",
+        "wildmenu_hide",
+    ];
+
+    fn assert_parse(ev: &str, args: Vec<Value>, accepted: bool) {
+        match RedrawEvent::parse(ev, args.clone()) {
+            Ok(Some(_)) if accepted => (),
+            Err(_) if !accepted => (),
+            res => panic!(
+                "{} with {:?} expected to be {}, got {:?}",
+                ev,
+                args,
+                if accepted { "accepted" } else { "rejected" },
+                res.map(|ev| ev.map(|ev| ev.summary()))
+            ),
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_payloads() {
+        for ev in HANDLED_EVENTS {
+            for len in 0..7 {
+                assert_parse(ev, vec![Value::Nil; len], NO_ARG_EVENTS.contains(ev));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_wrong_typed_payloads() {
+        // events that still parse when every argument has this value
+        let wrong_values: Vec<(Value, &[&str])> = vec![
+            (Value::Nil, &[]),
+            (
+                Value::from(-1),
+                &[
+                    "scroll",
+                    "update_bg",
+                    "update_fg",
+                    "update_sp",
+                    "popupmenu_select",
+                    "wildmenu_select",
+                ],
+            ),
+            (Value::from("str"), &["put", "option_set"]),
+            (Value::from(true), &[]),
+            (Value::Array(vec![Value::Nil]), &[]),
+            (
+                Value::Map(vec![(Value::Nil, Value::Nil)]),
+                &["highlight_set"],
+            ),
+        ];
+
+        for ev in HANDLED_EVENTS {
+            for &(ref value, accepted) in &wrong_values {
+                assert_parse(
+                    ev,
+                    vec![value.clone(); 6],
+                    NO_ARG_EVENTS.contains(ev) || accepted.contains(ev),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_highlight_set_wrong_key() {
+        let attrs = Value::Map(vec![
+            (1.into(), true.into()),
+            ("bold".into(), true.into()),
+        ]);
+
+        match RedrawEvent::parse("highlight_set", vec![attrs]) {
+            Ok(Some(RedrawEvent::HighlightSet(attrs))) => {
+                assert_eq!(1, attrs.len());
+                assert!(attrs.contains_key("bold"));
+            }
+            _ => panic!("highlight_set parsed wrong"),
+        }
+
+        assert!(RedrawEvent::parse("highlight_set", vec![1.into()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_malformed_batch() {
+        let params = vec![
+            Value::Array(vec![]),
+            Value::Array(vec![1.into(), Value::Array(vec![])]),
+            Value::Array(vec!["cursor_goto".into(), 1.into()]),
+            Value::Array(vec!["unknown_event".into(), Value::Array(vec![])]),
+            Value::Array(vec!["unknown_event".into(), Value::Array(vec![])]),
+        ];
+
+        assert!(parse_redraw(params).is_empty());
+    }
+
+    #[test]
+    fn test_parse_redraw_batch() {
+        let params = vec![
+            Value::Array(vec![
+                "put".into(),
+                Value::Array(vec!["a".into()]),
+                Value::Array(vec![1.into()]),
+                Value::Array(vec!["b".into()]),
+            ]),
+            Value::Array(vec!["clear".into(), Value::Array(vec![])]),
+            1.into(),
+        ];
+
+        let events = parse_redraw(params);
+        assert_eq!(3, events.len());
+    }
+}
@@ -0,0 +1,162 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use neovim_lib::Value;
+use serde_json::{self, Map, Number};
+
+use dirs;
+
+const LOG_ENV: &str = "NVIM_GTK_LOG";
+
+/// Writes RPC messages received from nvim and replies to them as JSON lines
+///
+/// Enabled by `NVIM_GTK_LOG=rpc`, file is created in nvim-gtk data dir.
+#[derive(Clone)]
+pub struct RpcLog {
+    writer: Arc<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+
+impl RpcLog {
+    pub fn from_env() -> Option<Self> {
+        let enabled = env::var(LOG_ENV)
+            .map(|v| v.split(',').any(|target| target.trim() == "rpc"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        match RpcLog::new() {
+            Ok(log) => Some(log),
+            Err(e) => {
+                error!("Can't open RPC log: {}", e);
+                None
+            }
+        }
+    }
+
+    fn new() -> Result<Self, String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        let mut path = dirs::get_app_data_dir_create()?;
+        path.push(format!("rpc-{}.log", timestamp));
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        info!("RPC log {}", path.display());
+
+        Ok(RpcLog {
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn notification(&self, method: &str, params: &[Value]) {
+        self.write("in", "notification", method, to_json(&Value::Array(params.to_vec())));
+    }
+
+    pub fn request(&self, method: &str, params: &[Value]) {
+        self.write("in", "request", method, to_json(&Value::Array(params.to_vec())));
+    }
+
+    pub fn response(&self, method: &str, result: &Result<Value, Value>) {
+        let mut response = Map::new();
+        match *result {
+            Ok(ref value) => response.insert("result".to_owned(), to_json(value)),
+            Err(ref value) => response.insert("error".to_owned(), to_json(value)),
+        };
+        self.write("out", "response", method, serde_json::Value::Object(response));
+    }
+
+    fn write(&self, direction: &str, kind: &str, method: &str, payload: serde_json::Value) {
+        let elapsed = self.start.elapsed();
+        let timestamp = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+
+        let mut record = Map::new();
+        record.insert("time_ms".to_owned(), timestamp.into());
+        record.insert("direction".to_owned(), direction.into());
+        record.insert("type".to_owned(), kind.into());
+        record.insert("method".to_owned(), method.into());
+        record.insert("payload".to_owned(), payload);
+
+        let mut writer = self.writer.lock().unwrap();
+        let res = serde_json::to_writer(&mut *writer, &serde_json::Value::Object(record))
+            .map_err(|e| e.to_string())
+            .and_then(|_| writeln!(writer).map_err(|e| e.to_string()))
+            .and_then(|_| writer.flush().map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            error!("Can't write RPC log: {}", e);
+        }
+    }
+}
+
+/// Msgpack value as JSON, binary and ext values are written as byte arrays
+fn to_json(value: &Value) -> serde_json::Value {
+    match *value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => b.into(),
+        Value::Integer(ref i) => i
+            .as_i64()
+            .map(serde_json::Value::from)
+            .or_else(|| i.as_u64().map(serde_json::Value::from))
+            .unwrap_or(serde_json::Value::Null),
+        Value::F32(f) => Number::from_f64(f64::from(f)).map_or(serde_json::Value::Null, Into::into),
+        Value::F64(f) => Number::from_f64(f).map_or(serde_json::Value::Null, Into::into),
+        Value::String(ref s) => s
+            .as_str()
+            .map(serde_json::Value::from)
+            .unwrap_or_else(|| bytes_json(s.as_bytes())),
+        Value::Binary(ref b) => bytes_json(b),
+        Value::Array(ref a) => serde_json::Value::Array(a.iter().map(to_json).collect()),
+        Value::Map(ref m) => {
+            let map = m
+                .iter()
+                .map(|&(ref k, ref v)| {
+                    let key = match *k {
+                        Value::String(ref s) => s.as_str().map(str::to_owned),
+                        _ => None,
+                    }.unwrap_or_else(|| k.to_string());
+                    (key, to_json(v))
+                })
+                .collect();
+            serde_json::Value::Object(map)
+        }
+        Value::Ext(ty, ref data) => {
+            let mut ext = Map::new();
+            ext.insert("ext".to_owned(), ty.into());
+            ext.insert("data".to_owned(), bytes_json(data));
+            serde_json::Value::Object(ext)
+        }
+    }
+}
+
+fn bytes_json(bytes: &[u8]) -> serde_json::Value {
+    serde_json::Value::Array(bytes.iter().map(|&b| b.into()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let value = Value::Array(vec![
+            "grid_line".into(),
+            Value::Map(vec![("rgb".into(), true.into()), (1.into(), Value::Nil)]),
+            Value::Ext(0, vec![1]),
+            (-1).into(),
+        ]);
+        assert_eq!(
+            r#"["grid_line",{"1":null,"rgb":true},{"data":[1],"ext":0},-1]"#,
+            serde_json::to_string(&to_json(&value)).unwrap()
+        );
+    }
+}
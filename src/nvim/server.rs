@@ -0,0 +1,55 @@
+use std::fs;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use dirs;
+
+/// Incremented for each started nvim, so windows of one process get different addresses
+static SERVER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// New `--listen` address for embedded nvim, unique for process and window
+pub fn new_server_address() -> Result<String, String> {
+    let idx = SERVER_COUNT.fetch_add(1, Ordering::Relaxed);
+    let name = server_name(process::id(), idx);
+
+    if cfg!(target_os = "windows") {
+        return Ok(format!(r"\\.\pipe\{}", name));
+    }
+
+    let mut path = dirs::get_app_runtime_dir_create()?;
+    path.push(format!("{}.sock", name));
+
+    // left by process with same pid that was killed, nvim can't listen on it
+    if path.exists() {
+        remove_server_socket(&path.to_string_lossy());
+    }
+
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Nvim removes socket on exit, but it is left when nvim is killed
+pub fn remove_server_socket(address: &str) {
+    if cfg!(target_os = "windows") {
+        return;
+    }
+
+    if let Err(e) = fs::remove_file(address) {
+        if e.kind() != ::std::io::ErrorKind::NotFound {
+            warn!("Can't remove server socket {}: {}", address, e);
+        }
+    }
+}
+
+fn server_name(pid: u32, idx: usize) -> String {
+    format!("nvim-{}-{}", pid, idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_name() {
+        assert_eq!("nvim-10-2", server_name(10, 2));
+    }
+}
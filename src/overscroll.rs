@@ -0,0 +1,76 @@
+/// Each spring back step keeps this part of offset
+const SPRING_BACK_FACTOR: f64 = 0.75;
+/// Offset below this is not visible, so animation ends
+const MIN_OFFSET: f64 = 0.5;
+pub const SPRING_BACK_INTERVAL_MS: u32 = 16;
+
+/// Rubber band offset of grid when kinetic scroll hits first or last line
+///
+/// Positive offset moves grid down (pulled at top), negative moves it up.
+#[derive(Default)]
+pub struct Overscroll {
+    offset: f64,
+}
+
+impl Overscroll {
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Move by `delta` pixels, resistance grows near `max`, so offset never exceeds it
+    pub fn pull(&mut self, delta: f64, max: f64) {
+        if max <= 0.0 {
+            return;
+        }
+
+        let away = self.offset == 0.0 || delta.signum() == self.offset.signum();
+        let resistance = if away {
+            1.0 - (self.offset.abs() / max).min(1.0)
+        } else {
+            1.0
+        };
+        self.offset = (self.offset + delta * resistance).max(-max).min(max);
+    }
+
+    /// Returns false when grid is back in place
+    pub fn spring_back_step(&mut self) -> bool {
+        self.offset *= SPRING_BACK_FACTOR;
+        if self.offset.abs() < MIN_OFFSET {
+            self.offset = 0.0;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pull_is_capped() {
+        let mut overscroll = Overscroll::default();
+        for _ in 0..100 {
+            overscroll.pull(5.0, 8.0);
+        }
+        assert!(overscroll.offset() <= 8.0);
+        assert!(overscroll.offset() > 7.0);
+
+        overscroll.pull(-100.0, 8.0);
+        assert_eq!(-8.0, overscroll.offset());
+    }
+
+    #[test]
+    fn test_spring_back() {
+        let mut overscroll = Overscroll::default();
+        overscroll.pull(4.0, 8.0);
+
+        let mut steps = 0;
+        while overscroll.spring_back_step() {
+            steps += 1;
+            assert!(steps < 100);
+        }
+        assert_eq!(0.0, overscroll.offset());
+    }
+}
@@ -1,5 +1,5 @@
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp::min;
 use std::iter;
 
@@ -9,7 +9,7 @@ use glib;
 use gdk::{EventButton, EventType};
 use pango::{self, LayoutExt};
 
-use neovim_lib::{Neovim, NeovimApi};
+use neovim_lib::{Neovim, NeovimApiAsync};
 
 use color::ColorModel;
 use nvim::{self, ErrorReport, NeovimClient};
@@ -206,6 +206,10 @@ impl State {
 pub struct PopupMenu {
     popover: gtk::Popover,
     open: bool,
+    /// Cell popup points to: x, y, width, height
+    anchor: Cell<(i32, i32, i32, i32)>,
+    /// Vertical offset of anchor, used while input method preedit is shown
+    shift: Cell<i32>,
 
     state: Rc<RefCell<State>>,
 }
@@ -262,6 +266,8 @@ impl PopupMenu {
             popover,
             state,
             open: false,
+            anchor: Cell::new((0, 0, 0, 0)),
+            shift: Cell::new(0),
         }
     }
 
@@ -272,16 +278,29 @@ impl PopupMenu {
     pub fn show(&mut self, ctx: PopupMenuContext) {
         self.open = true;
 
-        self.popover.set_pointing_to(&gtk::Rectangle {
-            x: ctx.x,
-            y: ctx.y,
-            width: ctx.width,
-            height: ctx.height,
-        });
+        self.anchor.set((ctx.x, ctx.y, ctx.width, ctx.height));
+        self.update_pointing_to();
         self.state.borrow_mut().before_show(ctx);
         self.popover.popup()
     }
 
+    /// Move popup down by `shift` pixels, also applied to next shown popup
+    pub fn set_shift(&self, shift: i32) {
+        if self.shift.replace(shift) != shift && self.open {
+            self.update_pointing_to();
+        }
+    }
+
+    fn update_pointing_to(&self) {
+        let (x, y, width, height) = self.anchor.get();
+        self.popover.set_pointing_to(&gtk::Rectangle {
+            x,
+            y: y + self.shift.get(),
+            width,
+            height,
+        });
+    }
+
     pub fn hide(&mut self) {
         self.open = false;
         // popdown() in case of fast hide/show
@@ -352,7 +371,9 @@ pub fn tree_button_press(
                 .collect()
         };
 
-        nvim.input(&apply_command).report_err();
+        nvim.input_async(&apply_command)
+            .cb(|r| r.report_err())
+            .call();
     }
 }
 
@@ -150,8 +150,12 @@ impl Projects {
 
         let prj_ref = projects.clone();
         open_btn.connect_clicked(move |_| {
-            prj_ref.borrow().show_open_file_dlg();
-            let popup = prj_ref.borrow().popup.clone();
+            // dialog runs nested main loop, so projects must not stay borrowed
+            let (popup, shell) = {
+                let prj = prj_ref.borrow();
+                (prj.popup.clone(), prj.shell.clone())
+            };
+            show_open_file_dlg(&popup, &shell);
             popup.popdown();
         });
 
@@ -234,32 +238,6 @@ impl Projects {
             .unwrap()
     }
 
-    fn show_open_file_dlg(&self) {
-        let window = self.popup
-            .get_toplevel()
-            .unwrap()
-            .downcast::<gtk::Window>()
-            .ok();
-        let dlg = gtk::FileChooserDialog::new(
-            Some("Open Document"),
-            window.as_ref(),
-            gtk::FileChooserAction::Open,
-        );
-
-        const OPEN_ID: i32 = 0;
-        const CANCEL_ID: i32 = 1;
-
-        dlg.add_buttons(&[("_Open", OPEN_ID), ("_Cancel", CANCEL_ID)]);
-        if dlg.run() == OPEN_ID {
-            if let Some(filename) = dlg.get_filename() {
-                if let Some(filename) = filename.to_str() {
-                    self.shell.borrow().open_file(filename);
-                }
-            }
-        }
-        dlg.destroy();
-    }
-
     pub fn show(&mut self) {
         self.load_oldfiles();
 
@@ -267,12 +245,15 @@ impl Projects {
     }
 
     fn load_oldfiles(&mut self) {
-        let shell_borrow = self.shell.borrow();
-        let shell_state = shell_borrow.state.borrow_mut();
+        // shell is released before list store changes emit signals
+        let store = {
+            let shell = self.shell.borrow();
+            let shell_state = shell.state.borrow();
+            let nvim = shell_state.try_nvim();
+            nvim.map(|mut nvim| EntryStore::load(&mut nvim))
+        };
 
-        let nvim = shell_state.try_nvim();
-        if let Some(mut nvim) = nvim {
-            let store = EntryStore::load(&mut nvim);
+        if let Some(store) = store {
             store.populate(&self.get_list_store(), None);
             self.store = Some(store);
         }
@@ -369,6 +350,35 @@ impl Projects {
     }
 }
 
+fn show_open_file_dlg(popup: &Popover, shell: &RefCell<Shell>) {
+    let window = popup
+        .get_toplevel()
+        .unwrap()
+        .downcast::<gtk::Window>()
+        .ok();
+    let dlg = gtk::FileChooserDialog::new(
+        Some("Open Document"),
+        window.as_ref(),
+        gtk::FileChooserAction::Open,
+    );
+
+    if let Some(cwd) = shell.borrow().cwd() {
+        dlg.set_current_folder(&cwd);
+    }
+
+    const OPEN_ID: i32 = 0;
+    const CANCEL_ID: i32 = 1;
+
+    dlg.add_buttons(&[("_Open", OPEN_ID), ("_Cancel", CANCEL_ID)]);
+    if dlg.run() == OPEN_ID {
+        if let Some(filename) = dlg.get_filename() {
+            if let Some(filename) = filename.to_str() {
+                shell.borrow().open_file(filename);
+            }
+        }
+    }
+    dlg.destroy();
+}
 
 fn on_treeview_allocate(projects: Rc<RefCell<Projects>>) {
     let treeview_height = projects.borrow().calc_treeview_height();
@@ -417,6 +427,7 @@ fn list_old_files(nvim: &mut Neovim) -> Vec<String> {
 
 pub struct EntryStore {
     entries: Vec<Entry>,
+    loaded_paths: Vec<String>,
     changed: bool,
 }
 
@@ -428,7 +439,9 @@ impl EntryStore {
     pub fn load(nvim: &mut Neovim) -> EntryStore {
         let mut entries = Vec::new();
 
-        for project in ProjectSettings::load().projects {
+        let projects = ProjectSettings::load().projects;
+        let loaded_paths = projects.iter().map(|p| p.path.clone()).collect();
+        for project in projects {
             entries.push(project.to_entry());
         }
 
@@ -450,6 +463,7 @@ impl EntryStore {
 
         EntryStore {
             entries,
+            loaded_paths,
             changed: false,
         }
     }
@@ -462,6 +476,7 @@ impl EntryStore {
                     .filter(|e| e.project && e.stored)
                     .map(|p| p.to_entry_settings())
                     .collect(),
+                self.loaded_paths.clone(),
             ).save();
         }
     }
@@ -583,6 +598,10 @@ use toml;
 #[derive(Serialize, Deserialize, Default)]
 struct ProjectSettings {
     projects: Vec<ProjectEntrySettings>,
+    /// Paths of projects when settings were loaded, projects saved by other
+    /// instance since then are kept, removed by this one are not restored
+    #[serde(skip)]
+    loaded_paths: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -610,10 +629,57 @@ impl SettingsLoader for ProjectSettings {
     fn from_str(s: &str) -> Result<Self, String> {
         toml::from_str(&s).map_err(|e| format!("{}", e))
     }
+
+    fn merge(&self, saved: Self) -> Option<Self> {
+        let mut projects: Vec<_> = self.projects
+            .iter()
+            .map(|p| ProjectEntrySettings::new(&p.name, &p.path))
+            .collect();
+
+        for project in saved.projects {
+            let known = self.loaded_paths.contains(&project.path)
+                || projects.iter().any(|p| p.path == project.path);
+            if !known {
+                projects.push(project);
+            }
+        }
+
+        Some(ProjectSettings::new(projects, Vec::new()))
+    }
 }
 
 impl ProjectSettings {
-    fn new(projects: Vec<ProjectEntrySettings>) -> ProjectSettings {
-        ProjectSettings { projects }
+    fn new(projects: Vec<ProjectEntrySettings>, loaded_paths: Vec<String>) -> ProjectSettings {
+        ProjectSettings {
+            projects,
+            loaded_paths,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(settings: &ProjectSettings) -> Vec<&str> {
+        settings.projects.iter().map(|p| p.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge_keeps_removed_and_adds_new() {
+        let ours = ProjectSettings::new(
+            vec![ProjectEntrySettings::new("a", "/a")],
+            vec!["/a".to_owned(), "/b".to_owned()],
+        );
+        let saved = ProjectSettings::new(
+            vec![
+                ProjectEntrySettings::new("b", "/b"),
+                ProjectEntrySettings::new("c", "/c"),
+            ],
+            Vec::new(),
+        );
+
+        let merged = ours.merge(saved).unwrap();
+        assert_eq!(vec!["/a", "/c"], paths(&merged));
     }
 }
@@ -1,17 +1,28 @@
 use std::collections::HashSet;
 
+use cairo;
 use pango;
 use pango::prelude::*;
+use pangocairo;
+use unicode_width::UnicodeWidthChar;
 
+use misc;
 use sys::pango as sys_pango;
 
 use super::itemize::ItemizeIterator;
 use ui_model::StyledLine;
 
+/// CJK char used to measure 'guifontwide' advance
+const WIDE_SAMPLE_CHAR: &str = "\u{6c34}";
+
 pub struct Context {
     font_metrics: FontMetrix,
+    font_wide: Option<pango::FontDescription>,
     font_features: FontFeatures,
+    font_variants: FontVariants,
+    cell_widths: CellWidths,
     line_space: i32,
+    antialiasing: Option<AntiAliasing>,
 }
 
 impl Context {
@@ -19,16 +30,81 @@ impl Context {
         Context {
             line_space: 0,
             font_metrics: FontMetrix::new(pango_context, 0),
+            font_wide: None,
             font_features: FontFeatures::new(),
+            font_variants: FontVariants::new(),
+            cell_widths: CellWidths::new(),
+            antialiasing: None,
         }
     }
 
     pub fn update(&mut self, pango_context: pango::Context) {
+        if let Some(antialiasing) = self.antialiasing {
+            antialiasing.apply(&pango_context);
+        }
         self.font_metrics = FontMetrix::new(pango_context, self.line_space);
+        self.update_wide_variant();
+    }
+
+    /// Set 'guifontwide', empty `font` resets it
+    pub fn update_font_wide(&mut self, font: &str) {
+        self.font_wide = if font.trim().is_empty() {
+            None
+        } else {
+            Some(pango::FontDescription::from_string(font))
+        };
+        self.update_wide_variant();
+    }
+
+    /// Wide font is scaled, so double width char advance is exactly two cells
+    fn update_wide_variant(&mut self) {
+        let font_wide = match self.font_wide {
+            Some(ref font_wide) => font_wide,
+            None => {
+                self.font_variants.wide = None;
+                return;
+            }
+        };
+
+        let mut desc = font_wide.clone();
+        desc.set_size(self.font_metrics.font_desc.get_size());
+
+        let layout = pango::Layout::new(&self.font_metrics.pango_context);
+        layout.set_font_description(Some(&desc));
+        layout.set_text(WIDE_SAMPLE_CHAR);
+        let (width, _) = layout.get_size();
+
+        if width > 0 {
+            let target_width = 2 * self.font_metrics.cell_metrics.pango_char_width;
+            let size = f64::from(desc.get_size()) * f64::from(target_width) / f64::from(width);
+            desc.set_size(size as i32);
+        }
+
+        self.font_variants.wide = Some(desc);
     }
 
-    pub fn update_font_features(&mut self, font_features: FontFeatures) {
+    /// Returns true in case features changed and shaped glyphs must be invalidated
+    pub fn update_font_features(&mut self, font_features: FontFeatures) -> bool {
+        if self.font_features == font_features {
+            return false;
+        }
+
         self.font_features = font_features;
+        true
+    }
+
+    pub fn update_font_variant(&mut self, variant: &str, font: &str) -> Result<(), String> {
+        self.font_variants.set(variant, font)
+    }
+
+    /// Returns true in case widths changed and shaped glyphs must be invalidated
+    pub fn update_cell_widths(&mut self, cell_widths: CellWidths) -> bool {
+        if self.cell_widths == cell_widths {
+            return false;
+        }
+
+        self.cell_widths = cell_widths;
+        true
     }
 
     pub fn update_line_space(&mut self, line_space: i32) {
@@ -37,6 +113,18 @@ impl Context {
         self.font_metrics = FontMetrix::new(pango_context, self.line_space);
     }
 
+    /// Returns true in case font options changed and shaped glyphs must be invalidated
+    pub fn update_antialiasing(&mut self, antialiasing: AntiAliasing) -> bool {
+        if self.antialiasing == Some(antialiasing) {
+            return false;
+        }
+
+        self.antialiasing = Some(antialiasing);
+        let pango_context = self.font_metrics.pango_context.clone();
+        self.update(pango_context);
+        true
+    }
+
     pub fn itemize(&self, line: &StyledLine) -> Vec<pango::Item> {
         let attr_iter = line.attr_list.get_iterator();
 
@@ -69,6 +157,14 @@ impl Context {
         &self.font_features
     }
 
+    pub fn font_variants(&self) -> &FontVariants {
+        &self.font_variants
+    }
+
+    pub fn cell_widths(&self) -> &CellWidths {
+        &self.cell_widths
+    }
+
     pub fn font_families(&self) -> HashSet<String> {
         self.font_metrics
             .pango_context
@@ -79,6 +175,48 @@ impl Context {
     }
 }
 
+/// Width of ambiguous and emoji chars as nvim counts it,
+/// follows 'ambiwidth' and 'emoji' options
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellWidths {
+    pub ambiwidth_double: bool,
+    pub emoji: bool,
+}
+
+impl CellWidths {
+    pub fn new() -> Self {
+        CellWidths {
+            ambiwidth_double: false,
+            emoji: true,
+        }
+    }
+
+    pub fn text_cells(&self, text: &str) -> usize {
+        text.chars().map(|ch| self.char_cells(ch)).sum()
+    }
+
+    fn char_cells(&self, ch: char) -> usize {
+        if !self.emoji && is_emoji(ch) {
+            return 1;
+        }
+
+        let width = if self.ambiwidth_double {
+            ch.width_cjk()
+        } else {
+            ch.width()
+        };
+        width.unwrap_or(0)
+    }
+}
+
+/// Wide pictographs, nvim draws them in one cell with 'noemoji'
+fn is_emoji(ch: char) -> bool {
+    match ch {
+        '\u{2600}'..='\u{27bf}' | '\u{1f000}'..='\u{1faff}' => ch.width() == Some(2),
+        _ => false,
+    }
+}
+
 struct FontMetrix {
     pango_context: pango::Context,
     cell_metrics: CellMetrics,
@@ -144,23 +282,88 @@ impl CellMetrics {
     }
 }
 
+/// Font rasterization mode, hinting is chosen to match it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AntiAliasing {
+    None,
+    Gray,
+    Subpixel,
+}
+
+impl AntiAliasing {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "none" => Ok(AntiAliasing::None),
+            "gray" => Ok(AntiAliasing::Gray),
+            "subpixel" => Ok(AntiAliasing::Subpixel),
+            _ => Err(format!(
+                "Wrong anti aliasing '{}', expected none, gray or subpixel",
+                value
+            )),
+        }
+    }
+
+    fn font_options(self) -> cairo::FontOptions {
+        let (antialias, hint_style) = match self {
+            // without smoothing only full hinting keeps stems readable
+            AntiAliasing::None => (cairo::Antialias::None, cairo::HintStyle::Full),
+            AntiAliasing::Gray => (cairo::Antialias::Gray, cairo::HintStyle::Slight),
+            AntiAliasing::Subpixel => (cairo::Antialias::Subpixel, cairo::HintStyle::Slight),
+        };
+
+        let options = cairo::FontOptions::new();
+        options.set_antialias(antialias);
+        options.set_hint_style(hint_style);
+        options
+    }
+
+    fn apply(self, pango_context: &pango::Context) {
+        pangocairo::functions::context_set_font_options(
+            pango_context,
+            Some(&self.font_options()),
+        );
+    }
+}
+
 pub struct FontFeatures {
+    features: String,
     attr: Option<pango::Attribute>,
 }
 
 impl FontFeatures {
     pub fn new() -> Self {
-        FontFeatures { attr: None }
+        FontFeatures {
+            features: String::new(),
+            attr: None,
+        }
     }
 
-    pub fn from(font_features: String) -> Self {
-        if font_features.trim().is_empty() {
-            return Self::new();
-        }
+    /// Parse comma separated features list, like `ss01,calt=0`
+    pub fn parse(font_features: &str) -> Result<Self, String> {
+        let features = font_features
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| {
+                if misc::is_font_feature(f) {
+                    Ok(f)
+                } else {
+                    Err(format!(
+                        "Wrong font feature '{}', expected tag, +tag, -tag or tag=value",
+                        f
+                    ))
+                }
+            }).collect::<Result<Vec<_>, _>>()?
+            .join(",");
 
-        FontFeatures {
-            attr: sys_pango::attribute::new_features(&font_features),
+        if features.is_empty() {
+            return Ok(Self::new());
         }
+
+        Ok(FontFeatures {
+            attr: sys_pango::attribute::new_features(&features),
+            features,
+        })
     }
 
     pub fn insert_attr(&self, attr_list: &pango::AttrList) {
@@ -169,3 +372,96 @@ impl FontFeatures {
         }
     }
 }
+
+impl PartialEq for FontFeatures {
+    fn eq(&self, other: &FontFeatures) -> bool {
+        self.features == other.features
+    }
+}
+
+/// Explicit faces for bold, italic and bold italic text
+///
+/// Only style of text is taken from this faces, cell metrics are
+/// always calculated from regular font
+#[derive(Default)]
+pub struct FontVariants {
+    bold: Option<pango::FontDescription>,
+    italic: Option<pango::FontDescription>,
+    bold_italic: Option<pango::FontDescription>,
+    /// 'guifontwide' scaled to two cells width
+    wide: Option<pango::FontDescription>,
+}
+
+impl FontVariants {
+    pub fn new() -> Self {
+        FontVariants::default()
+    }
+
+    /// Set face for `bold`, `italic` or `bold_italic` variant, empty `font` resets it
+    pub fn set(&mut self, variant: &str, font: &str) -> Result<(), String> {
+        let desc = if font.trim().is_empty() {
+            None
+        } else {
+            let mut desc = pango::FontDescription::from_string(font);
+            // face given only by family keeps requested style
+            if variant != "italic" && desc.get_weight() == pango::Weight::Normal {
+                desc.set_weight(pango::Weight::Bold);
+            }
+            if variant != "bold" && desc.get_style() == pango::Style::Normal {
+                desc.set_style(pango::Style::Italic);
+            }
+            // size always comes from regular font
+            desc.unset_fields(pango::FontMask::SIZE);
+            Some(desc)
+        };
+
+        match variant {
+            "bold" => self.bold = desc,
+            "italic" => self.italic = desc,
+            "bold_italic" => self.bold_italic = desc,
+            _ => return Err(format!("Unknown font variant {}", variant)),
+        }
+
+        Ok(())
+    }
+
+    pub fn wide(&self) -> Option<&pango::FontDescription> {
+        self.wide.as_ref()
+    }
+
+    pub fn get(&self, bold: bool, italic: bool) -> Option<&pango::FontDescription> {
+        match (bold, italic) {
+            (true, true) => self.bold_italic.as_ref(),
+            (true, false) => self.bold.as_ref(),
+            (false, true) => self.italic.as_ref(),
+            (false, false) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_widths() {
+        let mut cell_widths = CellWidths::new();
+        assert_eq!(2, cell_widths.text_cells("\u{1f600}"));
+        assert_eq!(1, cell_widths.text_cells("\u{2190}"));
+
+        cell_widths.emoji = false;
+        cell_widths.ambiwidth_double = true;
+        assert_eq!(1, cell_widths.text_cells("\u{1f600}"));
+        assert_eq!(2, cell_widths.text_cells("\u{2190}"));
+        assert_eq!(2, cell_widths.text_cells("\u{6c34}"));
+    }
+
+    #[test]
+    fn test_antialiasing_parse() {
+        assert_eq!(Ok(AntiAliasing::None), AntiAliasing::parse("none"));
+        assert_eq!(Ok(AntiAliasing::Gray), AntiAliasing::parse(" gray"));
+        assert_eq!(Ok(AntiAliasing::Subpixel), AntiAliasing::parse("subpixel"));
+        assert!(AntiAliasing::parse("grey").is_err());
+        assert!(AntiAliasing::parse("").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use cairo;
+
+/// Area in drawing area coordinates: x, y, width, height
+pub type Area = (i32, i32, i32, i32);
+
+/// More areas are rendered at once as whole grid
+const MAX_DAMAGE_AREAS: usize = 32;
+
+#[derive(Debug, PartialEq)]
+enum Damage {
+    All,
+    Areas(Vec<Area>),
+}
+
+impl Damage {
+    fn add(&mut self, area: Area) {
+        let too_many = match *self {
+            Damage::Areas(ref mut areas) => {
+                areas.push(area);
+                areas.len() > MAX_DAMAGE_AREAS
+            }
+            Damage::All => false,
+        };
+
+        if too_many {
+            *self = Damage::All;
+        }
+    }
+
+    /// Areas inside scrolled region move with its content,
+    /// area that crosses region border is extended to cover both places
+    fn scroll(&mut self, region: Area, dy: i32) {
+        if let Damage::Areas(ref mut areas) = *self {
+            for area in areas.iter_mut().filter(|area| intersects(**area, region)) {
+                if contains(region, *area) {
+                    area.1 -= dy;
+                } else {
+                    let top = area.1.min(area.1 - dy);
+                    let bottom = (area.1 + area.3).max(area.1 + area.3 - dy);
+                    *area = (area.0, top, area.2, bottom - top);
+                }
+            }
+        }
+    }
+}
+
+fn contains(outer: Area, inner: Area) -> bool {
+    outer.0 <= inner.0
+        && inner.0 + inner.2 <= outer.0 + outer.2
+        && outer.1 <= inner.1
+        && inner.1 + inner.3 <= outer.1 + outer.3
+}
+
+fn intersects(a: Area, b: Area) -> bool {
+    a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+}
+
+/// Retained image of grid
+///
+/// Only damaged areas are rendered again on draw,
+/// scroll moves already rendered rows inside surface.
+pub struct GridSurface {
+    surface: Option<(cairo::Surface, i32, i32)>,
+    damage: Damage,
+}
+
+impl GridSurface {
+    pub fn new() -> Self {
+        GridSurface {
+            surface: None,
+            damage: Damage::All,
+        }
+    }
+
+    pub fn damage_all(&mut self) {
+        self.damage = Damage::All;
+    }
+
+    pub fn damage_area(&mut self, area: Area) {
+        self.damage.add(area);
+    }
+
+    /// Move region content by dy pixels up (down for negative dy)
+    ///
+    /// Return false in case surface can't be used,
+    /// so region must be rendered from scratch
+    pub fn scroll(&mut self, region: Area, dy: i32) -> bool {
+        if self.damage == Damage::All {
+            return false;
+        }
+
+        let surface = match self.surface {
+            Some((ref surface, ..)) => surface,
+            None => return false,
+        };
+
+        let (x, y, width, height) = region;
+        let ctx = cairo::Context::new(surface);
+        ctx.rectangle(f64::from(x), f64::from(y), f64::from(width), f64::from(height));
+        ctx.clip();
+
+        // source and target are same surface, so copy through group
+        ctx.push_group();
+        ctx.set_source_surface(surface, 0.0, f64::from(-dy));
+        ctx.paint();
+        ctx.pop_group_to_source();
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.paint();
+
+        self.damage.scroll(region, dy);
+        true
+    }
+
+    /// Render damaged areas with draw_fn and paint whole surface to ctx
+    pub fn draw<F>(&mut self, ctx: &cairo::Context, width: i32, height: i32, draw_fn: F)
+    where
+        F: FnOnce(&cairo::Context),
+    {
+        let size_changed = self
+            .surface
+            .as_ref()
+            .map_or(true, |&(_, w, h)| w != width || h != height);
+        if size_changed {
+            let surface = ctx
+                .get_target()
+                .create_similar(cairo::Content::ColorAlpha, width, height);
+            self.surface = Some((surface, width, height));
+            self.damage = Damage::All;
+        }
+
+        let surface = &self.surface.as_ref().unwrap().0;
+
+        match ::std::mem::replace(&mut self.damage, Damage::Areas(vec![])) {
+            Damage::All => draw_fn(&cairo::Context::new(surface)),
+            Damage::Areas(ref areas) if !areas.is_empty() => {
+                let surface_ctx = cairo::Context::new(surface);
+                for &(x, y, width, height) in areas {
+                    surface_ctx.rectangle(
+                        f64::from(x),
+                        f64::from(y),
+                        f64::from(width),
+                        f64::from(height),
+                    );
+                }
+                surface_ctx.clip();
+                draw_fn(&surface_ctx);
+            }
+            Damage::Areas(_) => (),
+        }
+
+        ctx.set_source_surface(surface, 0.0, 0.0);
+        ctx.paint();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_damage() {
+        let mut damage = Damage::Areas(vec![(0, 20, 10, 10), (0, 100, 10, 10)]);
+        damage.scroll((0, 0, 50, 50), 10);
+        assert_eq!(Damage::Areas(vec![(0, 10, 10, 10), (0, 100, 10, 10)]), damage);
+    }
+
+    #[test]
+    fn test_scroll_damage_crossing_region() {
+        let mut damage = Damage::Areas(vec![(0, 45, 10, 10)]);
+        damage.scroll((0, 0, 50, 50), 10);
+        assert_eq!(Damage::Areas(vec![(0, 35, 10, 20)]), damage);
+    }
+
+    #[test]
+    fn test_repeated_scroll_damage() {
+        let mut damage = Damage::Areas(vec![(0, 40, 10, 10), (0, 100, 10, 10)]);
+        for _ in 0..5 {
+            damage.scroll((0, 0, 50, 90), 5);
+        }
+        assert_eq!(Damage::Areas(vec![(0, 15, 10, 10), (0, 100, 10, 10)]), damage);
+    }
+
+    #[test]
+    fn test_many_areas_damage_all() {
+        let mut damage = Damage::Areas(vec![]);
+        for i in 0..MAX_DAMAGE_AREAS as i32 {
+            damage.add((0, i * 10, 10, 10));
+        }
+        assert_ne!(Damage::All, damage);
+        damage.add((0, 0, 10, 10));
+        assert_eq!(Damage::All, damage);
+    }
+
+    #[test]
+    fn test_all_damage() {
+        let mut damage = Damage::All;
+        damage.add((0, 0, 10, 10));
+        damage.scroll((0, 0, 50, 50), 10);
+        assert_eq!(Damage::All, damage);
+    }
+
+    #[test]
+    fn test_intersects() {
+        assert!(intersects((0, 0, 10, 10), (5, 5, 10, 10)));
+        assert!(!intersects((0, 0, 10, 10), (10, 0, 10, 10)));
+    }
+}
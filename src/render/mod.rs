@@ -1,14 +1,21 @@
 mod context;
+mod grid_surface;
 mod itemize;
 mod model_clip_iterator;
 
 pub use self::context::CellMetrics;
-pub use self::context::{Context, FontFeatures};
+pub use self::context::{AntiAliasing, CellWidths, Context, FontFeatures, FontVariants};
+pub use self::grid_surface::GridSurface;
 use self::model_clip_iterator::{ModelClipIteratorFactory, RowView};
 
+use std::slice;
+
 use cairo;
 use color;
+use glib::translate::*;
 use pango;
+use pango::LayoutExt;
+use pango_sys;
 use pangocairo;
 use sys::pangocairo::*;
 
@@ -29,6 +36,259 @@ impl ContextAlpha for cairo::Context {
     }
 }
 
+/// Smallest grid sent to nvim, it rejects empty grid
+pub const MIN_GRID_SIZE: (usize, usize) = (12, 3);
+
+/// Columns and rows fitting to grid area, zero when area is smaller than one cell
+pub fn grid_size(width: i32, height: i32, cell_metrics: &CellMetrics) -> (usize, usize) {
+    let cells = |size: i32, cell_size: f64| {
+        if size <= 0 || cell_size <= 0.0 {
+            0
+        } else {
+            (f64::from(size) / cell_size).trunc() as usize
+        }
+    };
+
+    (
+        cells(width, cell_metrics.char_width),
+        cells(height, cell_metrics.line_height),
+    )
+}
+
+pub fn clamp_grid_size((columns, rows): (usize, usize)) -> (usize, usize) {
+    (columns.max(MIN_GRID_SIZE.0), rows.max(MIN_GRID_SIZE.1))
+}
+
+/// Space between grid and drawing area border, filled by background
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Padding {
+    pub top: i32,
+    pub bottom: i32,
+    pub left: i32,
+    pub right: i32,
+}
+
+impl Padding {
+    pub fn new(padding: i32) -> Self {
+        Padding {
+            top: padding,
+            bottom: padding,
+            left: padding,
+            right: padding,
+        }
+    }
+
+    /// Parse `all`, `vertical horizontal` or `top right bottom left` pixel values
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let values = value
+            .split_whitespace()
+            .map(|v| v.parse::<i32>().map(|v| v.max(0)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Wrong padding '{}': {}", value, e))?;
+
+        match values.as_slice() {
+            [all] => Ok(Padding::new(*all)),
+            [vertical, horizontal] => Ok(Padding {
+                top: *vertical,
+                bottom: *vertical,
+                left: *horizontal,
+                right: *horizontal,
+            }),
+            [top, right, bottom, left] => Ok(Padding {
+                top: *top,
+                bottom: *bottom,
+                left: *left,
+                right: *right,
+            }),
+            _ => Err(format!(
+                "Wrong padding '{}', expected 1, 2 or 4 values",
+                value
+            )),
+        }
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Padding::new(4)
+    }
+}
+
+/// Geometry of current window from `getwininfo()` and `winsaveview()`,
+/// screen positions are zero based
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct WinInfo {
+    pub top: usize,
+    pub left: usize,
+    pub height: usize,
+    pub width: usize,
+    /// Columns used by 'number', 'signcolumn' and 'foldcolumn'
+    pub textoff: usize,
+    /// First buffer column shown in window, scrolled by 'nowrap'
+    pub leftcol: usize,
+}
+
+impl WinInfo {
+    /// Build from values of `WIN_INFO_ARGS` expressions
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let int_args: Vec<i64> = args
+            .iter()
+            .map(|arg| arg.parse::<i64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        if int_args.iter().any(|&arg| arg < 0) {
+            return None;
+        }
+
+        match *int_args.as_slice() {
+            [row, col, height, width, textoff, leftcol] if row > 0 && col > 0 => Some(WinInfo {
+                top: (row - 1) as usize,
+                left: (col - 1) as usize,
+                height: height as usize,
+                width: width as usize,
+                textoff: textoff as usize,
+                leftcol: leftcol as usize,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Screen column of zero based buffer column, None if it is scrolled out of window
+    pub fn screen_col(&self, col: usize) -> Option<usize> {
+        if col < self.leftcol {
+            return None;
+        }
+
+        let screen_col = self.left + self.textoff + col - self.leftcol;
+        if screen_col < self.left + self.width {
+            Some(screen_col)
+        } else {
+            None
+        }
+    }
+}
+
+/// Nvim expressions for `WinInfo::parse`
+pub const WIN_INFO_ARGS: &[&str] = &[
+    "getwininfo(win_getid())[0].winrow",
+    "getwininfo(win_getid())[0].wincol",
+    "getwininfo(win_getid())[0].height",
+    "getwininfo(win_getid())[0].width",
+    "getwininfo(win_getid())[0].textoff",
+    "winsaveview().leftcol",
+];
+
+/// Screen position of 'colorcolumn' stripes in current window
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct ColorColumns {
+    pub columns: Vec<usize>,
+    pub top: usize,
+    pub height: usize,
+}
+
+impl ColorColumns {
+    /// Build from 'colorcolumn' and 'textwidth' values,
+    /// columns scrolled out of window are skipped
+    pub fn parse(colorcolumn: &str, textwidth: i64, window: &WinInfo) -> Self {
+        let columns = colorcolumn
+            .split(',')
+            .filter_map(|col| {
+                let col = col.trim();
+                if col.starts_with('+') || col.starts_with('-') {
+                    if textwidth <= 0 {
+                        return None;
+                    }
+                    col.parse::<i64>().ok().map(|offset| textwidth + offset)
+                } else {
+                    col.parse::<i64>().ok()
+                }
+            })
+            .filter(|&col| col > 0)
+            .filter_map(|col| window.screen_col((col - 1) as usize))
+            .collect();
+
+        ColorColumns {
+            columns,
+            top: window.top,
+            height: window.height,
+        }
+    }
+}
+
+/// Current window with 'nowrap', rows with text up to right edge get a fade out mark
+#[derive(Clone, PartialEq, Debug)]
+pub struct NowrapWindow {
+    pub top: usize,
+    pub left: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl NowrapWindow {
+    /// Build from `&wrap`, one based `win_screenpos(0)` and window size,
+    /// wrapped window has no marks
+    pub fn parse(wrap: &str, row: i64, col: i64, height: i64, width: i64) -> Option<Self> {
+        if wrap != "0" || row < 1 || col < 1 || height < 1 || width < 1 {
+            return None;
+        }
+
+        Some(NowrapWindow {
+            top: (row - 1) as usize,
+            left: (col - 1) as usize,
+            height: height as usize,
+            width: width as usize,
+        })
+    }
+
+    /// Line likely continues behind window edge when its last visible cell is not blank
+    pub fn truncated_rows(&self, model: &ui_model::UiModel) -> Vec<usize> {
+        let last_col = self.left + self.width - 1;
+        if last_col >= model.columns {
+            return Vec::new();
+        }
+
+        (self.top..(self.top + self.height).min(model.rows))
+            .filter(|&row| {
+                let text = model.cell_text(row, last_col);
+                !text.is_empty() && text != " "
+            })
+            .collect()
+    }
+}
+
+/// Fade last visible column of truncated rows into background
+pub fn draw_nowrap_marks(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    color_model: &color::ColorModel,
+    window: &NowrapWindow,
+    rows: &[usize],
+) {
+    let &CellMetrics {
+        line_height,
+        char_width,
+        ..
+    } = cell_metrics;
+    let x = (window.left + window.width - 1) as f64 * char_width;
+    let bg = &color_model.bg_color;
+
+    ctx.set_operator(cairo::Operator::Over);
+    for &row in rows {
+        let y = row as f64 * line_height;
+        let fade = cairo::LinearGradient::new(x, 0.0, x + char_width, 0.0);
+        fade.add_color_stop_rgba(0.0, bg.0, bg.1, bg.2, 0.0);
+        fade.add_color_stop_rgba(1.0, bg.0, bg.1, bg.2, 0.8);
+        ctx.set_source(&fade);
+        ctx.rectangle(x, y, char_width, line_height);
+        ctx.fill();
+    }
+}
+
+/// Glyphs are drawn second time with this offset when font has no bold face
+const SYNTHETIC_BOLD_OFFSET: f64 = 1.0;
+/// Skew of oblique text when font has no italic face
+const SYNTHETIC_SLANT: f64 = 0.2;
+
 pub fn fill_background(ctx: &cairo::Context, color_model: &color::ColorModel, alpha: Option<f64>) {
     // must be dest over here
     //ctx.set_operator(cairo::Operator::DestOver);
@@ -36,6 +296,96 @@ pub fn fill_background(ctx: &cairo::Context, color_model: &color::ColorModel, al
     ctx.paint();
 }
 
+/// Cover whole clip with translucent background, used for unfocused window
+pub fn draw_dim(ctx: &cairo::Context, color_model: &color::ColorModel, percent: u8) {
+    let bg = &color_model.bg_color;
+    ctx.set_operator(cairo::Operator::Over);
+    ctx.set_source_rgba(bg.0, bg.1, bg.2, f64::from(percent) / 100.0);
+    ctx.paint();
+}
+
+/// Highlight whole rows, used for GUI side selection
+pub fn draw_selection(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    color_model: &color::ColorModel,
+    start_row: usize,
+    end_row: usize,
+) {
+    let (_x1, _y1, x2, _y2) = ctx.clip_extents();
+    let fg = &color_model.fg_color;
+
+    ctx.set_operator(cairo::Operator::Over);
+    ctx.set_source_rgba(fg.0, fg.1, fg.2, 0.3);
+    ctx.rectangle(
+        0.0,
+        start_row as f64 * cell_metrics.line_height,
+        x2,
+        (end_row - start_row + 1) as f64 * cell_metrics.line_height,
+    );
+    ctx.fill();
+}
+
+/// Placeholder for drawing area smaller than one cell
+pub fn draw_too_small(ctx: &cairo::Context, color_model: &color::ColorModel, width: f64, height: f64) {
+    let bg = &color_model.bg_color;
+    ctx.set_source_rgb(bg.0, bg.1, bg.2);
+    ctx.paint();
+
+    let layout = pangocairo::functions::create_layout(ctx).unwrap();
+    layout.set_markup("<small>window too small</small>");
+    let (text_width, text_height) = layout.get_pixel_size();
+
+    let fg = &color_model.fg_color;
+    ctx.set_source_rgba(fg.0, fg.1, fg.2, 0.6);
+    ctx.move_to(
+        ((width - f64::from(text_width)) / 2.0).max(0.0),
+        ((height - f64::from(text_height)) / 2.0).max(0.0),
+    );
+    pangocairo::functions::update_layout(ctx, &layout);
+    pangocairo::functions::show_layout(ctx, &layout);
+}
+
+/// Dim rows above message area and show hint while hit-enter prompt is pending
+pub fn draw_hit_enter(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    color_model: &color::ColorModel,
+    width: f64,
+    msg_row: usize,
+) {
+    let msg_y = msg_row as f64 * cell_metrics.line_height;
+
+    ctx.set_operator(cairo::Operator::Over);
+    ctx.set_source_rgba(0.0, 0.0, 0.0, 0.2);
+    ctx.rectangle(0.0, 0.0, width, msg_y);
+    ctx.fill();
+
+    let layout = pangocairo::functions::create_layout(ctx).unwrap();
+    layout.set_markup("<small>press Enter to continue</small>");
+    let (hint_width, hint_height) = layout.get_pixel_size();
+    let (hint_width, hint_height) = (f64::from(hint_width), f64::from(hint_height));
+    let x = (width - hint_width - cell_metrics.char_width * 2.0).max(0.0);
+    let y = (msg_y - hint_height - cell_metrics.line_height / 2.0).max(0.0);
+
+    let bg = color_model.pmenu_bg();
+    let margin = cell_metrics.char_width / 2.0;
+    ctx.set_source_rgba(bg.0, bg.1, bg.2, 0.9);
+    ctx.rectangle(
+        x - margin,
+        y - margin,
+        hint_width + margin * 2.0,
+        hint_height + margin * 2.0,
+    );
+    ctx.fill();
+
+    let fg = color_model.pmenu_fg();
+    ctx.set_source_rgb(fg.0, fg.1, fg.2);
+    ctx.move_to(x, y);
+    pangocairo::functions::update_layout(ctx, &layout);
+    pangocairo::functions::show_layout(ctx, &layout);
+}
+
 pub fn render<C: Cursor>(
     ctx: &cairo::Context,
     cursor: &C,
@@ -43,30 +393,50 @@ pub fn render<C: Cursor>(
     ui_model: &ui_model::UiModel,
     color_model: &color::ColorModel,
     bg_alpha: Option<f64>,
+    cursor_line: Option<&WinInfo>,
+    color_columns: &ColorColumns,
 ) {
     let cell_metrics = font_ctx.cell_metrics();
     let &CellMetrics { char_width, .. } = cell_metrics;
     let (cursor_row, cursor_col) = ui_model.get_cursor();
+    // cleared grid has no text and no cell backgrounds,
+    // it is filled at once by fill_background
+    let cleared = ui_model.is_cleared();
 
     // draw text
     ctx.set_operator(cairo::Operator::Over);
 
-    for cell_view in ui_model.get_clip_iterator(ctx, cell_metrics) {
-        let mut line_x = 0.0;
+    if !cleared {
+        for cell_view in ui_model.get_clip_iterator(ctx, cell_metrics) {
+            let mut line_x = 0.0;
 
-        for (col, cell) in cell_view.line.line.iter().enumerate() {
-            draw_cell(&cell_view, color_model, cell, col, line_x);
-            draw_underline(&cell_view, color_model, cell, line_x);
+            for (col, cell) in cell_view.line.line.iter().enumerate() {
+                draw_cell(&cell_view, color_model, cell, col, line_x);
+                draw_underline(&cell_view, color_model, cell, line_x);
 
-            line_x += char_width;
+                line_x += char_width;
+            }
         }
     }
 
+    // draw cursor trail while cursor moves
+    if let Some((trail_row, trail_col)) = cursor.trail_position() {
+        let (anim_row, anim_col) = cursor.position(cursor_row, cursor_col);
+        draw_cursor_trail(
+            ctx,
+            cell_metrics,
+            (trail_row, trail_col),
+            (anim_row, anim_col),
+            &color_model.cursor_bg(),
+        );
+    }
+
     // draw cursor
     ctx.set_operator(cairo::Operator::Xor);
     let (_x1, _y1, x2, y2) = ctx.clip_extents();
-    let line_x = cursor_col as f64 * cell_metrics.char_width;
-    let line_y = cursor_row as f64 * cell_metrics.line_height;
+    let (anim_row, anim_col) = cursor.position(cursor_row, cursor_col);
+    let line_x = anim_col * cell_metrics.char_width;
+    let line_y = anim_row * cell_metrics.line_height;
 
     if line_x < x2 && line_y < y2 {
         if let Some(cursor_line) = ui_model.model().get(cursor_row) {
@@ -81,16 +451,108 @@ pub fn render<C: Cursor>(
 
     // draw background
     ctx.set_operator(cairo::Operator::DestOver);
-    for cell_view in ui_model.get_clip_iterator(ctx, cell_metrics) {
-        let mut line_x = 0.0;
+    if !cleared {
+        for cell_view in ui_model.get_clip_iterator(ctx, cell_metrics) {
+            let mut line_x = 0.0;
+
+            for (col, cell) in cell_view.line.line.iter().enumerate() {
+                draw_cell_bg(&cell_view, color_model, cell, col, line_x, bg_alpha);
+                line_x += char_width;
+            }
+        }
+    }
 
-        for (col, cell) in cell_view.line.line.iter().enumerate() {
-            draw_cell_bg(&cell_view, color_model, cell, col, line_x, bg_alpha);
-            line_x += char_width;
+    // draw cursor line behind cells background
+    if let Some(window) = cursor_line {
+        if let Some(bg) = color_model.cursor_line_bg() {
+            draw_cursor_line(ctx, cell_metrics, window, cursor_row, &bg, bg_alpha);
+        }
+    }
+
+    if !color_columns.columns.is_empty() {
+        if let Some(bg) = color_model.color_column_bg() {
+            draw_color_columns(ctx, cell_metrics, color_columns, &bg, bg_alpha);
         }
     }
 }
 
+fn draw_color_columns(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    color_columns: &ColorColumns,
+    bg: &color::Color,
+    bg_alpha: Option<f64>,
+) {
+    let &CellMetrics {
+        char_width,
+        line_height,
+        ..
+    } = cell_metrics;
+
+    ctx.set_source_rgbo(bg, bg_alpha);
+    for &col in &color_columns.columns {
+        ctx.rectangle(
+            col as f64 * char_width,
+            color_columns.top as f64 * line_height,
+            char_width,
+            color_columns.height as f64 * line_height,
+        );
+    }
+    ctx.fill();
+}
+
+fn draw_cursor_trail(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    from: (f64, f64),
+    to: (f64, f64),
+    bg: &color::Color,
+) {
+    let &CellMetrics {
+        char_width,
+        line_height,
+        ..
+    } = cell_metrics;
+
+    let x = from.1.min(to.1) * char_width;
+    let y = from.0.min(to.0) * line_height;
+    let width = (from.1 - to.1).abs() * char_width + char_width;
+    let height = (from.0 - to.0).abs() * line_height + line_height;
+
+    ctx.set_source_rgba(bg.0, bg.1, bg.2, 0.3);
+    ctx.rectangle(x, y, width, height);
+    ctx.fill();
+}
+
+/// Stripe is limited to current window, cursor outside of it is in cmdline
+fn draw_cursor_line(
+    ctx: &cairo::Context,
+    cell_metrics: &CellMetrics,
+    window: &WinInfo,
+    cursor_row: usize,
+    bg: &color::Color,
+    bg_alpha: Option<f64>,
+) {
+    if cursor_row < window.top || cursor_row >= window.top + window.height {
+        return;
+    }
+
+    let &CellMetrics {
+        char_width,
+        line_height,
+        ..
+    } = cell_metrics;
+
+    ctx.set_source_rgbo(bg, bg_alpha);
+    ctx.rectangle(
+        window.left as f64 * char_width,
+        cursor_row as f64 * line_height,
+        window.width as f64 * char_width,
+        line_height,
+    );
+    ctx.fill();
+}
+
 fn draw_underline(
     cell_view: &RowView,
     color_model: &color::ColorModel,
@@ -159,9 +621,9 @@ fn draw_cell_bg(
         ..
     } = cell_view;
 
-    let bg = color_model.cell_bg(cell);
+    let bg = color_model.blended_cell_bg(cell);
 
-    if let Some(bg) = bg {
+    if let Some(ref bg) = bg {
         if !line.is_binded_to_item(col) {
             if bg != &color_model.bg_color {
                 ctx.set_source_rgbo(bg, bg_alpha);
@@ -200,10 +662,33 @@ fn draw_cell(
         if let Some(ref glyphs) = item.glyphs {
             let fg = color_model.actual_cell_fg(cell);
 
-            ctx.move_to(line_x, line_y + ascent);
             ctx.set_source_rgb(fg.0, fg.1, fg.2);
 
-            show_glyph_string(ctx, item.font(), glyphs);
+            if item.synthetic_italic || item.glyph_scale.is_some() {
+                // scale and slant glyphs around baseline
+                ctx.save();
+                ctx.translate(line_x, line_y + ascent);
+                if let Some(scale) = item.glyph_scale {
+                    ctx.scale(scale, scale);
+                }
+                if item.synthetic_italic {
+                    ctx.transform(cairo::Matrix::new(1.0, 0.0, -SYNTHETIC_SLANT, 1.0, 0.0, 0.0));
+                }
+                ctx.move_to(0.0, 0.0);
+                show_glyph_string(ctx, item.font(), glyphs);
+                if item.synthetic_bold {
+                    ctx.move_to(SYNTHETIC_BOLD_OFFSET, 0.0);
+                    show_glyph_string(ctx, item.font(), glyphs);
+                }
+                ctx.restore();
+            } else {
+                ctx.move_to(line_x, line_y + ascent);
+                show_glyph_string(ctx, item.font(), glyphs);
+                if item.synthetic_bold {
+                    ctx.move_to(line_x + SYNTHETIC_BOLD_OFFSET, line_y + ascent);
+                    show_glyph_string(ctx, item.font(), glyphs);
+                }
+            }
         }
     }
 }
@@ -215,7 +700,12 @@ pub fn shape_dirty(
 ) {
     for line in ui_model.model_mut() {
         if line.dirty_line {
-            let styled_line = ui_model::StyledLine::from(line, color_model, ctx.font_features());
+            let styled_line = ui_model::StyledLine::from(
+                line,
+                color_model,
+                ctx.font_features(),
+                ctx.font_variants(),
+            );
             let items = ctx.itemize(&styled_line);
             line.merge(&styled_line, &items);
 
@@ -223,18 +713,24 @@ pub fn shape_dirty(
                 if cell.dirty {
                     if let Some(item) = line.item_line[col].as_mut() {
                         let mut glyphs = pango::GlyphString::new();
+                        let advance;
                         {
                             let analysis = item.analysis();
                             let offset = item.item.offset() as usize;
                             let length = item.item.length() as usize;
-                            pango::shape(
-                                &styled_line.line_str[offset..offset + length],
-                                analysis,
+                            let text = &styled_line.line_str[offset..offset + length];
+                            pango::shape(text, analysis, &mut glyphs);
+                            advance = align_double_width_glyphs(
                                 &mut glyphs,
+                                text,
+                                ctx.cell_metrics().pango_char_width,
+                                ctx.cell_widths(),
                             );
                         }
 
                         item.set_glyphs(ctx, glyphs);
+                        item.update_glyph_scale(advance, ctx.cell_metrics().pango_char_width);
+                        item.update_synthetic_style(cell.attrs.bold, cell.attrs.italic);
                     }
                 }
 
@@ -245,3 +741,220 @@ pub fn shape_dirty(
         }
     }
 }
+
+/// Fallback font can give double width char some other advance,
+/// so advance is set to exactly two cells to keep next glyphs on grid.
+///
+/// Returns total advance of glyphs after alignment
+fn align_double_width_glyphs(
+    glyphs: &mut pango::GlyphString,
+    text: &str,
+    char_width: i32,
+    cell_widths: &CellWidths,
+) -> i32 {
+    unsafe {
+        let raw: *mut pango_sys::PangoGlyphString = glyphs.to_glib_none_mut().0;
+        let count = (*raw).num_glyphs as usize;
+        if count == 0 {
+            return 0;
+        }
+
+        let infos = slice::from_raw_parts_mut((*raw).glyphs, count);
+        let clusters = slice::from_raw_parts((*raw).log_clusters, count);
+        let widths: Vec<i32> = infos.iter().map(|info| info.geometry.width).collect();
+
+        for (first, last, diff) in
+            double_width_clusters(clusters, &widths, text, char_width, cell_widths)
+        {
+            // center glyph inside two cells
+            infos[first].geometry.x_offset += diff / 2;
+            infos[last].geometry.width += diff;
+        }
+
+        infos.iter().map(|info| info.geometry.width).sum()
+    }
+}
+
+/// Glyph clusters of double width chars with advance different from two cells:
+/// first glyph, last glyph and advance difference
+fn double_width_clusters(
+    clusters: &[i32],
+    widths: &[i32],
+    text: &str,
+    char_width: i32,
+    cell_widths: &CellWidths,
+) -> Vec<(usize, usize, i32)> {
+    let mut starts: Vec<usize> = clusters.iter().map(|&c| c as usize).collect();
+    starts.sort();
+    starts.dedup();
+
+    let mut result = vec![];
+    let mut idx = 0;
+    while idx < clusters.len() {
+        let first = idx;
+        while idx + 1 < clusters.len() && clusters[idx + 1] == clusters[first] {
+            idx += 1;
+        }
+        let last = idx;
+        idx += 1;
+
+        let start = clusters[first] as usize;
+        let end = starts
+            .iter()
+            .cloned()
+            .find(|&s| s > start)
+            .unwrap_or_else(|| text.len());
+
+        if text.get(start..end).map_or(false, |t| cell_widths.text_cells(t) == 2) {
+            let advance: i32 = widths[first..last + 1].iter().sum();
+            let diff = 2 * char_width - advance;
+            if diff != 0 {
+                result.push((first, last, diff));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_size() {
+        let metrics = CellMetrics::new_hw(16.0, 8.0);
+        assert_eq!((10, 2), grid_size(87, 40, &metrics));
+        assert_eq!((0, 0), grid_size(7, 15, &metrics));
+        // padding bigger than allocation
+        assert_eq!((0, 2), grid_size(-4, 32, &metrics));
+        // font metrics not loaded yet
+        assert_eq!((0, 0), grid_size(100, 100, &CellMetrics::new_hw(0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_clamp_grid_size() {
+        assert_eq!(MIN_GRID_SIZE, clamp_grid_size((0, 0)));
+        assert_eq!((12, 5), clamp_grid_size((1, 5)));
+        assert_eq!((80, 24), clamp_grid_size((80, 24)));
+    }
+
+    #[test]
+    fn test_nowrap_window_parse() {
+        assert_eq!(None, NowrapWindow::parse("1", 1, 1, 10, 20));
+        assert_eq!(
+            Some(NowrapWindow {
+                top: 1,
+                left: 4,
+                height: 10,
+                width: 20,
+            }),
+            NowrapWindow::parse("0", 2, 5, 10, 20)
+        );
+    }
+
+    #[test]
+    fn test_truncated_rows() {
+        let mut model = ui_model::UiModel::new(3, 6);
+        model.set_cursor(0, 3);
+        model.put("a", false, None);
+        model.set_cursor(1, 2);
+        model.put("b", false, None);
+        model.set_cursor(2, 3);
+        model.put(" ", false, None);
+
+        let window = NowrapWindow::parse("0", 1, 1, 3, 4).unwrap();
+        assert_eq!(vec![0], window.truncated_rows(&model));
+    }
+
+    #[test]
+    fn test_double_width_clusters() {
+        // "a中b": narrow, double width with short advance, narrow
+        let clusters = [0, 1, 4];
+        let widths = [10, 16, 10];
+        let cell_widths = CellWidths::new();
+        assert_eq!(
+            vec![(1, 1, 4)],
+            double_width_clusters(&clusters, &widths, "a中b", 10, &cell_widths)
+        );
+
+        let widths = [10, 20, 10];
+        assert!(double_width_clusters(&clusters, &widths, "a中b", 10, &cell_widths).is_empty());
+    }
+
+    #[test]
+    fn test_padding_parse() {
+        assert_eq!(Padding::new(2), Padding::parse("2").unwrap());
+        assert_eq!(
+            Padding {
+                top: 1,
+                bottom: 1,
+                left: 5,
+                right: 5,
+            },
+            Padding::parse("1 5").unwrap()
+        );
+        assert_eq!(
+            Padding {
+                top: 1,
+                right: 2,
+                bottom: 3,
+                left: 4,
+            },
+            Padding::parse("1 2 3 4").unwrap()
+        );
+        assert!(Padding::parse("1 2 3").is_err());
+        assert!(Padding::parse("a").is_err());
+    }
+
+    #[test]
+    fn test_color_columns_parse() {
+        let window = WinInfo {
+            top: 0,
+            left: 0,
+            height: 10,
+            width: 120,
+            textoff: 4,
+            leftcol: 0,
+        };
+        let cc = ColorColumns::parse("80,+1,-2", 100, &window);
+        assert_eq!(vec![83, 104, 101], cc.columns);
+
+        let cc = ColorColumns::parse("+1", 0, &window);
+        assert!(cc.columns.is_empty());
+
+        let cc = ColorColumns::parse("", 80, &window);
+        assert!(cc.columns.is_empty());
+    }
+
+    #[test]
+    fn test_color_columns_scrolled() {
+        let window = WinInfo {
+            top: 1,
+            left: 41,
+            height: 10,
+            width: 40,
+            textoff: 2,
+            leftcol: 10,
+        };
+        let cc = ColorColumns::parse("5,20,80", 0, &window);
+        assert_eq!(vec![41 + 2 + 19 - 10], cc.columns);
+        assert_eq!(1, cc.top);
+    }
+
+    #[test]
+    fn test_win_info_parse() {
+        let args: Vec<String> = ["2", "5", "10", "40", "3", "1"]
+            .iter()
+            .map(|s| (*s).to_owned())
+            .collect();
+        let window = WinInfo::parse(&args).unwrap();
+        assert_eq!(1, window.top);
+        assert_eq!(4, window.left);
+        assert_eq!(Some(4 + 3), window.screen_col(1));
+        assert_eq!(None, window.screen_col(0));
+        assert_eq!(None, window.screen_col(40));
+
+        assert_eq!(None, WinInfo::parse(&args[..5]));
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use neovim_lib::{Neovim, NeovimApi, NeovimApiAsync};
+
+use dirs;
+use misc::escape_filename;
+use nvim::ErrorReport;
+
+const SESSION_FILE: &str = "session.vim";
+
+/// Session file managed by GUI, stored under XDG data dir
+pub fn session_file() -> Result<PathBuf, String> {
+    let mut path = dirs::get_app_data_dir_create()?;
+    path.push(SESSION_FILE);
+    Ok(path)
+}
+
+/// Save session when nvim exits and optionally source previous one
+///
+/// Session is sourced asynchronously, so big session file doesn't block UI.
+/// Its errors are passed to `on_error` from rpc thread, but session is still
+/// applied as far as possible, so they must not stop startup
+pub fn init<F>(nvim: &mut Neovim, restore: bool, on_error: F) -> Result<(), String>
+where
+    F: FnOnce(String) + Send + 'static,
+{
+    let path = session_file()?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("Wrong session file path {:?}", path))?;
+    let escaped_path = escape_filename(path_str);
+
+    let save_cmd = format!(
+        "augroup NvimGtkSession | autocmd! | \
+         autocmd VimLeavePre * mksession! {} | augroup END",
+        escaped_path
+    );
+    nvim.command_async(&save_cmd)
+        .cb(|r| r.report_err())
+        .call();
+
+    if !restore || !path.exists() {
+        return Ok(());
+    }
+
+    let source_cmd = format!("let v:errmsg = '' | silent! source {}", escaped_path);
+    nvim.command_async(&source_cmd)
+        .cb(|r| r.report_err())
+        .call();
+
+    // requests are handled in order, so it is evaluated after session is sourced
+    let path_str = path_str.to_owned();
+    nvim.eval_async("v:errmsg")
+        .cb(move |res| match res {
+            Ok(errmsg) => match errmsg.as_str() {
+                Some(errmsg) if !errmsg.is_empty() => {
+                    on_error(format!("Error in session file {}: {}", path_str, errmsg))
+                }
+                _ => (),
+            },
+            Err(e) => on_error(format!("{}", e)),
+        })
+        .call();
+
+    Ok(())
+}
+
+/// Write session on GUI close, nvim won't get to VimLeavePre after ui detach
+pub fn save(nvim: &mut Neovim) -> Result<(), String> {
+    let path = session_file()?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("Wrong session file path {:?}", path))?;
+
+    nvim.command(&format!("mksession! {}", escape_filename(path_str)))
+        .map_err(|e| format!("{}", e))
+}
@@ -10,6 +10,7 @@ use gio::SettingsExt;
 #[derive(PartialEq)]
 pub enum FontSource {
     Rpc,
+    Config,
     #[cfg(unix)]
     Gnome,
     Default,
@@ -37,9 +38,9 @@ impl State {
     }
 
     #[cfg(unix)]
-    fn update_font(&mut self, shell: &mut Shell) {
-        // rpc is priority for font
-        if self.font_source == FontSource::Rpc {
+    fn update_font(&mut self, shell: &Shell) {
+        // rpc and config are priority for font
+        if self.font_source == FontSource::Rpc || self.font_source == FontSource::Config {
             return;
         }
 
@@ -75,14 +76,12 @@ impl Settings {
     pub fn init(&mut self) {
         let shell = Weak::upgrade(self.shell.as_ref().unwrap()).unwrap();
         let state = self.state.clone();
-        self.state.borrow_mut().update_font(
-            &mut *shell.borrow_mut(),
-        );
+        self.state.borrow_mut().update_font(&*shell.borrow());
         self.state
             .borrow()
             .gnome_interface_settings
             .connect_changed(move |_, _| {
-                monospace_font_changed(&mut *shell.borrow_mut(), &mut *state.borrow_mut())
+                monospace_font_changed(&*shell.borrow(), &mut *state.borrow_mut())
             });
     }
 
@@ -95,16 +94,21 @@ impl Settings {
 }
 
 #[cfg(unix)]
-fn monospace_font_changed(mut shell: &mut Shell, state: &mut State) {
-    // rpc is priority for font
-    if state.font_source != FontSource::Rpc {
-        state.update_font(&mut shell);
+fn monospace_font_changed(shell: &Shell, state: &mut State) {
+    // rpc and config are priority for font
+    if state.font_source != FontSource::Rpc && state.font_source != FontSource::Config {
+        state.update_font(shell);
     }
 }
 
-use std::path::Path;
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
+use std::io;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use toml;
 use serde;
@@ -116,8 +120,15 @@ pub trait SettingsLoader: Sized + serde::Serialize + Default {
 
     fn from_str(s: &str) -> Result<Self, String>;
 
+    /// Default location of settings file inside nvim-gtk config dir
+    fn settings_path() -> Result<PathBuf, String> {
+        let mut toml_path = dirs::get_app_config_dir()?;
+        toml_path.push(Self::SETTINGS_FILE);
+        Ok(toml_path)
+    }
+
     fn load() -> Self {
-        match load_err() {
+        match Self::settings_path().and_then(|path| load_from_file(&path)) {
             Ok(settings) => settings,
             Err(e) => {
                 error!("{}", e);
@@ -126,17 +137,30 @@ pub trait SettingsLoader: Sized + serde::Serialize + Default {
         }
     }
 
-    fn is_file_exists() -> bool {
-        if let Ok(mut toml_path) = dirs::get_app_config_dir() {
-            toml_path.push(Self::SETTINGS_FILE);
-            toml_path.is_file()
-        } else {
-            false
+    fn load_from(path: &Path) -> Self {
+        match load_from_file(path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("{}: {}", path.display(), e);
+                Default::default()
+            }
         }
     }
 
+    fn is_file_exists() -> bool {
+        Self::settings_path()
+            .map(|path| path.is_file())
+            .unwrap_or(false)
+    }
+
+    /// Settings to write when file was saved by other instance after this one
+    /// was loaded, None writes these settings as is, so last saved wins
+    fn merge(&self, _saved: Self) -> Option<Self> {
+        None
+    }
+
     fn save(&self) {
-        match save_err(self) {
+        match Self::settings_path().and_then(|path| save_locked(self, &path, LOCK_WAIT)) {
             Ok(()) => (),
             Err(e) => error!("{}", e),
         }
@@ -156,21 +180,237 @@ fn load_from_file<T: SettingsLoader>(path: &Path) -> Result<T, String> {
     }
 }
 
-fn load_err<T: SettingsLoader>() -> Result<T, String> {
-    let mut toml_path = dirs::get_app_config_dir_create()?;
-    toml_path.push(T::SETTINGS_FILE);
-    load_from_file(&toml_path)
-}
+/// Merge with saved file and write it while holding lock,
+/// so instances closed at same time don't lose each other changes.
+/// Readers don't need lock as file is replaced by rename.
+fn save_locked<T: SettingsLoader>(
+    sl: &T,
+    path: &Path,
+    lock_wait: Duration,
+) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}", e))?;
+    }
 
+    let _lock = FileLock::acquire(path, lock_wait).map_err(|e| {
+        format!("{}, settings are kept in memory only", e)
+    })?;
 
-fn save_err<T: SettingsLoader>(sl: &T) -> Result<(), String> {
-    let mut toml_path = dirs::get_app_config_dir_create()?;
-    toml_path.push(T::SETTINGS_FILE);
-    let mut file = File::create(toml_path).map_err(|e| format!("{}", e))?;
+    let merged = if path.exists() {
+        load_from_file::<T>(path)
+            .ok()
+            .and_then(|saved| sl.merge(saved))
+    } else {
+        None
+    };
+
+    save_to_file(merged.as_ref().unwrap_or(sl), path)
+}
 
+/// Content is written to temporary file and renamed over old one,
+/// so crash during save can't leave truncated settings
+fn save_to_file<T: SettingsLoader>(sl: &T, path: &Path) -> Result<(), String> {
     let contents = toml::to_vec::<T>(sl).map_err(|e| format!("{}", e))?;
 
-    file.write_all(&contents).map_err(|e| format!("{}", e))?;
+    let tmp_path = tmp_path(path);
+    {
+        let mut file = File::create(&tmp_path).map_err(|e| format!("{}", e))?;
+        file.write_all(&contents).map_err(|e| format!("{}", e))?;
+        file.sync_all().map_err(|e| format!("{}", e))?;
+    }
+    fs::rename(&tmp_path, path).map_err(|e| format!("{}", e))
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    path_with_suffix(path, ".tmp")
+}
+
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().map_or_else(Default::default, |name| name.to_owned());
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// How long to wait for other instance to finish writing
+const LOCK_WAIT: Duration = Duration::from_millis(200);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+/// Lock is held only while file is written, older one is left by crashed instance
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10);
+
+/// Lock file with pid of owner, removed on drop
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(path: &Path, wait: Duration) -> Result<Self, String> {
+        let lock_path = path_with_suffix(path, ".lock");
+        let start = Instant::now();
+
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut file) => {
+                    write!(file, "{}", process::id()).map_err(|e| format!("{}", e))?;
+                    return Ok(FileLock { path: lock_path });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_stale_lock(&lock_path) {
+                        remove_stale_lock(&lock_path);
+                    } else if start.elapsed() < wait {
+                        thread::sleep(LOCK_RETRY_INTERVAL);
+                    } else {
+                        break;
+                    }
+                }
+                Err(e) => return Err(format!("{}: {}", lock_path.display(), e)),
+            }
+        }
+
+        let owner = fs::read_to_string(&lock_path).unwrap_or_default();
+        Err(format!(
+            "{} is locked by other nvim-gtk instance (pid {})",
+            path.display(),
+            owner.trim()
+        ))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Can't remove lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Take stale lock away by rename, so only one of instances that found it
+/// stale removes it. Lock that is fresh after rename was just created
+/// by other instance, so it is put back.
+fn remove_stale_lock(lock_path: &Path) {
+    static STALE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let stale_path = path_with_suffix(
+        lock_path,
+        &format!(
+            ".stale-{}-{}",
+            process::id(),
+            STALE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+    if fs::rename(lock_path, &stale_path).is_err() {
+        // other instance took it first
+        return;
+    }
+
+    if is_stale_lock(&stale_path) {
+        warn!("Removing stale lock {}", lock_path.display());
+    } else if let Err(e) = fs::hard_link(&stale_path, lock_path) {
+        warn!("Can't restore lock {}: {}", lock_path.display(), e);
+    }
+    fs::remove_file(&stale_path).ok();
+}
+
+fn is_stale_lock(lock_path: &Path) -> bool {
+    fs::metadata(lock_path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map_or(false, |age| age > STALE_LOCK_AGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+    use std::env;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct Recent {
+        files: BTreeSet<String>,
+    }
+
+    impl SettingsLoader for Recent {
+        const SETTINGS_FILE: &'static str = "recent.toml";
+
+        fn from_str(s: &str) -> Result<Self, String> {
+            toml::from_str(&s).map_err(|e| format!("{}", e))
+        }
+
+        fn merge(&self, saved: Self) -> Option<Self> {
+            Some(Recent {
+                files: self.files.union(&saved.files).cloned().collect(),
+            })
+        }
+    }
 
-    Ok(())
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("nvim-gtk-test-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let path = test_dir("lock").join("window.toml");
+
+        let lock = FileLock::acquire(&path, LOCK_WAIT).unwrap();
+        assert!(FileLock::acquire(&path, LOCK_WAIT).is_err());
+        drop(lock);
+        assert!(FileLock::acquire(&path, LOCK_WAIT).is_ok());
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_fresh_lock_is_not_removed() {
+        let path = test_dir("fresh").join("window.toml");
+
+        let lock = FileLock::acquire(&path, LOCK_WAIT).unwrap();
+        remove_stale_lock(&lock.path);
+        assert!(lock.path.exists());
+        assert_eq!(1, fs::read_dir(path.parent().unwrap()).unwrap().count());
+        drop(lock);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_concurrent_writers_merge() {
+        let path = Arc::new(test_dir("merge").join(Recent::SETTINGS_FILE));
+
+        let writers: Vec<_> = (0..4)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let mut recent = Recent::default();
+                    recent.files.insert(format!("file{}", i));
+                    // generous wait, so slow test machine doesn't fail it
+                    save_locked(&recent, &path, Duration::from_secs(5)).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let saved: Recent = load_from_file(&path).unwrap();
+        assert_eq!(4, saved.files.len());
+        assert!(!path_with_suffix(&path, ".lock").exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_tmp_path() {
+        assert_eq!(
+            PathBuf::from("/home/user/.config/nvim-gtk/config.toml.tmp"),
+            tmp_path(Path::new("/home/user/.config/nvim-gtk/config.toml"))
+        );
+    }
 }
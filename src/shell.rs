@@ -1,16 +1,23 @@
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::f64::consts::PI;
 use std::ops::Deref;
+use std::path::Path;
+use std::process::Command;
 use std::rc::Rc;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use clap;
 
 use cairo;
 use gdk;
 use gdk::{EventButton, EventMotion, EventScroll, EventType, ModifierType, WindowExt};
+use gio;
+use gio::FileExt;
 use glib;
 use gtk;
 use gtk::prelude::*;
@@ -22,31 +29,57 @@ use pangocairo;
 use neovim_lib::neovim_api::Tabpage;
 use neovim_lib::{Neovim, NeovimApi, NeovimApiAsync, Value};
 
-use color::{Color, ColorModel, COLOR_BLACK, COLOR_RED, COLOR_WHITE};
-use misc::{decode_uri, escape_filename, split_at_comma};
+use color::{self, Color, ColorModel, DefaultColors};
+use misc::{self, decode_uri, escape_filename, parse_guifont};
 use nvim::{
-    self, CompleteItem, ErrorReport, NeovimClient, NeovimClientAsync, NeovimRef, NvimHandler,
-    RepaintMode,
+    self, BufferTracker, CompleteItem, ErrorReport, EventRecorder, EventReplay, NeovimClient, NeovimClientAsync,
+    NeovimRef, NvimAccess, NvimHandler, RepaintMode,
 };
 use settings::{FontSource, Settings};
-use ui_model::{Attrs, ModelRect, UiModel};
+use ui_model::{Attrs, ModelRect, ModelRectVec, UiModel};
 
+use accessible::{self, GridAccessible};
 use cmd_line::{CmdLine, CmdLineContext};
-use cursor::{BlinkCursor, Cursor, CursorRedrawCb};
+use crash;
+use cursor::{BlinkCursor, Cursor, CursorRedrawCb, Easing};
+use dirs;
 use error;
 use input;
 use input::keyval_to_input_string;
 use mode;
+use overscroll::{self, Overscroll};
 use popup_menu::{self, PopupMenu};
 use render;
 use render::CellMetrics;
+use session;
 use subscriptions::{SubscriptionHandle, SubscriptionKey, Subscriptions};
 use tabline::Tabline;
+use throttle::Throttle;
 use ui::UiMutex;
+use version::VersionInfo;
 
 const DEFAULT_FONT_NAME: &str = "DejaVu Sans Mono 12";
 pub const MINIMUM_SUPPORTED_NVIM_VERSION: &str = "0.2.2";
 
+/// After this time user is offered to start nvim without configuration
+const SLOW_INIT_THRESHOLD: Duration = Duration::from_secs(15);
+const SPLASH_UPDATE_INTERVAL_MS: u32 = 100;
+// start nvim anyway in case drawing area is never allocated (hidden start)
+const INIT_ALLOCATION_TIMEOUT_MS: u32 = 1000;
+const DEFAULT_GRID_SIZE: (usize, usize) = (80, 24);
+/// Resize is sent when window size is not changed for this time, so drag doesn't flood nvim
+const RESIZE_DEBOUNCE_MS: u32 = 50;
+// ~30Hz, enough for external tools following cursor
+const CURSOR_RECT_INTERVAL_MS: u32 = 33;
+
+/// Colorscheme switch redefines many groups, css is regenerated at most this often
+const CHROME_CSS_INTERVAL_MS: u32 = 100;
+/// Upper bound of scroll lines, bigger value floods nvim input on each wheel tick
+const MAX_SCROLL_LINES: u64 = 100;
+
+/// Cursor cell on screen in device pixels: x, y, width, height
+pub type CursorScreenRect = (i32, i32, i32, i32);
+
 macro_rules! idle_cb_call {
     ($state:ident.$cb:ident($( $x:expr ),*)) => (
             glib::idle_add(move || {
@@ -63,6 +96,7 @@ pub struct RenderState {
     pub font_ctx: render::Context,
     pub color_model: ColorModel,
     pub mode: mode::Mode,
+    pub tabstop: usize,
 }
 
 impl RenderState {
@@ -71,6 +105,7 @@ impl RenderState {
             font_ctx: render::Context::new(pango_context),
             color_model: ColorModel::new(),
             mode: mode::Mode::new(),
+            tabstop: 8,
         }
     }
 }
@@ -110,9 +145,50 @@ impl TransparencySettigns {
 pub struct State {
     pub model: UiModel,
     cur_attrs: Option<Attrs>,
+    /// Mouse reporting requested by nvim with `mouse_on`/`mouse_off`
     mouse_enabled: bool,
+    /// 'mouse' option is not empty, so `mouse_off` is temporary
+    /// (e.g. hit-enter prompt) and gui selection is not used
+    mouse_option: Cell<bool>,
+    /// Requested ui options that are not supported by nvim
+    dropped_ui_options: Vec<String>,
+    /// 'termguicolors' was forced on startup
+    termguicolors_checked: bool,
+    /// Font before zoom shortcuts were used, restored by Ctrl+0
+    zoom_base_font: Option<String>,
+    focused: bool,
+    /// Percent of dimming of unfocused grid
+    dim_inactive: u8,
+    /// Repaint of gui events, applied once from main loop
+    pending_repaint: Option<RepaintMode>,
+    /// Linewise selection made while nvim mouse is disabled
+    selection: Option<(usize, usize)>,
+    scroll_without_mouse: bool,
+    scroll_lines: usize,
+    /// Shift+wheel scrolls horizontally
+    horizontal_scroll: bool,
+    nowrap_indicator: bool,
+    nowrap_window: RefCell<Option<render::NowrapWindow>>,
+    overscroll_enabled: bool,
+    /// Current window shows first line, last line
+    buffer_edges: Cell<(bool, bool)>,
+    overscroll: Rc<RefCell<Overscroll>>,
+    overscroll_timer: Rc<Cell<Option<glib::SourceId>>>,
+    /// Current window when 'cursorline' is set
+    cursor_line: RefCell<Option<render::WinInfo>>,
+    color_columns: RefCell<render::ColorColumns>,
+    padding: render::Padding,
+    hit_enter: bool,
+    /// Working directory of current window, updated on DirChanged
+    cwd: RefCell<String>,
+    buffer_tracker: RefCell<BufferTracker>,
     nvim: Rc<NeovimClient>,
     cursor: Option<BlinkCursor<State>>,
+    cursor_frame_updating: Cell<bool>,
+    cursor_rect_throttle: Option<Throttle<CursorScreenRect>>,
+    buffer_stats_cb: Option<Box<Fn(usize, usize)>>,
+    color_map: Vec<(Value, Value)>,
+    last_cursor_rect: Cell<Option<CursorScreenRect>>,
     popup_menu: PopupMenu,
     cmd_line: CmdLine,
     settings: Rc<RefCell<Settings>>,
@@ -126,10 +202,19 @@ pub struct State {
 
     stack: gtk::Stack,
     pub drawing_area: gtk::DrawingArea,
+    grid_surface: RefCell<render::GridSurface>,
+    accessible: GridAccessible,
     tabs: Tabline,
     im_context: gtk::IMMulticontext,
+    im_preedit: RefCell<input::ImPreedit>,
     update_im_location: bool,
     error_area: error::ErrorArea,
+    error_bar: error::ErrorBar,
+    slow_init_bar: gtk::InfoBar,
+    save_session: Cell<bool>,
+    init_started: Option<Instant>,
+    /// Grid replaces splash as soon as first redraw batch applied
+    redraw_applied: bool,
 
     options: ShellOptions,
     transparency_settings: TransparencySettigns,
@@ -143,7 +228,7 @@ pub struct State {
 
 impl State {
     pub fn new(settings: Rc<RefCell<Settings>>, options: ShellOptions) -> State {
-        let drawing_area = gtk::DrawingArea::new();
+        let drawing_area = accessible::grid_drawing_area();
 
         let pango_context = drawing_area.create_pango_context().unwrap();
         pango_context.set_font_description(&FontDescription::from_string(DEFAULT_FONT_NAME));
@@ -157,7 +242,35 @@ impl State {
             nvim: Rc::new(NeovimClient::new()),
             cur_attrs: None,
             mouse_enabled: true,
+            mouse_option: Cell::new(false),
+            dropped_ui_options: Vec::new(),
+            termguicolors_checked: false,
+            zoom_base_font: None,
+            focused: true,
+            dim_inactive: 0,
+            pending_repaint: None,
+            selection: None,
+            scroll_without_mouse: false,
+            scroll_lines: 1,
+            horizontal_scroll: true,
+            nowrap_indicator: true,
+            nowrap_window: RefCell::new(None),
+            overscroll_enabled: false,
+            buffer_edges: Cell::new((false, false)),
+            overscroll: Rc::new(RefCell::new(Overscroll::default())),
+            overscroll_timer: Rc::new(Cell::new(None)),
+            cursor_line: RefCell::new(None),
+            color_columns: RefCell::new(render::ColorColumns::default()),
+            padding: render::Padding::default(),
+            hit_enter: false,
+            cwd: RefCell::new(String::new()),
+            buffer_tracker: RefCell::new(BufferTracker::new()),
             cursor: None,
+            cursor_frame_updating: Cell::new(false),
+            cursor_rect_throttle: None,
+            buffer_stats_cb: None,
+            color_map: Vec::new(),
+            last_cursor_rect: Cell::new(None),
             popup_menu,
             cmd_line,
             settings,
@@ -171,11 +284,19 @@ impl State {
 
             // UI
             stack: gtk::Stack::new(),
+            accessible: GridAccessible::new(&drawing_area),
             drawing_area,
+            grid_surface: RefCell::new(render::GridSurface::new()),
             tabs: Tabline::new(),
             im_context: gtk::IMMulticontext::new(),
+            im_preedit: RefCell::new(input::ImPreedit::default()),
             update_im_location: false,
             error_area: error::ErrorArea::new(),
+            error_bar: error::ErrorBar::new(),
+            slow_init_bar: gtk::InfoBar::new(),
+            save_session: Cell::new(false),
+            init_started: None,
+            redraw_applied: false,
 
             options,
             transparency_settings: TransparencySettigns::new(),
@@ -204,13 +325,18 @@ impl State {
         self.nvim.try_nvim()
     }
 
+    pub fn try_nvim_access(&self) -> NvimAccess {
+        self.nvim.try_access()
+    }
+
     pub fn nvim_clone(&self) -> Rc<NeovimClient> {
         self.nvim.clone()
     }
 
-    pub fn start_nvim_initialization(&self) -> bool {
+    pub fn start_nvim_initialization(&mut self) -> bool {
         if self.nvim.is_uninitialized() {
             self.nvim.set_in_progress();
+            self.init_started = Some(Instant::now());
             true
         } else {
             false
@@ -250,17 +376,45 @@ impl State {
         }
     }
 
-    pub fn set_font_desc(&mut self, desc: &str) {
+    /// Parse font and check it can be loaded, returned context uses this font
+    fn load_font_desc(&self, desc: &str) -> Result<pango::Context, String> {
         let font_description = FontDescription::from_string(desc);
 
         if font_description.get_size() <= 0 {
-            error!("Font size must be > 0");
-            return;
+            return Err(format!("Font size must be > 0: {}", desc));
         }
 
         let pango_context = self.drawing_area.create_pango_context().unwrap();
+
+        // unknown family silently falls back to default font
+        if let Some(family) = font_description.get_family() {
+            let installed = pango_context
+                .list_families()
+                .iter()
+                .filter_map(pango::FontFamilyExt::get_name)
+                .map(|name| name.to_lowercase())
+                .collect();
+            if !misc::is_font_family_known(&installed, &family) {
+                return Err(format!("Unknown font family: {}", family));
+            }
+        }
+
+        if pango_context.load_font(&font_description).is_none() {
+            return Err(format!("Can't load font: {}", desc));
+        }
+
         pango_context.set_font_description(&font_description);
+        Ok(pango_context)
+    }
 
+    pub fn set_font_desc(&mut self, desc: &str) {
+        match self.load_font_desc(desc) {
+            Ok(pango_context) => self.set_font_context(pango_context),
+            Err(err) => error!("{}", err),
+        }
+    }
+
+    fn set_font_context(&mut self, pango_context: pango::Context) {
         self.render_state
             .borrow_mut()
             .font_ctx
@@ -270,15 +424,101 @@ impl State {
         self.on_redraw(&RepaintMode::All);
     }
 
-    pub fn set_font_features(&mut self, font_features: String) {
-        let font_features = render::FontFeatures::from(font_features);
+    /// Change font size by 1pt, reset restores font that was set before zoom
+    pub fn zoom(&mut self, zoom: input::Zoom) {
+        let current = self.font_desc();
+        let desc = match zoom {
+            input::Zoom::Reset => match self.zoom_base_font.take() {
+                Some(base) => base,
+                None => return,
+            },
+            input::Zoom::In | input::Zoom::Out => {
+                let mut font_description = FontDescription::from_string(&current);
+                let step = if zoom == input::Zoom::In {
+                    pango::SCALE
+                } else {
+                    -pango::SCALE
+                };
+                let size = font_description.get_size() + step;
+                if size < pango::SCALE {
+                    return;
+                }
+                font_description.set_size(size);
+
+                if self.zoom_base_font.is_none() {
+                    self.zoom_base_font = Some(current);
+                }
+                font_description.to_string()
+            }
+        };
+
+        self.set_font_desc(&desc);
+    }
 
+    pub fn font_desc(&self) -> String {
         self.render_state
+            .borrow()
+            .font_ctx
+            .font_description()
+            .to_string()
+    }
+
+    pub fn set_font_features(&mut self, font_features: String) {
+        let font_features = match render::FontFeatures::parse(&font_features) {
+            Ok(font_features) => font_features,
+            Err(e) => {
+                self.error_bar.show_warning(&e);
+                return;
+            }
+        };
+
+        let changed = self
+            .render_state
             .borrow_mut()
             .font_ctx
             .update_font_features(font_features);
-        self.model.clear_glyphs();
-        self.on_redraw(&RepaintMode::All);
+        if changed {
+            self.model.clear_glyphs();
+            self.on_redraw(&RepaintMode::All);
+        }
+    }
+
+    pub fn set_antialiasing(&mut self, antialiasing: &str) {
+        let antialiasing = match render::AntiAliasing::parse(antialiasing) {
+            Ok(antialiasing) => antialiasing,
+            Err(e) => {
+                self.error_bar.show_warning(&e);
+                return;
+            }
+        };
+
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .font_ctx
+            .update_antialiasing(antialiasing);
+        if changed {
+            // hinting can change advances, so cell size is recalculated as well
+            self.model.clear_glyphs();
+            self.try_nvim_resize();
+            self.on_redraw(&RepaintMode::All);
+        }
+    }
+
+    pub fn set_font_variant(&mut self, variant: String, font: String) {
+        let res = self
+            .render_state
+            .borrow_mut()
+            .font_ctx
+            .update_font_variant(&variant, &font);
+
+        match res {
+            Ok(()) => {
+                self.model.clear_glyphs();
+                self.on_redraw(&RepaintMode::All);
+            }
+            Err(e) => self.error_bar.show_warning(&e),
+        }
     }
 
     pub fn set_line_space(&mut self, line_space: String) {
@@ -324,6 +564,73 @@ impl State {
         }
     }
 
+    pub fn buffer_tracker(&self) -> RefMut<BufferTracker> {
+        self.buffer_tracker.borrow_mut()
+    }
+
+    /// Called with word and char count of current buffer after each change
+    pub fn set_buffer_stats_cb<F: Fn(usize, usize) + 'static>(&mut self, cb: F) {
+        self.buffer_stats_cb = Some(Box::new(cb));
+    }
+
+    pub fn notify_buffer_stats(&self) {
+        if let Some(ref cb) = self.buffer_stats_cb {
+            let tracker = self.buffer_tracker.borrow();
+            cb(tracker.word_count(), tracker.char_count());
+        }
+    }
+
+    /// Receive live changes of current buffer
+    pub fn attach_current_buffer(&self) {
+        if let Some(mut nvim) = self.nvim() {
+            self.buffer_tracker.borrow_mut().attach_current(&mut nvim);
+        }
+    }
+
+    pub fn set_cwd(&self, cwd: &str) {
+        *self.cwd.borrow_mut() = cwd.to_owned();
+    }
+
+    pub fn cwd(&self) -> Option<String> {
+        let cwd = self.cwd.borrow();
+        if cwd.is_empty() {
+            None
+        } else {
+            Some(cwd.clone())
+        }
+    }
+
+    /// Resolve relative path against nvim working directory
+    pub fn resolve_path(&self, path: &str) -> String {
+        let cwd = self.cwd.borrow();
+        if cwd.is_empty() || Path::new(path).is_absolute() {
+            path.to_owned()
+        } else {
+            Path::new(&*cwd).join(path).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Open existing file which path is under given position,
+    /// return false when there is none, so click goes to nvim
+    fn open_path_at(&self, position: (f64, f64)) -> bool {
+        let (col, row) = self.grid_cell(position);
+        let (col, row) = (col as usize, row as usize);
+        if row >= self.model.rows || col >= self.model.columns {
+            return false;
+        }
+
+        let path = match self.model.path_at(row, col) {
+            Some(path) => self.resolve_path(&path),
+            None => return false,
+        };
+        if !Path::new(&path).is_file() {
+            return false;
+        }
+
+        self.open_file(&escape_filename(&path));
+        true
+    }
+
     pub fn cd(&self, path: &str) {
         if let Some(mut nvim) = self.nvim() {
             nvim.command_async(&format!("cd {}", path))
@@ -343,7 +650,7 @@ impl State {
     fn close_popup_menu(&self) {
         if self.popup_menu.is_open() {
             if let Some(mut nvim) = self.nvim() {
-                nvim.input("<Esc>").report_err();
+                nvim.input_async("<Esc>").cb(|r| r.report_err()).call();
             }
         }
     }
@@ -367,11 +674,82 @@ impl State {
         for mut rect in rects {
             rect.extend_by_items(&self.model);
 
-            let (x, y, width, height) = rect.to_area_extend_ink(&self.model, cell_metrics);
+            let (x, y, width, height) =
+                self.padded_area(rect.to_area_extend_ink(&self.model, cell_metrics));
+            self.grid_surface
+                .borrow_mut()
+                .damage_area((x, y, width, height));
             self.drawing_area.queue_draw_area(x, y, width, height);
         }
     }
 
+    fn queue_draw_all(&self) {
+        self.grid_surface.borrow_mut().damage_all();
+        self.drawing_area.queue_draw();
+    }
+
+    /// Move already rendered rows of scroll region inside grid surface
+    ///
+    /// Return rows that must be rendered after model scroll,
+    /// None in case whole region must be rendered.
+    fn blit_scroll(&self, count: i64) -> Option<ModelRectVec> {
+        if self.selection.is_some() {
+            return None;
+        }
+
+        let region = self.model.scroll_region();
+        let (top, bot) = (region.top as i64, region.bot as i64);
+        if count.abs() > bot - top {
+            return None;
+        }
+
+        // rows changed after last render are not valid in surface
+        let stale_rows = self.model.dirty_rows(region.top, region.bot);
+        if stale_rows.len() as i64 > bot - top {
+            return None;
+        }
+
+        let render_state = self.render_state.borrow();
+        let cell_metrics = render_state.font_ctx.cell_metrics();
+        let dy = count as f64 * cell_metrics.line_height;
+        if dy.fract() != 0.0 {
+            return None;
+        }
+
+        let area = self.padded_area(region.to_area(cell_metrics));
+        if !self.grid_surface.borrow_mut().scroll(area, dy as i32) {
+            return None;
+        }
+        let (x, y, width, height) = area;
+        self.drawing_area.queue_draw_area(x, y, width, height);
+
+        let row_rect = |row: usize| ModelRect::new(row, row, region.left, region.right);
+        let exposed = if count > 0 {
+            ModelRect::new((bot - count + 1) as usize, bot as usize, region.left, region.right)
+        } else {
+            ModelRect::new(top as usize, (top - count - 1) as usize, region.left, region.right)
+        };
+
+        // region borders can contain moved ink of rows outside region
+        let mut rows = ModelRectVec::new(exposed);
+        rows.join(&row_rect(region.top));
+        rows.join(&row_rect(region.bot));
+
+        let (cur_row, _) = self.model.get_cursor();
+        let moved_rows = stale_rows.into_iter().chain(Some(cur_row));
+        for row in moved_rows {
+            let row = row as i64 - count;
+            if row >= top && row <= bot {
+                rows.join(&row_rect(row as usize));
+            }
+        }
+        if cur_row >= region.top && cur_row <= region.bot {
+            rows.join(&row_rect(cur_row));
+        }
+
+        Some(rows)
+    }
+
     #[inline]
     fn update_dirty_glyphs(&mut self) {
         let render_state = self.render_state.borrow();
@@ -383,24 +761,106 @@ impl State {
     }
 
     fn im_commit(&self, ch: &str) {
+        if self.im_preedit.borrow_mut().commit() {
+            self.update_popup_shift();
+        }
+
         if let Some(mut nvim) = self.nvim() {
             input::im_input(&mut nvim, ch);
         }
     }
 
+    fn im_preedit_changed(&self) {
+        let (preedit, ..) = self.im_context.get_preedit_string();
+        if self.im_preedit.borrow_mut().preedit_changed(&preedit) {
+            self.update_popup_shift();
+        }
+    }
+
+    /// Preedit window of input method is shown at cursor, popup is moved below it
+    fn update_popup_shift(&self) {
+        let line_height = self.render_state.borrow().font_ctx.cell_metrics().line_height;
+        let shift_rows = self.im_preedit.borrow().popup_shift_rows();
+        self.popup_menu
+            .set_shift((f64::from(shift_rows) * line_height).ceil() as i32);
+    }
+
+    /// Grid size fitting to allocation, can be zero for tiny window
     fn calc_nvim_size(&self) -> (usize, usize) {
+        let alloc = self.drawing_area.get_allocation();
+        let width = alloc.width - self.padding.left - self.padding.right;
+        let height = alloc.height - self.padding.top - self.padding.bottom;
+        render::grid_size(width, height, self.render_state.borrow().font_ctx.cell_metrics())
+    }
+
+    fn is_too_small(&self) -> bool {
+        match self.calc_nvim_size() {
+            (0, _) | (_, 0) => true,
+            _ => false,
+        }
+    }
+
+    /// Request drawing area size to fit given grid size
+    pub fn set_grid_size_request(&self, columns: usize, rows: usize) {
         let &CellMetrics {
             line_height,
             char_width,
             ..
         } = self.render_state.borrow().font_ctx.cell_metrics();
-        let alloc = self.drawing_area.get_allocation();
+        let width = (columns as f64 * char_width).ceil() as i32;
+        let height = (rows as f64 * line_height).ceil() as i32;
+        self.drawing_area.set_size_request(
+            width + self.padding.left + self.padding.right,
+            height + self.padding.top + self.padding.bottom,
+        );
+    }
+
+    pub fn reset_size_request(&self) {
+        self.drawing_area.set_size_request(-1, -1);
+    }
+
+    /// Move grid area to drawing area coordinates
+    fn padded_area(&self, area: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+        let (x, y, width, height) = area;
+        (x + self.padding.left, y + self.padding.top, width, height)
+    }
+
+    /// Move drawing area coordinates to grid coordinates
+    fn grid_position(&self, position: (f64, f64)) -> (f64, f64) {
+        let (x, y) = position;
+        (
+            (x - f64::from(self.padding.left)).max(0.0),
+            (y - f64::from(self.padding.top)).max(0.0),
+        )
+    }
+
+    /// Column and row of cell under pointer position
+    fn grid_cell(&self, position: (f64, f64)) -> (u64, u64) {
+        let &CellMetrics {
+            line_height,
+            char_width,
+            ..
+        } = self.render_state.borrow().font_ctx.cell_metrics();
+        let (x, y) = self.grid_position(position);
         (
-            (alloc.width as f64 / char_width).trunc() as usize,
-            (alloc.height as f64 / line_height).trunc() as usize,
+            (x / char_width).trunc() as u64,
+            (y / line_height).trunc() as u64,
         )
     }
 
+    pub fn set_padding(&mut self, padding: &str) {
+        match render::Padding::parse(padding) {
+            Ok(padding) => {
+                if self.padding != padding {
+                    self.padding = padding;
+                    self.try_nvim_resize();
+                    self.queue_draw_all();
+                }
+            }
+            Err(e) => self.error_bar.show_warning(&e),
+        }
+    }
+
     fn show_error_area(&self) {
         let stack = self.stack.clone();
         gtk::idle_add(move || {
@@ -412,8 +872,9 @@ impl State {
     fn set_im_location(&self) {
         let (row, col) = self.model.get_cursor();
 
-        let (x, y, width, height) =
-            ModelRect::point(col, row).to_area(self.render_state.borrow().font_ctx.cell_metrics());
+        let (x, y, width, height) = self.padded_area(
+            ModelRect::point(col, row).to_area(self.render_state.borrow().font_ctx.cell_metrics()),
+        );
 
         self.im_context.set_cursor_location(&gdk::Rectangle {
             x,
@@ -426,7 +887,8 @@ impl State {
     }
 
     fn try_nvim_resize(&mut self) {
-        let (columns, rows) = self.calc_nvim_size();
+        // tiny window keeps minimal grid, placeholder is drawn instead of it
+        let (columns, rows) = render::clamp_grid_size(self.calc_nvim_size());
 
         let (requested_rows, requested_cols) = self.resize_request;
 
@@ -444,7 +906,7 @@ impl State {
         let nvim = self.nvim.clone();
         let resize_timer = self.resize_timer.clone();
 
-        let resize_id = gtk::timeout_add(200, move || {
+        let resize_id = gtk::timeout_add(RESIZE_DEBOUNCE_MS, move || {
             if let Some(mut nvim) = nvim.try_nvim() {
                 debug!("ui_try_resize {}/{}", columns, rows);
                 resize_timer.set(None);
@@ -463,6 +925,20 @@ impl State {
     }
 
     fn edit_paste(&self, clipboard: &str) {
+        if self.nvim.nvim_version_at_least(nvim::NVIM_PASTE_VERSION) {
+            let gtk_clipboard = match clipboard {
+                "*" => &self.clipboard_primary,
+                _ => &self.clipboard_clipboard,
+            };
+            let nvim = self.nvim.clone();
+            gtk_clipboard.request_text(move |_, text| {
+                if let (Some(text), Some(mut nvim)) = (text, nvim.nvim()) {
+                    nvim::paste(&mut *nvim, &text);
+                }
+            });
+            return;
+        }
+
         let nvim = self.nvim();
         if let Some(mut nvim) = nvim {
             let render_state = self.render_state.borrow();
@@ -504,7 +980,12 @@ impl State {
     pub fn set_autocmds(&self) {
         self.subscriptions
             .borrow()
-            .set_autocmds(&mut self.nvim().unwrap());
+            .set_autocmds(&mut self.nvim().unwrap(), self.channel_id());
+    }
+
+    /// Channel of this GUI, first channel when nvim is not initialized yet
+    fn channel_id(&self) -> u64 {
+        self.nvim.channel_id().unwrap_or(1)
     }
 
     pub fn notify(&self, params: Vec<Value>) -> Result<(), String> {
@@ -521,13 +1002,666 @@ impl State {
         self.set_font_rpc(&font_desc);
     }
 
-    pub fn set_font_rpc(&mut self, font_desc: &str) {
+    /// Return false in case font can't be loaded, error is reported to nvim
+    pub fn set_font_rpc(&mut self, font_desc: &str) -> bool {
+        let pango_context = match self.load_font_desc(font_desc) {
+            Ok(pango_context) => pango_context,
+            Err(err) => {
+                self.echo_error(&err);
+                return false;
+            }
+        };
+
         {
             let mut settings = self.settings.borrow_mut();
             settings.set_font_source(FontSource::Rpc);
         }
 
-        self.set_font_desc(font_desc);
+        // new font is default size for zoom
+        self.zoom_base_font = None;
+        self.set_font_context(pango_context);
+        true
+    }
+
+    /// Show error in nvim message area
+    fn echo_error(&self, msg: &str) {
+        if let Some(mut nvim) = self.nvim() {
+            let command = format!(
+                "echohl ErrorMsg | echomsg '{}' | echohl None",
+                msg.replace('\'', "''")
+            );
+            nvim.command_async(&command).cb(|r| r.report_err()).call();
+        }
+    }
+
+    /// State can be inconsistent after panic in redraw handler,
+    /// so grid is cleared and nvim sends whole screen again
+    pub fn recover_after_panic(&mut self) {
+        self.model.clear();
+        self.queue_draw_all();
+
+        if let Some(mut nvim) = self.nvim() {
+            let columns = self.model.columns as u64;
+            let rows = self.model.rows as u64;
+            // nvim redraws whole grid on resize
+            nvim.ui_try_resize_async(columns, rows.saturating_sub(1).max(1))
+                .cb(|r| r.report_err())
+                .call();
+            nvim.ui_try_resize_async(columns, rows)
+                .cb(|r| r.report_err())
+                .call();
+        }
+    }
+
+    /// Without GUI tabline nvim draws its own one in grid
+    pub fn set_tabline_enabled(&mut self, enabled: bool) {
+        self.tabs.set_enabled(enabled);
+
+        if let Some(mut nvim) = self.nvim() {
+            nvim.ui_set_option_async("ext_tabline", enabled.into())
+                .cb(|r| r.report_err())
+                .call();
+        }
+    }
+
+    pub fn set_fullscreen_hide_tabline(&mut self, hide: bool) {
+        self.tabs.set_hide_in_fullscreen(hide);
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.tabs.set_fullscreen(fullscreen);
+    }
+
+    pub fn save_screenshot(&mut self, path: String) {
+        if let Err(err) = self.render_to_file(&path) {
+            error!("Can't save screenshot: {}", err);
+            self.echo_error(&format!("Can't save screenshot {}: {}", path, err));
+        }
+    }
+
+    /// Render grid to svg or pdf file, format is selected by file extension
+    fn render_to_file(&self, path: &str) -> Result<(), String> {
+        if !self.nvim.is_initialized() {
+            return Err("nvim not initialized".to_owned());
+        }
+
+        let alloc = self.drawing_area.get_allocation();
+        let (width, height) = (f64::from(alloc.width), f64::from(alloc.height));
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        let surface: cairo::Surface = match extension.as_ref().map(String::as_str) {
+            Some("svg") => cairo::SvgSurface::new(width, height, path).deref().clone(),
+            Some("pdf") => cairo::PdfSurface::new(width, height, path).deref().clone(),
+            _ => return Err("unsupported format, use .svg or .pdf".to_owned()),
+        };
+
+        // file surface is empty, Source would force raster fallback for the whole page
+        draw_content(self, &cairo::Context::new(&surface), cairo::Operator::Over);
+        surface.finish();
+
+        match surface.status() {
+            cairo::Status::Success => Ok(()),
+            status => Err(format!("{:?}", status)),
+        }
+    }
+
+    /// Write recent redraw events and grid state to file for bug reports
+    pub fn dump_events(&mut self) {
+        match self.write_events_dump() {
+            Ok(path) => {
+                if let Some(mut nvim) = self.nvim() {
+                    let command = format!(
+                        "echomsg 'Events dump saved to {}'",
+                        path.replace('\'', "''")
+                    );
+                    nvim.command_async(&command).cb(|r| r.report_err()).call();
+                }
+            }
+            Err(err) => {
+                error!("Can't dump events: {}", err);
+                self.echo_error(&format!("Can't dump events: {}", err));
+            }
+        }
+    }
+
+    fn write_events_dump(&self) -> Result<String, String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut path = dirs::get_app_data_dir_create()?;
+        path.push(format!("events-{}.log", timestamp));
+
+        fs::write(&path, self.events_dump()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    fn events_dump(&self) -> String {
+        let render_state = self.render_state.borrow();
+        let font_ctx = &render_state.font_ctx;
+        let CellMetrics {
+            line_height,
+            char_width,
+            ascent,
+            underline_position,
+            underline_thickness,
+            ..
+        } = *font_ctx.cell_metrics();
+        let color_model = &render_state.color_model;
+        let (cursor_row, cursor_col) = self.model.get_cursor();
+
+        let mut dump = format!(
+            "grid: {}x{}, cursor {},{}\n\
+             font: {}\n\
+             cell: width {}, line height {}, ascent {}, \
+             underline position {}, underline thickness {}\n\
+             default colors: fg {}, bg {}, sp {}\n\
+             current attrs: {:?}\n\
+             recent redraw events, oldest first:\n",
+            self.model.columns,
+            self.model.rows,
+            cursor_row,
+            cursor_col,
+            font_ctx.font_description().to_string(),
+            char_width,
+            line_height,
+            ascent,
+            underline_position,
+            underline_thickness,
+            color_model.fg_color.to_hex(),
+            color_model.bg_color.to_hex(),
+            color_model.sp_color.to_hex(),
+            self.cur_attrs,
+        );
+        for event in crash::recent_events() {
+            dump.push_str(&event);
+            dump.push('\n');
+        }
+
+        dump
+    }
+
+    pub fn set_cursor_animation(&mut self, enabled: bool) {
+        self.cursor.as_mut().unwrap().set_move_animation(enabled);
+    }
+
+    pub fn set_cursor_shape(&mut self, name: &str) {
+        match mode::CursorShape::from_override_name(name) {
+            Ok(shape) => {
+                self.cursor.as_mut().unwrap().set_shape_override(shape);
+                self.queue_redraw_cursor();
+            }
+            Err(e) => self.echo_error(&e),
+        }
+    }
+
+    pub fn set_cursor_animation_easing(&mut self, easing: &str) {
+        match Easing::from_name(easing) {
+            Some(easing) => self.cursor
+                .as_mut()
+                .unwrap()
+                .set_move_animation_easing(easing),
+            None => error!("Unknown cursor animation easing {}", easing),
+        }
+    }
+
+    fn start_cursor_frame_updates(&self) {
+        if !self.cursor_frame_updating.replace(true) {
+            if let Some(frame_clock) = self.drawing_area.get_frame_clock() {
+                frame_clock.begin_updating();
+            } else {
+                self.cursor_frame_updating.set(false);
+            }
+        }
+    }
+
+    /// Redraw on every frame until cursor move animation finished
+    fn on_cursor_frame_update(&self, frame_clock: &gdk::FrameClock) {
+        if !self.cursor_frame_updating.get() {
+            return;
+        }
+
+        self.queue_draw_all();
+        self.notify_cursor_rect();
+
+        if !self.cursor.as_ref().unwrap().is_moving() {
+            frame_clock.end_updating();
+            self.cursor_frame_updating.set(false);
+        }
+    }
+
+    /// Report cursor screen rectangle on moves, at most ~30 times per second
+    pub fn set_cursor_rect_cb<F>(&mut self, cb: F)
+    where
+        F: Fn(CursorScreenRect) + 'static,
+    {
+        self.cursor_rect_throttle = Some(Throttle::new(CURSOR_RECT_INTERVAL_MS, cb));
+    }
+
+    /// Absolute screen rectangle of cursor cell, follows move animation
+    pub fn cursor_screen_rect(&self) -> Option<CursorScreenRect> {
+        let window = self.drawing_area.get_window()?;
+        let (_, origin_x, origin_y) = window.get_origin();
+
+        let (row, col) = self.model.get_cursor();
+        let (row, col) = self.cursor.as_ref()?.position(row, col);
+
+        let render_state = self.render_state.borrow();
+        let cell_metrics = render_state.font_ctx.cell_metrics();
+        let scale = f64::from(self.drawing_area.get_scale_factor());
+
+        let x = f64::from(origin_x + self.padding.left) + col * cell_metrics.char_width;
+        let y = f64::from(origin_y + self.padding.top) + row * cell_metrics.line_height;
+
+        Some((
+            (x * scale).round() as i32,
+            (y * scale).round() as i32,
+            (cell_metrics.char_width * scale).round() as i32,
+            (cell_metrics.line_height * scale).round() as i32,
+        ))
+    }
+
+    fn notify_cursor_rect(&self) {
+        if let Some(ref throttle) = self.cursor_rect_throttle {
+            if let Some(rect) = self.cursor_screen_rect() {
+                if self.last_cursor_rect.replace(Some(rect)) != Some(rect) {
+                    throttle.update(rect);
+                }
+            }
+        }
+    }
+
+    pub fn set_mouse_option(&self, mouse: &str) {
+        self.mouse_option.set(!mouse.is_empty());
+    }
+
+    /// Mouse is disabled by 'mouse' option, so gui handles selection itself
+    fn gui_mouse(&self) -> bool {
+        !self.mouse_enabled && !self.mouse_option.get()
+    }
+
+    /// Text beam while clicks are handled by nvim or gui selection
+    fn pointer_cursor(&self) -> MouseCursor {
+        if self.mouse_enabled || self.gui_mouse() {
+            MouseCursor::Text
+        } else {
+            MouseCursor::Default
+        }
+    }
+
+    /// Scroll with `<C-e>`/`<C-y>` when nvim mouse is disabled
+    pub fn set_scroll_without_mouse(&mut self, enabled: bool) {
+        self.scroll_without_mouse = enabled;
+    }
+
+    /// Switch input method, empty name restores default one
+    pub fn set_im_module(&mut self, module: String) {
+        let module = module.trim();
+        let settings = match gtk::Settings::get_default() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        settings.set_property_gtk_im_module(if module.is_empty() {
+            None
+        } else {
+            Some(module)
+        });
+
+        // multicontext picks up new module on next focus, so switch it now
+        self.im_context.set_context_id(module);
+        self.im_context.set_use_preedit(false);
+        self.im_context.reset();
+        if self.drawing_area.has_focus() {
+            self.im_context.focus_in();
+        }
+    }
+
+    /// Dim grid by given percent while window is not focused, 0 disables dimming
+    pub fn set_dim_inactive(&mut self, percent: u64) {
+        if percent > 100 {
+            self.error_bar.show_warning("Dim percent must be in range 0..100");
+            return;
+        }
+        self.dim_inactive = percent as u8;
+        self.drawing_area.queue_draw();
+    }
+
+    pub fn set_message_timeout(&mut self, timeout_ms: u64) {
+        if timeout_ms > u64::from(u32::max_value()) {
+            self.error_bar.show_warning("Message timeout is too big");
+            return;
+        }
+        self.error_bar.set_transient_timeout(timeout_ms as u32);
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        if self.focused != focused {
+            self.focused = focused;
+            if self.dim_inactive > 0 {
+                // overlay is not part of grid surface, no need to damage it
+                self.drawing_area.queue_draw();
+            }
+        }
+    }
+
+    /// Wheel events sent to nvim for each scroll step
+    pub fn set_scroll_lines(&mut self, scroll_lines: u64) {
+        let clamped = scroll_lines.max(1).min(MAX_SCROLL_LINES);
+        if clamped != scroll_lines {
+            self.error_bar.show_warning(&format!(
+                "Scroll lines must be in range 1..{}, {} is used",
+                MAX_SCROLL_LINES, clamped
+            ));
+        }
+        self.scroll_lines = clamped as usize;
+    }
+
+    pub fn set_overscroll(&mut self, overscroll: bool) {
+        self.overscroll_enabled = overscroll;
+    }
+
+    pub fn set_buffer_edges(&self, top: bool, bottom: bool) {
+        self.buffer_edges.set((top, bottom));
+    }
+
+    /// Kinetic scroll at first or last line pulls grid instead of sending input to nvim
+    fn try_overscroll(&self, ev: &EventScroll) -> bool {
+        if !self.overscroll_enabled || !is_touch_scroll(ev) {
+            return false;
+        }
+
+        let delta_y = ev.as_ref().delta_y;
+        let (top, bottom) = self.buffer_edges.get();
+        if !(delta_y < 0.0 && top || delta_y > 0.0 && bottom) {
+            return false;
+        }
+
+        let max = self.render_state.borrow().font_ctx.cell_metrics().line_height / 2.0;
+        self.overscroll.borrow_mut().pull(-delta_y * max, max);
+        self.start_overscroll_spring_back();
+        self.drawing_area.queue_draw();
+        true
+    }
+
+    fn start_overscroll_spring_back(&self) {
+        if self.overscroll_timer.get().is_some() {
+            return;
+        }
+
+        let overscroll = self.overscroll.clone();
+        let timer = self.overscroll_timer.clone();
+        let drawing_area = self.drawing_area.clone();
+        let timer_id = gtk::timeout_add(overscroll::SPRING_BACK_INTERVAL_MS, move || {
+            let running = overscroll.borrow_mut().spring_back_step();
+            drawing_area.queue_draw();
+            if !running {
+                timer.set(None);
+            }
+            Continue(running)
+        });
+        self.overscroll_timer.set(Some(timer_id));
+    }
+
+    pub fn set_horizontal_scroll(&mut self, horizontal_scroll: bool) {
+        self.horizontal_scroll = horizontal_scroll;
+    }
+
+    pub fn set_nowrap_indicator(&mut self, nowrap_indicator: bool) {
+        if self.nowrap_indicator != nowrap_indicator {
+            self.nowrap_indicator = nowrap_indicator;
+            self.queue_draw_all();
+        }
+    }
+
+    fn row_at(&self, y: f64) -> usize {
+        let line_height = self.render_state.borrow().font_ctx.cell_metrics().line_height;
+        let (_, y) = self.grid_position((0.0, y));
+        let row = (y / line_height).trunc() as usize;
+        row.min(self.model.rows.saturating_sub(1))
+    }
+
+    fn start_selection(&mut self, y: f64) {
+        let row = self.row_at(y);
+        self.selection = Some((row, row));
+        self.queue_draw_all();
+    }
+
+    fn extend_selection(&mut self, y: f64) {
+        let row = self.row_at(y);
+        if let Some((start, _)) = self.selection {
+            self.selection = Some((start, row));
+            self.queue_draw_all();
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.queue_draw_all();
+        }
+    }
+
+    /// Copy selected lines to PRIMARY, like terminal does
+    fn copy_selection(&self) {
+        if let Some((start, end)) = self.selection {
+            let text = self.model.rows_text(start.min(end), start.max(end));
+            self.clipboard_primary_set(&text);
+        }
+    }
+
+    pub fn show_error(&self, err: &str) {
+        self.error_bar.show_error(err);
+    }
+
+    /// Save session on exit and restore previous one if allowed by options
+    pub fn init_session(state_arc: &Arc<UiMutex<State>>) {
+        let state = state_arc.borrow();
+        let mut nvim = match state.nvim() {
+            Some(nvim) => nvim,
+            None => return,
+        };
+
+        state.save_session.set(true);
+        let state_ref = state_arc.clone();
+        let res = session::init(&mut *nvim, state.options.restore_session, move |err| {
+            let mut err = Some(err);
+            glib::idle_add(move || {
+                if let Some(err) = err.take() {
+                    state_ref.borrow().show_error(&err);
+                }
+                Continue(false)
+            });
+        });
+        if let Err(err) = res {
+            state.show_error(&err);
+        }
+    }
+
+    pub fn version_info(&self) -> VersionInfo {
+        VersionInfo::new(
+            self.nvim.connected_neovim_version(),
+            &self.dropped_ui_options,
+        )
+    }
+
+    /// Features of ui options not supported by nvim are disabled
+    fn set_dropped_ui_options(&mut self, dropped_options: Vec<String>) {
+        if dropped_options.is_empty() {
+            return;
+        }
+
+        if dropped_options.iter().any(|o| o == "ext_tabline") {
+            self.tabs.set_enabled(false);
+        }
+
+        let features = dropped_options
+            .iter()
+            .map(|o| format!("{} (nvim {})", o, nvim::ui_option_min_version(o)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.error_bar.show_warning(&format!(
+            "Features not supported by this nvim version are disabled: {}",
+            features
+        ));
+
+        self.dropped_ui_options = dropped_options;
+    }
+
+    /// Expose versions to vimscript as `g:nvim_gtk_version`
+    pub fn set_version_var(&self) {
+        if let Some(mut nvim) = self.nvim() {
+            nvim.set_var("nvim_gtk_version", self.version_info().to_value())
+                .report_err();
+        }
+    }
+
+    /// Shim commands send notifications to `g:GtkGuiChannel`
+    pub fn set_channel_var(&self) {
+        if let Some(mut nvim) = self.nvim() {
+            nvim.set_var("GtkGuiChannel", Value::from(self.channel_id()))
+                .report_err();
+        }
+    }
+
+    /// Colorscheme without gui colors looks broken, as cterm colors are never used
+    pub fn check_colorscheme(&self, name: &str, gui_bg: &str, cterm_bg: &str) {
+        if gui_bg.is_empty() && !cterm_bg.is_empty() {
+            let msg = format!(
+                "Colorscheme {} sets only cterm colors, gui colors are used",
+                name
+            );
+            warn!("{}", msg);
+            self.error_bar.show_warning(&msg);
+        }
+    }
+
+    /// Tabline, cmdline and error bar follow colorscheme groups,
+    /// css is set for whole screen as popover is not a child of shell,
+    /// style class limits it to widgets of this shell
+    fn init_chrome_css(&self) {
+        let scope = misc::unique_style_class("nvim-gtk-shell");
+        self.tabs.get_style_context().unwrap().add_class(&scope);
+        self.error_bar.get_style_context().unwrap().add_class(&scope);
+        self.cmd_line.add_style_class(&scope);
+
+        let css_provider = gtk::CssProvider::new();
+        misc::add_screen_provider(&self.drawing_area, &css_provider);
+
+        let throttle = Throttle::new(CHROME_CSS_INTERVAL_MS, move |css: String| {
+            if let Err(e) = css_provider.load_from_data(css.as_bytes()) {
+                error!("Can't update css {}", e);
+            }
+        });
+        self.render_state
+            .borrow()
+            .color_model
+            .theme
+            .set_changed_cb(move |chrome| throttle.update(chrome.css(&scope)));
+    }
+
+    pub fn update_theme(&self) {
+        if let Some(mut nvim) = self.nvim() {
+            let render_state = self.render_state.borrow();
+            render_state.color_model.theme.queue_update(&mut *nvim);
+        }
+    }
+
+    /// Named colors are requested once, they don't change while nvim runs
+    fn load_color_map(state_arc: &Arc<UiMutex<State>>) {
+        let state = state_arc.borrow();
+        let mut nvim = match state.nvim() {
+            Some(nvim) => nvim,
+            None => return,
+        };
+
+        let state_arc = state_arc.clone();
+        nvim.session
+            .call_async::<Value>("nvim_get_color_map", vec![])
+            .cb(move |res| {
+                let color_map = res.map_err(|e| e.to_string()).and_then(|v| match v {
+                    Value::Map(map) => Ok(map),
+                    v => Err(format!("Color map expected, got {}", v)),
+                });
+                let color_map = match color_map {
+                    Ok(color_map) => color_map,
+                    Err(e) => {
+                        warn!("Can't load color map: {}", e);
+                        return;
+                    }
+                };
+
+                let mut color_map = Some(color_map);
+                glib::idle_add(clone!(state_arc => move || {
+                    if let Some(color_map) = color_map.take() {
+                        state_arc.borrow_mut().color_map = color_map;
+                    }
+                    Continue(false)
+                }));
+            })
+            .call();
+    }
+
+    /// `#rrggbb` or color name known by nvim
+    pub fn resolve_color(&self, name: &str) -> Result<Color, String> {
+        color::resolve_color(&self.color_map, name)
+            .ok_or_else(|| format!("Unknown color {}", name))
+    }
+
+    pub fn show_nvim_error(&self, event: &nvim::ErrorEvent) {
+        self.error_bar.show_transient_error(&event.to_string());
+    }
+
+    /// Warn about other GUIs plugins, they can break nvim-gtk on startup
+    pub fn check_gui_plugins(&self) {
+        // nvim_get_autocmds is available since 0.7
+        if !self.nvim.nvim_version_at_least((0, 7, 0)) {
+            return;
+        }
+
+        if let Some(mut nvim) = self.nvim() {
+            match nvim::conflicting_gui_plugins(&mut *nvim) {
+                Ok(ref plugins) if plugins.is_empty() => (),
+                Ok(plugins) => {
+                    let msg = format!(
+                        "Plugins of other GUI found: {}, consider to remove them from init.lua",
+                        plugins.join(", ")
+                    );
+                    warn!("{}", msg);
+                    self.error_bar.show_warning(&msg);
+                }
+                Err(err) => warn!("Can't check VimEnter autocommands: {}", err),
+            }
+        }
+    }
+
+    pub fn set_color_columns(&self, color_columns: render::ColorColumns) {
+        if *self.color_columns.borrow() != color_columns {
+            *self.color_columns.borrow_mut() = color_columns;
+            self.queue_draw_all();
+        }
+    }
+
+    pub fn set_nowrap_window(&self, nowrap_window: Option<render::NowrapWindow>) {
+        if *self.nowrap_window.borrow() != nowrap_window {
+            *self.nowrap_window.borrow_mut() = nowrap_window;
+            if self.nowrap_indicator {
+                self.queue_draw_all();
+            }
+        }
+    }
+
+    /// Used to expand tabs of externalized cmdline
+    pub fn set_tabstop(&self, tabstop: usize) {
+        self.render_state.borrow_mut().tabstop = tabstop;
+    }
+
+    pub fn set_cursor_line(&self, cursor_line: Option<render::WinInfo>) {
+        if self.cursor_line.replace(cursor_line.clone()) != cursor_line {
+            self.queue_draw_all();
+        }
     }
 
     pub fn on_command(&mut self, command: nvim::NvimCommand) {
@@ -541,6 +1675,40 @@ impl State {
     }
 }
 
+/// Grid context menu, first item depends on clicked cell
+struct ContextMenu {
+    menu: gtk::Menu,
+    open_url: gtk::MenuItem,
+    copy_cell: gtk::MenuItem,
+    /// Url or text of clicked cell
+    target: Rc<RefCell<String>>,
+}
+
+impl ContextMenu {
+    fn popup(&self, model: &UiModel, row: usize, col: usize) {
+        let in_grid = row < model.rows && col < model.columns;
+
+        match if in_grid { model.url_at(row, col) } else { None } {
+            Some(url) => {
+                *self.target.borrow_mut() = url;
+                self.open_url.show();
+                self.copy_cell.hide();
+            }
+            None => {
+                *self.target.borrow_mut() = if in_grid {
+                    model.cell_text(row, col)
+                } else {
+                    String::new()
+                };
+                self.open_url.hide();
+                self.copy_cell.show();
+            }
+        }
+
+        self.menu.popup_at_pointer(None);
+    }
+}
+
 #[derive(PartialEq)]
 enum MouseCursor {
     None,
@@ -592,6 +1760,11 @@ pub struct ShellOptions {
     args_for_neovim: Vec<String>,
     input_data: Option<String>,
     enable_swap: bool,
+    record_events: Option<String>,
+    replay_events: Option<String>,
+    replay_max_speed: bool,
+    restore_session: bool,
+    print_server_address: bool,
 }
 
 impl ShellOptions {
@@ -601,8 +1774,6 @@ impl ShellOptions {
         input_data: Option<String>,
     ) -> Self {
         ShellOptions {
-            open_paths,
-            input_data,
             nvim_bin_path: matches.value_of("nvim-bin-path").map(str::to_owned),
             timeout: value_t!(matches.value_of("timeout"), u64)
                 .map(Duration::from_secs)
@@ -612,9 +1783,25 @@ impl ShellOptions {
                 .map(|args| args.map(str::to_owned).collect())
                 .unwrap_or(vec![]),
             enable_swap: matches.is_present("enable-swap"),
+            record_events: matches.value_of("record-events").map(str::to_owned),
+            replay_events: matches.value_of("replay-events").map(str::to_owned),
+            replay_max_speed: matches.value_of("replay-speed") == Some("max"),
+            print_server_address: matches.is_present("print-server-address"),
+            // files or piped text are opened instead of previous session
+            restore_session: !matches.is_present("no-restore")
+                && open_paths.is_empty()
+                && input_data.is_none(),
+            open_paths,
+            input_data,
         }
     }
 
+    /// Only first window of application restores session
+    pub fn without_session_restore(mut self) -> Self {
+        self.restore_session = false;
+        self
+    }
+
     // remove input data from original
     // shell option, as it need to be used only once
     pub fn take(&mut self) -> Self {
@@ -661,14 +1848,18 @@ impl Shell {
         state.drawing_area.set_can_focus(true);
 
         state.im_context.set_use_preedit(false);
+        state.init_chrome_css();
 
         let nvim_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
 
+        nvim_box.pack_start(&state.slow_init_bar, false, true, 0);
+        nvim_box.pack_start(&*state.error_bar, false, true, 0);
         nvim_box.pack_start(&*state.tabs, false, true, 0);
         nvim_box.pack_start(&state.drawing_area, true, true, 0);
 
         state.stack.add_named(&nvim_box, "Nvim");
         state.stack.add_named(&*state.error_area, "Error");
+        init_slow_init_bar(&self.state);
 
         self.widget.pack_start(&state.stack, true, true, 0);
 
@@ -729,6 +1920,12 @@ impl Shell {
                 .as_mut()
                 .unwrap()
                 .reset_state();
+            ref_state.borrow().error_bar.dismiss_transient();
+
+            if let Some(zoom) = input::zoom_shortcut(ev) {
+                ref_state.borrow_mut().zoom(zoom);
+                return Inhibit(true);
+            }
 
             if ref_state.borrow().im_context.filter_keypress(ev) {
                 Inhibit(true)
@@ -773,6 +1970,12 @@ impl Shell {
 
         let ref_state = self.state.clone();
         state.drawing_area.connect_realize(move |w| {
+            if let Some(frame_clock) = w.get_frame_clock() {
+                frame_clock.connect_update(clone!(ref_state => move |frame_clock| {
+                    ref_state.borrow().on_cursor_frame_update(frame_clock)
+                }));
+            }
+
             // sometime set_client_window does not work without idle_add
             // and looks like not enabled im_context
             gtk::idle_add(clone!(ref_state, w => move || {
@@ -788,6 +1991,11 @@ impl Shell {
             .im_context
             .connect_commit(move |_, ch| ref_state.borrow().im_commit(ch));
 
+        let ref_state = self.state.clone();
+        state
+            .im_context
+            .connect_preedit_changed(move |_| ref_state.borrow().im_preedit_changed());
+
         let ref_state = self.state.clone();
         state.drawing_area.connect_configure_event(move |_, ev| {
             debug!("configure_event {:?}", ev.get_size());
@@ -803,6 +2011,12 @@ impl Shell {
             init_nvim(&ref_state);
         });
 
+        let ref_state = self.state.clone();
+        gtk::timeout_add(INIT_ALLOCATION_TIMEOUT_MS, move || {
+            init_nvim(&ref_state);
+            Continue(false)
+        });
+
         let ref_state = self.state.clone();
         let targets = vec![gtk::TargetEntry::new(
             "text/uri-list",
@@ -815,24 +2029,37 @@ impl Shell {
         state
             .drawing_area
             .connect_drag_data_received(move |_, _, _, _, s, _, _| {
-                let uris = s.get_uris();
-                let command = uris.iter().filter_map(|uri| decode_uri(uri)).fold(
-                    ":ar".to_owned(),
-                    |command, filename| {
-                        let filename = escape_filename(&filename);
-                        command + " " + &filename
-                    },
-                );
-                let state = ref_state.borrow_mut();
-                let mut nvim = state.nvim().unwrap();
-                nvim.command_async(&command).cb(|r| r.report_err()).call()
+                let state = ref_state.borrow();
+                // remote files are dropped too, they have local path only when mounted by gvfs
+                let paths: Vec<_> = s
+                    .get_uris()
+                    .iter()
+                    .filter_map(|uri| {
+                        gio::File::new_for_uri(uri)
+                            .get_path()
+                            .map(|path| path.to_string_lossy().into_owned())
+                            .or_else(|| decode_uri(uri))
+                    })
+                    .map(|path| {
+                        let is_dir = Path::new(&path).is_dir();
+                        (path, is_dir)
+                    })
+                    .collect();
+
+                if let Some(mut nvim) = state.nvim() {
+                    for command in nvim::dropped_files_commands(&paths) {
+                        nvim.command_async(&command).cb(|r| r.report_err()).call();
+                    }
+                }
             });
 
+        let ref_state = self.state.clone();
         let ui_state_ref = self.ui_state.clone();
         state.drawing_area.connect_enter_notify_event(move |_, ev| {
+            let cursor = ref_state.borrow().pointer_cursor();
             ui_state_ref
                 .borrow_mut()
-                .apply_mouse_cursor(MouseCursor::Text, ev.get_window());
+                .apply_mouse_cursor(cursor, ev.get_window());
             gtk::Inhibit(false)
         });
 
@@ -845,8 +2072,33 @@ impl Shell {
         });
     }
 
-    fn create_context_menu(&self) -> gtk::Menu {
+    fn create_context_menu(&self) -> ContextMenu {
         let menu = gtk::Menu::new();
+        let target = Rc::new(RefCell::new(String::new()));
+
+        let open_url = gtk::MenuItem::new_with_label("Open URL in browser");
+        let ref_state = self.state.clone();
+        let ref_target = target.clone();
+        open_url.connect_activate(move |_| {
+            let url = ref_target.borrow();
+            if let Err(err) =
+                gio::AppInfo::launch_default_for_uri(&url, None::<&gio::AppLaunchContext>)
+            {
+                ref_state
+                    .borrow()
+                    .show_error(&format!("Can't open {}: {}", url, err));
+            }
+        });
+
+        let copy_cell = gtk::MenuItem::new_with_label("Copy Cell");
+        let ref_state = self.state.clone();
+        let ref_target = target.clone();
+        copy_cell.connect_activate(move |_| {
+            ref_state
+                .borrow()
+                .clipboard_clipboard_set(&ref_target.borrow())
+        });
+
         let copy = gtk::MenuItem::new_with_label("Copy");
         let ref_state = self.state.clone();
         copy.connect_activate(move |_| ref_state.borrow().edit_copy("+"));
@@ -857,10 +2109,21 @@ impl Shell {
         paste.connect_activate(move |_| ref_state.borrow().edit_paste("+"));
         paste.show_all();
 
+        let separator = gtk::SeparatorMenuItem::new();
+        separator.show();
+
+        menu.append(&open_url);
+        menu.append(&copy_cell);
+        menu.append(&separator);
         menu.append(&copy);
         menu.append(&paste);
 
-        menu
+        ContextMenu {
+            menu,
+            open_url,
+            copy_cell,
+            target,
+        }
     }
 
     #[cfg(unix)]
@@ -880,11 +2143,20 @@ impl Shell {
         self.state.borrow().cd(path);
     }
 
-    pub fn detach_ui(&mut self) {
+    pub fn cwd(&self) -> Option<String> {
+        self.state.borrow().cwd()
+    }
+
+    pub fn detach_ui(&self) {
         let state = self.state.borrow();
 
         let nvim = state.nvim();
         if let Some(mut nvim) = nvim {
+            if state.save_session.get() {
+                if let Err(err) = session::save(&mut *nvim) {
+                    error!("Can't save session: {}", err);
+                }
+            }
             nvim.ui_detach().expect("Error in ui_detach");
         }
     }
@@ -935,6 +2207,99 @@ impl Shell {
         state.set_nvim_command_cb(cb);
     }
 
+    /// Run subscription after nvim finishes autocmd that is running now.
+    ///
+    /// Autocmd for not current buffer temporary makes it current,
+    /// so expressions like `expand('%')` must not be evaluated inside it.
+    pub fn run_deferred(&self, handle: &SubscriptionHandle) {
+        let state = self.state.borrow();
+        let mut nvim = match state.try_nvim() {
+            Some(nvim) => nvim,
+            None => return,
+        };
+
+        let expr = state.subscriptions.borrow().args_expr(handle);
+        let state_ref = self.state.clone();
+        let handle = handle.clone();
+        nvim.session
+            .call_async::<Value>("nvim_eval", vec![expr.into()])
+            .cb(move |res| match res {
+                Ok(Value::Array(values)) => {
+                    let mut values = Some(values);
+                    glib::idle_add(move || {
+                        if let Some(values) = values.take() {
+                            let state = state_ref.borrow();
+                            let subscriptions = state.subscriptions.borrow();
+                            if let Err(e) = subscriptions.notify_values(&handle, values) {
+                                error!("Error running {:?}: {}", handle, e);
+                            }
+                        }
+                        Continue(false)
+                    });
+                }
+                Ok(value) => error!("Unexpected value of {:?}: {}", handle, value),
+                Err(e) => error!("Error running {:?}: {}", handle, e),
+            })
+            .call();
+    }
+
+    pub fn set_cursor_line(&self, args: &[String]) {
+        let cursor_line = if args[0] == "1" {
+            render::WinInfo::parse(&args[1..])
+        } else {
+            None
+        };
+        self.state.borrow().set_cursor_line(cursor_line);
+    }
+
+    pub fn set_mouse_option(&self, mouse: &str) {
+        self.state.borrow().set_mouse_option(mouse);
+    }
+
+    pub fn check_colorscheme(&self, args: &[String]) {
+        let state = self.state.borrow();
+        state.check_colorscheme(&args[0], &args[1], &args[2]);
+        state.update_theme();
+    }
+
+    pub fn set_tabstop(&self, tabstop: &str) {
+        match tabstop.parse() {
+            Ok(tabstop) => self.state.borrow().set_tabstop(tabstop),
+            Err(e) => error!("Can't convert tabstop to integer: {}", e),
+        }
+    }
+
+    pub fn set_color_columns(&self, args: &[String]) {
+        let textwidth = args[1].parse::<i64>().unwrap_or(0);
+
+        let color_columns = match render::WinInfo::parse(&args[2..]) {
+            Some(window) => render::ColorColumns::parse(&args[0], textwidth, &window),
+            None => render::ColorColumns::default(),
+        };
+        self.state.borrow().set_color_columns(color_columns);
+    }
+
+    pub fn set_nowrap_window(&self, args: &[String]) {
+        let int_arg = |idx: usize| args[idx].parse::<i64>().unwrap_or(0);
+
+        self.state.borrow().set_nowrap_window(render::NowrapWindow::parse(
+            &args[0],
+            int_arg(1),
+            int_arg(2),
+            int_arg(3),
+            int_arg(4),
+        ));
+    }
+
+    pub fn set_buffer_edges(&self, args: &[String]) {
+        let top_line = args[0].parse::<i64>().unwrap_or(0);
+        let last_line = args[1].parse::<i64>().unwrap_or(0);
+
+        self.state
+            .borrow()
+            .set_buffer_edges(top_line <= 1, top_line >= last_line);
+    }
+
     pub fn set_completeopts(&self, options: &str) {
         self.state
             .borrow()
@@ -960,6 +2325,7 @@ fn gtk_focus_in(state: &mut State) -> Inhibit {
 
     state.im_context.focus_in();
     state.cursor.as_mut().unwrap().enter_focus();
+    state.set_focused(true);
     let point = state.model.cur_point();
     state.on_redraw(&RepaintMode::Area(point));
     Inhibit(false)
@@ -974,6 +2340,7 @@ fn gtk_focus_out(state: &mut State) -> Inhibit {
 
     state.im_context.focus_out();
     state.cursor.as_mut().unwrap().leave_focus();
+    state.set_focused(false);
     let point = state.model.cur_point();
     state.on_redraw(&RepaintMode::Area(point));
 
@@ -982,25 +2349,40 @@ fn gtk_focus_out(state: &mut State) -> Inhibit {
 
 fn gtk_scroll_event(state: &mut State, ui_state: &mut UiState, ev: &EventScroll) -> Inhibit {
     if !state.mouse_enabled && !state.nvim.is_initializing() {
+        if state.scroll_without_mouse && state.gui_mouse() {
+            scroll_without_mouse(state, ev);
+        }
         return Inhibit(false);
     }
 
     state.close_popup_menu();
 
-    match ev.get_direction() {
-        gdk::ScrollDirection::Right => {
-            mouse_input(state, "ScrollWheelRight", ev.get_state(), ev.get_position())
-        }
-        gdk::ScrollDirection::Left => {
-            mouse_input(state, "ScrollWheelLeft", ev.get_state(), ev.get_position())
-        }
-        gdk::ScrollDirection::Up => {
-            mouse_input(state, "ScrollWheelUp", ev.get_state(), ev.get_position())
-        }
-        gdk::ScrollDirection::Down => {
-            mouse_input(state, "ScrollWheelDown", ev.get_state(), ev.get_position())
+    if state.horizontal_scroll && ev.get_state().contains(ModifierType::SHIFT_MASK) {
+        if let Some(right) = shift_scroll_right(ev) {
+            let input = if right {
+                "ScrollWheelRight"
+            } else {
+                "ScrollWheelLeft"
+            };
+            let modifiers = ev.get_state() - ModifierType::SHIFT_MASK;
+            for _ in 0..state.scroll_lines {
+                mouse_input(state, input, modifiers, ev.get_position());
+            }
+            return Inhibit(false);
         }
+    }
+
+    match ev.get_direction() {
+        gdk::ScrollDirection::Right => scroll_wheel(state, "ScrollWheelRight", ev),
+        gdk::ScrollDirection::Left => scroll_wheel(state, "ScrollWheelLeft", ev),
+        gdk::ScrollDirection::Up => scroll_wheel(state, "ScrollWheelUp", ev),
+        gdk::ScrollDirection::Down => scroll_wheel(state, "ScrollWheelDown", ev),
         gdk::ScrollDirection::Smooth => {
+            if state.try_overscroll(ev) {
+                ui_state.scroll_delta.1 = 0.0;
+                return Inhibit(false);
+            }
+
             // Remember and accumulate scroll deltas, so slow scrolling still
             // works.
             ui_state.scroll_delta.0 += ev.as_ref().delta_x;
@@ -1009,16 +2391,16 @@ fn gtk_scroll_event(state: &mut State, ui_state: &mut UiState, ev: &EventScroll)
             let x = ui_state.scroll_delta.0 as isize;
             let y = ui_state.scroll_delta.1 as isize;
             for _ in 0..x {
-                mouse_input(state, "ScrollWheelRight", ev.get_state(), ev.get_position())
+                scroll_wheel(state, "ScrollWheelRight", ev)
             }
             for _ in 0..-x {
-                mouse_input(state, "ScrollWheelLeft", ev.get_state(), ev.get_position())
+                scroll_wheel(state, "ScrollWheelLeft", ev)
             }
             for _ in 0..y {
-                mouse_input(state, "ScrollWheelDown", ev.get_state(), ev.get_position())
+                scroll_wheel(state, "ScrollWheelDown", ev)
             }
             for _ in 0..-y {
-                mouse_input(state, "ScrollWheelUp", ev.get_state(), ev.get_position())
+                scroll_wheel(state, "ScrollWheelUp", ev)
             }
             // Subtract performed scroll deltas.
             ui_state.scroll_delta.0 -= x as f64;
@@ -1029,44 +2411,119 @@ fn gtk_scroll_event(state: &mut State, ui_state: &mut UiState, ev: &EventScroll)
     Inhibit(false)
 }
 
+/// Send wheel event repeated by scroll lines setting
+fn scroll_wheel(state: &mut State, input: &str, ev: &EventScroll) {
+    for _ in 0..state.scroll_lines {
+        mouse_input(state, input, ev.get_state(), ev.get_position());
+    }
+}
+
+/// Touchpad and touchscreen scroll is kinetic, wheel scroll never overscrolls
+fn is_touch_scroll(ev: &EventScroll) -> bool {
+    ev.get_source_device()
+        .map_or(false, |device| match device.get_source() {
+            gdk::InputSource::Touchpad | gdk::InputSource::Touchscreen => true,
+            _ => false,
+        })
+}
+
+/// Vertical wheel direction used for horizontal scroll with Shift, true is to the right
+fn shift_scroll_right(ev: &EventScroll) -> Option<bool> {
+    match ev.get_direction() {
+        gdk::ScrollDirection::Up => Some(false),
+        gdk::ScrollDirection::Down => Some(true),
+        gdk::ScrollDirection::Smooth if ev.as_ref().delta_y < 0.0 => Some(false),
+        gdk::ScrollDirection::Smooth if ev.as_ref().delta_y > 0.0 => Some(true),
+        _ => None,
+    }
+}
+
+fn scroll_without_mouse(state: &mut State, ev: &EventScroll) {
+    if state.horizontal_scroll && ev.get_state().contains(ModifierType::SHIFT_MASK) {
+        if let Some(right) = shift_scroll_right(ev) {
+            let input = format!("{}{}", state.scroll_lines, if right { "zl" } else { "zh" });
+            if let Some(mut nvim) = state.try_nvim() {
+                nvim.input_async(&input).cb(|r| r.report_err()).call();
+            }
+            return;
+        }
+    }
+
+    let input = match ev.get_direction() {
+        gdk::ScrollDirection::Up => "<C-y>",
+        gdk::ScrollDirection::Down => "<C-e>",
+        gdk::ScrollDirection::Smooth if ev.as_ref().delta_y < 0.0 => "<C-y>",
+        gdk::ScrollDirection::Smooth if ev.as_ref().delta_y > 0.0 => "<C-e>",
+        _ => return,
+    };
+
+    if let Some(mut nvim) = state.try_nvim() {
+        nvim.input_async(&input.repeat(state.scroll_lines))
+            .cb(|r| r.report_err())
+            .call();
+    }
+}
+
 fn gtk_button_press(
     shell: &mut State,
     ui_state: &Rc<RefCell<UiState>>,
     ev: &EventButton,
-    menu: &gtk::Menu,
+    menu: &ContextMenu,
 ) -> Inhibit {
     if ev.get_event_type() != EventType::ButtonPress {
         return Inhibit(false);
     }
 
+    if ev.get_button() == 1
+        && ev.get_state().contains(ModifierType::CONTROL_MASK)
+        && shell.open_path_at(ev.get_position())
+    {
+        return Inhibit(true);
+    }
+
     if shell.mouse_enabled {
         ui_state.borrow_mut().mouse_pressed = true;
+        shell.clear_selection();
 
         match ev.get_button() {
             1 => mouse_input(shell, "LeftMouse", ev.get_state(), ev.get_position()),
             2 => mouse_input(shell, "MiddleMouse", ev.get_state(), ev.get_position()),
-            3 => menu.popup_at_pointer(None),
-
+            3 => mouse_input(shell, "RightMouse", ev.get_state(), ev.get_position()),
             _ => (),
         }
+    } else if ev.get_button() == 3 {
+        // nvim doesn't handle mouse, so right click is free for context menu
+        let (col, row) = shell.grid_cell(ev.get_position());
+        menu.popup(&shell.model, row as usize, col as usize);
+    } else if !shell.gui_mouse() {
+        // click only focuses window until nvim enables mouse again
+        shell.drawing_area.grab_focus();
+    } else if ev.get_button() == 1 && shell.nvim.is_initialized() {
+        ui_state.borrow_mut().mouse_pressed = true;
+        shell.start_selection(ev.get_position().1);
     }
     Inhibit(false)
 }
 
 fn mouse_input(shell: &mut State, input: &str, state: ModifierType, position: (f64, f64)) {
     if let Some(mut nvim) = shell.try_nvim() {
-        let &CellMetrics {
-            line_height,
-            char_width,
-            ..
-        } = shell.render_state.borrow().font_ctx.cell_metrics();
-        let (x, y) = position;
-        let col = (x / char_width).trunc() as u64;
-        let row = (y / line_height).trunc() as u64;
+        let (col, row) = shell.grid_cell(position);
+
+        // clickable statusline and tabline regions (`%@Func@label%X`) are
+        // hit tested by nvim itself, so GUI doesn't check them
+        if shell.nvim.nvim_version_at_least(nvim::NVIM_INPUT_MOUSE_VERSION) {
+            let modifiers = input::mouse_modifiers(state);
+            if nvim::input_mouse(&mut *nvim, input, &modifiers, row, col) {
+                return;
+            }
+        }
+
+        // old nvim handles keys as click in buffer, even on statusline
         let input_str = format!("{}<{},{}>", keyval_to_input_string(input, state), col, row);
 
-        nvim.input(&input_str)
-            .expect("Can't send mouse input event");
+        nvim.input_async(&input_str)
+            .cb(|r| r.report_err())
+            .call();
     }
 }
 
@@ -1080,6 +2537,8 @@ fn gtk_button_release(shell: &mut State, ui_state: &mut UiState, ev: &EventButto
             3 => mouse_input(shell, "RightRelease", ev.get_state(), ev.get_position()),
             _ => (),
         }
+    } else if ev.get_button() == 1 && shell.gui_mouse() {
+        shell.copy_selection();
     }
 
     Inhibit(false)
@@ -1087,15 +2546,32 @@ fn gtk_button_release(shell: &mut State, ui_state: &mut UiState, ev: &EventButto
 
 fn gtk_motion_notify(shell: &mut State, ui_state: &mut UiState, ev: &EventMotion) -> Inhibit {
     if shell.mouse_enabled && ui_state.mouse_pressed {
-        mouse_input(shell, "LeftDrag", ev.get_state(), ev.get_position());
+        let state = ev.get_state();
+        let input = if state.contains(ModifierType::BUTTON1_MASK) {
+            Some("LeftDrag")
+        } else if state.contains(ModifierType::BUTTON2_MASK) {
+            Some("MiddleDrag")
+        } else if state.contains(ModifierType::BUTTON3_MASK) {
+            Some("RightDrag")
+        } else {
+            None
+        };
+        if let Some(input) = input {
+            mouse_input(shell, input, state, ev.get_position());
+        }
+    } else if ui_state.mouse_pressed && shell.gui_mouse() {
+        shell.extend_selection(ev.get_position().1);
     }
 
-    ui_state.apply_mouse_cursor(MouseCursor::Text, shell.drawing_area.get_window());
+    ui_state.apply_mouse_cursor(shell.pointer_cursor(), shell.drawing_area.get_window());
     Inhibit(false)
 }
 
-fn draw_content(state: &State, ctx: &cairo::Context) {
+/// Grid surface is drawn with `Operator::Source`, so transparent background replaces old content
+fn draw_content(state: &State, ctx: &cairo::Context, operator: cairo::Operator) {
     ctx.push_group();
+    // background fill paints whole clip, so padding gets same color
+    ctx.translate(f64::from(state.padding.left), f64::from(state.padding.top));
 
     let render_state = state.render_state.borrow();
     render::render(
@@ -1105,7 +2581,18 @@ fn draw_content(state: &State, ctx: &cairo::Context) {
         &state.model,
         &render_state.color_model,
         state.transparency_settings.filled_alpha(),
+        state.cursor_line.borrow().as_ref(),
+        &*state.color_columns.borrow(),
     );
+    if let Some((start, end)) = state.selection {
+        render::draw_selection(
+            ctx,
+            render_state.font_ctx.cell_metrics(),
+            &render_state.color_model,
+            start.min(end),
+            start.max(end),
+        );
+    }
     render::fill_background(
         ctx,
         &render_state.color_model,
@@ -1113,15 +2600,86 @@ fn draw_content(state: &State, ctx: &cairo::Context) {
     );
 
     ctx.pop_group_to_source();
+    ctx.set_operator(operator);
     ctx.paint();
 }
 
+fn draw_grid(state: &State, ctx: &cairo::Context) {
+    let alloc = state.drawing_area.get_allocation();
+    let overscroll = state.overscroll.borrow().offset();
+    if overscroll != 0.0 {
+        // strip uncovered by moved grid
+        let bg = &state.render_state.borrow().color_model.bg_color;
+        ctx.set_source_rgb(bg.0, bg.1, bg.2);
+        ctx.paint();
+        ctx.save();
+        ctx.translate(0.0, overscroll);
+    }
+    state
+        .grid_surface
+        .borrow_mut()
+        .draw(ctx, alloc.width, alloc.height, |surface_ctx| {
+            draw_content(state, surface_ctx, cairo::Operator::Source)
+        });
+    if overscroll != 0.0 {
+        ctx.restore();
+    }
+
+    // overlays are not part of grid surface, so painted on every draw
+    let render_state = state.render_state.borrow();
+    if state.hit_enter {
+        ctx.save();
+        ctx.translate(f64::from(state.padding.left), f64::from(state.padding.top));
+        let cell_metrics = render_state.font_ctx.cell_metrics();
+        let width = f64::from(alloc.width - state.padding.left - state.padding.right);
+        render::draw_hit_enter(
+            ctx,
+            cell_metrics,
+            &render_state.color_model,
+            width,
+            state.model.rows.saturating_sub(1),
+        );
+        ctx.restore();
+    }
+
+    if state.nowrap_indicator {
+        if let Some(ref window) = *state.nowrap_window.borrow() {
+            ctx.save();
+            ctx.translate(f64::from(state.padding.left), f64::from(state.padding.top));
+            render::draw_nowrap_marks(
+                ctx,
+                render_state.font_ctx.cell_metrics(),
+                &render_state.color_model,
+                window,
+                &window.truncated_rows(&state.model),
+            );
+            ctx.restore();
+        }
+    }
+
+    if !state.focused && state.dim_inactive > 0 {
+        render::draw_dim(ctx, &render_state.color_model, state.dim_inactive);
+    }
+}
+
 fn gtk_draw(state_arc: &Arc<UiMutex<State>>, ctx: &cairo::Context) -> Inhibit {
     let state = state_arc.borrow();
-    if state.nvim.is_initialized() {
-        draw_content(&*state, ctx);
+    if state.nvim.is_initialized() || (state.nvim.is_initializing() && state.redraw_applied) {
+        if state.is_too_small() {
+            let alloc = state.drawing_area.get_allocation();
+            render::draw_too_small(
+                ctx,
+                &state.render_state.borrow().color_model,
+                f64::from(alloc.width),
+                f64::from(alloc.height),
+            );
+        } else {
+            draw_grid(&*state, ctx);
+        }
     } else if state.nvim.is_initializing() {
-        draw_initializing(&*state, ctx);
+        draw_splash(&*state, ctx, state.init_started.map(|started| started.elapsed()));
+    } else {
+        draw_splash(&*state, ctx, None);
     }
 
     Inhibit(false)
@@ -1161,6 +2719,15 @@ fn init_nvim_async(
     cols: usize,
     rows: usize,
 ) {
+    // address given by user in nvim args is used as is
+    let server_address = if options.args_for_neovim.iter().any(|arg| arg == "--listen") {
+        None
+    } else {
+        nvim::new_server_address()
+            .map_err(|e| warn!("Can't create server address: {}", e))
+            .ok()
+    };
+
     // execute nvim
     let nvim = match nvim::start(
         nvim_handler,
@@ -1168,6 +2735,7 @@ fn init_nvim_async(
         options.timeout,
         options.args_for_neovim,
         options.enable_swap,
+        server_address.as_ref().map(String::as_str),
     ) {
         Ok(nvim) => nvim,
         Err(err) => {
@@ -1181,8 +2749,16 @@ fn init_nvim_async(
     // add callback on session end
     let guard = nvim.borrow().unwrap().session.take_dispatch_guard();
     let state_ref = state_arc.clone();
+    let socket = server_address.clone();
     thread::spawn(move || {
-        guard.join().expect("Can't join dispatch thread");
+        // panic is already reported by crash handler
+        if guard.join().is_err() {
+            error!("Nvim dispatch thread panicked");
+        }
+
+        if let Some(ref socket) = socket {
+            nvim::remove_server_socket(socket);
+        }
 
         glib::idle_add(move || {
             state_ref.borrow().nvim.clear();
@@ -1195,16 +2771,22 @@ fn init_nvim_async(
     });
 
     // attach ui
-    if let Err(err) = nvim::post_start_init(
+    match nvim::post_start_init(
         nvim,
         options.open_paths,
         cols as u64,
         rows as u64,
         options.input_data,
     ) {
-        show_nvim_init_error(&err, state_arc.clone());
-    } else {
-        set_nvim_initialized(state_arc);
+        Ok(dropped_options) => {
+            if options.print_server_address {
+                if let Some(ref address) = server_address {
+                    println!("{}", address);
+                }
+            }
+            set_nvim_initialized(state_arc, dropped_options)
+        }
+        Err(err) => show_nvim_init_error(&err, state_arc.clone()),
     }
 }
 
@@ -1234,15 +2816,19 @@ fn set_nvim_to_state(state_arc: Arc<UiMutex<State>>, nvim: Neovim) -> NeovimClie
     started.take().unwrap()
 }
 
-fn set_nvim_initialized(state_arc: Arc<UiMutex<State>>) {
+fn set_nvim_initialized(state_arc: Arc<UiMutex<State>>, dropped_options: Vec<String>) {
+    let mut dropped_options = Some(dropped_options);
     glib::idle_add(clone!(state_arc => move || {
         let mut state = state_arc.borrow_mut();
         state.nvim.async_to_sync();
         state.nvim.set_initialized();
+        state.set_dropped_ui_options(dropped_options.take().unwrap());
         // in some case resize can happens while initilization in progress
         // so force resize here
         state.try_nvim_resize();
         state.cursor.as_mut().unwrap().start();
+        drop(state);
+        State::load_color_map(&state_arc);
 
         Continue(false)
     }));
@@ -1250,11 +2836,15 @@ fn set_nvim_initialized(state_arc: Arc<UiMutex<State>>) {
     idle_cb_call!(state_arc.nvim_started_cb());
 }
 
-fn draw_initializing(state: &State, ctx: &cairo::Context) {
+/// Splash is drawn without nvim, so it is also shown behind error page
+///
+/// `elapsed` is initialization time, spinner hidden when not set
+fn draw_splash(state: &State, ctx: &cairo::Context, elapsed: Option<Duration>) {
     let render_state = state.render_state.borrow();
     let color_model = &render_state.color_model;
-    let layout = pangocairo::functions::create_layout(ctx).unwrap();
     let alloc = state.drawing_area.get_allocation();
+    let center_x = alloc.width as f64 / 2.0;
+    let center_y = alloc.height as f64 / 2.0;
 
     ctx.set_source_rgb(
         color_model.bg_color.0,
@@ -1263,57 +2853,208 @@ fn draw_initializing(state: &State, ctx: &cairo::Context) {
     );
     ctx.paint();
 
-    layout.set_text("Loading->");
-    let (width, height) = layout.get_pixel_size();
-
-    let x = alloc.width as f64 / 2.0 - width as f64 / 2.0;
-    let y = alloc.height as f64 / 2.0 - height as f64 / 2.0;
+    let fg = &color_model.fg_color;
+    ctx.set_source_rgb(fg.0, fg.1, fg.2);
 
-    ctx.move_to(x, y);
-    ctx.set_source_rgb(
-        color_model.fg_color.0,
-        color_model.fg_color.1,
-        color_model.fg_color.2,
-    );
+    let layout = pangocairo::functions::create_layout(ctx).unwrap();
+    layout.set_markup("<big><b>NeovimGtk</b></big>");
+    let (width, height) = layout.get_pixel_size();
+    ctx.move_to(center_x - width as f64 / 2.0, center_y - height as f64 * 2.0);
     pangocairo::functions::update_layout(ctx, &layout);
     pangocairo::functions::show_layout(ctx, &layout);
 
-    ctx.move_to(x + width as f64, y);
-    state
-        .cursor
-        .as_ref()
-        .unwrap()
-        .draw(ctx, &render_state.font_ctx, y, false, &color_model);
+    if let Some(elapsed) = elapsed {
+        let millis = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        draw_spinner(ctx, fg, center_x, center_y, millis);
+
+        layout.set_text(&format!("{:.1}s", millis as f64 / 1000.0));
+        let (width, height) = layout.get_pixel_size();
+        ctx.set_source_rgb(fg.0, fg.1, fg.2);
+        ctx.move_to(center_x - width as f64 / 2.0, center_y + height as f64);
+        pangocairo::functions::update_layout(ctx, &layout);
+        pangocairo::functions::show_layout(ctx, &layout);
+    }
+}
+
+fn draw_spinner(ctx: &cairo::Context, color: &Color, x: f64, y: f64, millis: u64) {
+    const SEGMENTS: u64 = 8;
+    const RADIUS: f64 = 10.0;
+
+    let active = millis / 100 % SEGMENTS;
+
+    ctx.set_line_width(2.0);
+    for i in 0..SEGMENTS {
+        let angle = i as f64 * 2.0 * PI / SEGMENTS as f64;
+        let alpha = 1.0 - ((SEGMENTS + active - i) % SEGMENTS) as f64 / SEGMENTS as f64;
+
+        ctx.set_source_rgba(color.0, color.1, color.2, alpha);
+        ctx.move_to(x + angle.cos() * RADIUS / 2.0, y + angle.sin() * RADIUS / 2.0);
+        ctx.line_to(x + angle.cos() * RADIUS, y + angle.sin() * RADIUS);
+        ctx.stroke();
+    }
+}
+
+fn start_splash_updates(state_arc: &Arc<UiMutex<State>>) {
+    let state_arc = state_arc.clone();
+    gtk::timeout_add(SPLASH_UPDATE_INTERVAL_MS, move || {
+        let state = state_arc.borrow();
+
+        if !state.nvim.is_initializing() || state.redraw_applied {
+            state.slow_init_bar.hide();
+            return Continue(false);
+        }
+
+        state.queue_draw_all();
+
+        let slow = state
+            .init_started
+            .map(|started| started.elapsed() > SLOW_INIT_THRESHOLD)
+            .unwrap_or(false);
+        if slow && !state.slow_init_bar.is_visible() {
+            state.slow_init_bar.show_all();
+        }
+
+        Continue(true)
+    });
+}
+
+fn init_slow_init_bar(state_arc: &Arc<UiMutex<State>>) {
+    let state = state_arc.borrow();
+    let bar = &state.slow_init_bar;
+
+    bar.set_message_type(gtk::MessageType::Warning);
+    bar.set_no_show_all(true);
+    if let Some(content) = bar
+        .get_content_area()
+        .and_then(|w| w.downcast::<gtk::Container>().ok())
+    {
+        content.add(&gtk::Label::new(Some(
+            "Nvim initialization takes too long, configuration can be skipped",
+        )));
+    }
+
+    const SKIP_CONFIG_ID: i32 = 0;
+    bar.add_button("Start with -u NONE", SKIP_CONFIG_ID);
+
+    let state_ref = state_arc.clone();
+    bar.connect_response(move |_, response| {
+        if response == SKIP_CONFIG_ID {
+            restart_without_config(&*state_ref.borrow());
+        }
+    });
+}
+
+/// Start new nvim-gtk process with same arguments and `-u NONE`,
+/// current nvim may be blocked, so this window is just closed
+fn restart_without_config(state: &State) {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == "--") {
+        args.push("--".to_owned());
+    }
+    args.push("-u".to_owned());
+    args.push("NONE".to_owned());
+
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            error!("Can't find nvim-gtk executable: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = Command::new(exe).args(&args).spawn() {
+        error!("Can't start nvim-gtk: {}", e);
+        return;
+    }
+
+    if let Some(window) = state
+        .drawing_area
+        .get_toplevel()
+        .and_then(|w| w.downcast::<gtk::Window>().ok())
+    {
+        window.close();
+    }
 }
 
 fn init_nvim(state_ref: &Arc<UiMutex<State>>) {
     let mut state = state_ref.borrow_mut();
     if state.start_nvim_initialization() {
-        let (cols, rows) = state.calc_nvim_size();
+        let (cols, rows) = match state.calc_nvim_size() {
+            (0, _) | (_, 0) => DEFAULT_GRID_SIZE,
+            size => render::clamp_grid_size(size),
+        };
 
         debug!("Init nvim {}/{}", cols, rows);
 
         state.model = UiModel::new(rows as u64, cols as u64);
+        // nvim is attached with this size, no need to resize it after allocation
+        state.resize_request = (rows as i64, cols as i64);
 
         let state_arc = state_ref.clone();
-        let nvim_handler = NvimHandler::new(state_ref.clone());
+        let mut nvim_handler = NvimHandler::new(state_ref.clone());
         let options = state.options.take();
+
+        if let Some(ref path) = options.replay_events {
+            start_replay(&mut *state, path, nvim_handler, options.replay_max_speed);
+            return;
+        }
+
+        if let Some(ref path) = options.record_events {
+            match EventRecorder::new(path, cols as u64, rows as u64, nvim::ui_attach_options()) {
+                Ok(recorder) => nvim_handler.set_recorder(recorder),
+                Err(err) => error!("{}", err),
+            }
+        }
+
         thread::spawn(move || init_nvim_async(state_arc, nvim_handler, options, cols, rows));
+        start_splash_updates(state_ref);
+    }
+}
+
+/// Start ui without nvim instance and feed it with recorded redraw events
+fn start_replay(state: &mut State, path: &str, nvim_handler: NvimHandler, max_speed: bool) {
+    match EventReplay::open(path) {
+        Ok(replay) => {
+            state.model = UiModel::new(replay.rows, replay.cols);
+            state.nvim.set_initialized();
+            replay.start(nvim_handler, max_speed);
+        }
+        Err(err) => {
+            state.nvim.set_error();
+            state.error_area.show_nvim_init_error(&err);
+            state.show_error_area();
+        }
     }
 }
 
 // Neovim redraw events
 impl State {
     pub fn redraw_handler_finish(&mut self) {
+        if !self.redraw_applied {
+            self.redraw_applied = true;
+            if let Some(started) = self.init_started {
+                info!("First paint in {:?}", started.elapsed());
+            }
+            self.queue_draw_all();
+        }
+
         if self.update_im_location {
             self.set_im_location();
+            self.notify_cursor_rect();
             self.update_im_location = false;
         }
+
+        self.accessible.update(&self.model);
     }
 
     pub fn on_cursor_goto(&mut self, row: u64, col: u64) -> RepaintMode {
         let repaint_area = self.model.set_cursor(row as usize, col as usize);
         self.update_im_location = true;
+
+        if self.cursor.as_mut().unwrap().move_to(row as usize, col as usize) {
+            self.start_cursor_frame_updates();
+        }
+
         RepaintMode::AreaList(repaint_area)
     }
 
@@ -1337,7 +3078,7 @@ impl State {
         debug!("on_resize {}/{}", columns, rows);
 
         if self.model.columns != columns as usize || self.model.rows != rows as usize {
-            self.model = UiModel::new(rows, columns);
+            self.model.resize(rows, columns);
         }
 
         if let Some(mut nvim) = self.nvim.nvim() {
@@ -1347,11 +3088,31 @@ impl State {
         RepaintMode::Nothing
     }
 
+    /// Join repaint with pending one, return true in case flush must be scheduled
+    pub fn queue_repaint(&mut self, mode: RepaintMode) -> bool {
+        let scheduled = self.pending_repaint.is_some();
+        self.pending_repaint = Some(match self.pending_repaint.take() {
+            Some(pending) => pending.join(mode),
+            None => mode,
+        });
+        !scheduled
+    }
+
+    pub fn take_pending_repaint(&mut self) -> Option<RepaintMode> {
+        self.pending_repaint.take()
+    }
+
+    pub fn flush_repaint(&mut self) {
+        if let Some(mode) = self.pending_repaint.take() {
+            self.on_redraw(&mode);
+        }
+    }
+
     pub fn on_redraw(&mut self, mode: &RepaintMode) {
         match *mode {
             RepaintMode::All => {
                 self.update_dirty_glyphs();
-                self.drawing_area.queue_draw();
+                self.queue_draw_all();
             }
             RepaintMode::Area(ref rect) => self.queue_draw_area(&[rect]),
             RepaintMode::AreaList(ref list) => self.queue_draw_area(&list.list),
@@ -1371,7 +3132,13 @@ impl State {
     }
 
     pub fn on_scroll(&mut self, count: i64) -> RepaintMode {
-        RepaintMode::Area(self.model.scroll(count))
+        match self.blit_scroll(count) {
+            Some(rows) => {
+                self.model.scroll(count);
+                RepaintMode::AreaList(rows)
+            }
+            None => RepaintMode::Area(self.model.scroll(count)),
+        }
     }
 
     pub fn on_highlight_set(&mut self, attrs: HashMap<String, Value>) -> RepaintMode {
@@ -1382,36 +3149,60 @@ impl State {
     }
 
     pub fn on_update_bg(&mut self, bg: i64) -> RepaintMode {
-        let mut render_state = self.render_state.borrow_mut();
-        if bg >= 0 {
-            render_state.color_model.bg_color = Color::from_indexed_color(bg as u64);
-        } else {
-            render_state.color_model.bg_color = COLOR_BLACK;
-        }
-        RepaintMode::Nothing
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .color_model
+            .set_default_bg(Color::from_rgb_value(bg));
+        self.on_default_colors_changed(changed)
     }
 
     pub fn on_update_fg(&mut self, fg: i64) -> RepaintMode {
-        let mut render_state = self.render_state.borrow_mut();
-        if fg >= 0 {
-            render_state.color_model.fg_color = Color::from_indexed_color(fg as u64);
-        } else {
-            render_state.color_model.fg_color = COLOR_WHITE;
-        }
-        RepaintMode::Nothing
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .color_model
+            .set_default_fg(Color::from_rgb_value(fg));
+        self.on_default_colors_changed(changed)
     }
 
     pub fn on_update_sp(&mut self, sp: i64) -> RepaintMode {
-        let mut render_state = self.render_state.borrow_mut();
-        if sp >= 0 {
-            render_state.color_model.sp_color = Color::from_indexed_color(sp as u64);
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .color_model
+            .set_default_sp(Color::from_rgb_value(sp));
+        self.on_default_colors_changed(changed)
+    }
+
+    pub fn on_default_colors_set(&mut self, colors: DefaultColors) -> RepaintMode {
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .color_model
+            .set_default_colors(colors);
+        self.on_default_colors_changed(changed)
+    }
+
+    /// Cells with default colors and padding must be repainted,
+    /// glyphs are shaped with colors so they must be rebuilt too
+    fn on_default_colors_changed(&mut self, changed: bool) -> RepaintMode {
+        if changed {
+            self.model.clear_glyphs();
+            RepaintMode::All
         } else {
-            render_state.color_model.sp_color = COLOR_RED;
+            RepaintMode::Nothing
         }
-        RepaintMode::Nothing
     }
 
     pub fn on_mode_change(&mut self, mode: String, idx: u64) -> RepaintMode {
+        // hit-enter and more prompts use "more" cursor shape
+        let hit_enter = mode.starts_with("more");
+        if self.hit_enter != hit_enter {
+            self.hit_enter = hit_enter;
+            self.drawing_area.queue_draw();
+        }
+
         let mut render_state = self.render_state.borrow_mut();
         render_state.mode.update(&mode, idx as usize);
         self.cursor
@@ -1446,7 +3237,8 @@ impl State {
     ) -> RepaintMode {
         let point = ModelRect::point(col as usize, row as usize);
         let render_state = self.render_state.borrow();
-        let (x, y, width, height) = point.to_area(render_state.font_ctx.cell_metrics());
+        let (x, y, width, height) =
+            self.padded_area(point.to_area(render_state.font_ctx.cell_metrics()));
 
         let context = popup_menu::PopupMenuContext {
             nvim: &self.nvim,
@@ -1489,36 +3281,99 @@ impl State {
     pub fn option_set(&mut self, name: String, val: Value) -> RepaintMode {
         match name.as_str() {
             "guifont" => self.set_font_from_value(val),
+            "guifontwide" => self.set_font_wide_from_value(val),
+            "ambiwidth" => {
+                let mut cell_widths = *self.render_state.borrow().font_ctx.cell_widths();
+                cell_widths.ambiwidth_double = val.as_str() == Some("double");
+                self.set_cell_widths(cell_widths);
+            }
+            "emoji" => {
+                let mut cell_widths = *self.render_state.borrow().font_ctx.cell_widths();
+                cell_widths.emoji = val.as_bool().unwrap_or(true);
+                self.set_cell_widths(cell_widths);
+            }
+            "termguicolors" => self.check_termguicolors(val.as_bool().unwrap_or(false)),
+            "showtabline" => self.tabs.set_show_tabline(val.as_u64().unwrap_or(1)),
             _ => (),
         };
         RepaintMode::Nothing
     }
 
+    /// nvim decides how many cells char takes, glyphs must be drawn the same way
+    fn set_cell_widths(&mut self, cell_widths: render::CellWidths) {
+        let changed = self
+            .render_state
+            .borrow_mut()
+            .font_ctx
+            .update_cell_widths(cell_widths);
+        if changed {
+            self.model.clear_glyphs();
+            self.on_redraw(&RepaintMode::All);
+        }
+    }
+
+    /// UI is attached with rgb colors, so colorschemes must use gui colors too.
+    /// Option is set once on startup, later changes are only reported
+    fn check_termguicolors(&mut self, termguicolors: bool) {
+        if termguicolors {
+            self.termguicolors_checked = true;
+            return;
+        }
+
+        if !self.termguicolors_checked {
+            self.termguicolors_checked = true;
+            if let Some(mut nvim) = self.nvim() {
+                nvim.command_async("set termguicolors")
+                    .cb(|r| r.report_err())
+                    .call();
+            }
+        } else {
+            warn!("'notermguicolors' has no effect, colors are always RGB");
+        }
+    }
+
     fn set_font_from_value(&mut self, val: Value) {
         if let Value::String(val) = val {
             if let Some(val) = val.into_str() {
                 if !val.is_empty() {
                     let exists_fonts = self.render_state.borrow().font_ctx.font_families();
-                    let fonts = split_at_comma(&val);
-                    for font in &fonts {
-                        let desc = FontDescription::from_string(&font);
-                        if desc.get_size() > 0
-                            && exists_fonts.contains(&desc.get_family().unwrap_or("".to_owned()))
-                        {
-                            self.set_font_rpc(font);
+                    let fonts = parse_guifont(&val);
+                    let font = fonts
+                        .iter()
+                        .find(|font| {
+                            let desc = FontDescription::from_string(&font.font);
+                            desc.get_size() > 0
+                                && exists_fonts
+                                    .contains(&desc.get_family().unwrap_or("".to_owned()))
+                        })
+                        // font does not exists? set first one
+                        .or_else(|| fonts.first());
+
+                    if let Some(font) = font {
+                        if !self.set_font_rpc(&font.font) {
                             return;
                         }
-                    }
-
-                    // font does not exists? set first one
-                    if !fonts.is_empty() {
-                        self.set_font_rpc(&fonts[0]);
+                        self.set_font_features(font.features_to_apply());
                     }
                 }
             }
         }
     }
 
+    fn set_font_wide_from_value(&mut self, val: Value) {
+        if let Some(val) = val.as_str() {
+            let fonts = parse_guifont(val);
+            let font = fonts.first().map_or("", |font| font.font.as_str());
+
+            self.render_state
+                .borrow_mut()
+                .font_ctx
+                .update_font_wide(font);
+            self.model.clear_glyphs();
+            self.on_redraw(&RepaintMode::All);
+        }
+    }
+
     pub fn mode_info_set(
         &mut self,
         cursor_style_enabled: bool,
@@ -1556,7 +3411,8 @@ impl State {
         {
             let cursor = self.model.cur_point();
             let render_state = self.render_state.borrow();
-            let (x, y, width, height) = cursor.to_area(render_state.font_ctx.cell_metrics());
+            let (x, y, width, height) =
+                self.padded_area(cursor.to_area(render_state.font_ctx.cell_metrics()));
             let ctx = CmdLineContext {
                 nvim: &self.nvim,
                 content,
@@ -7,16 +7,60 @@ use gtk;
 use gtk::prelude::*;
 use gtk::{MessageDialog, MessageType, ButtonsType};
 
-pub fn can_close_window(comps: &UiMutex<Components>, shell: &RefCell<Shell>) -> bool {
-    let shell = shell.borrow();
-    match get_changed_buffers(&*shell) {
-        Ok(vec) => {
-            if !vec.is_empty() {
-                show_not_saved_dlg(comps, &*shell, &vec)
-            } else {
-                true
+/// Answer of "save changes" dialog
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SaveAnswer {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Source of modified buffers that can save them
+trait ChangedBuffers {
+    fn changed_buffers(&self) -> Result<Vec<String>, CallError>;
+    fn save_all(&self) -> bool;
+}
+
+impl ChangedBuffers for Shell {
+    fn changed_buffers(&self) -> Result<Vec<String>, CallError> {
+        get_changed_buffers(self)
+    }
+
+    fn save_all(&self) -> bool {
+        let state = self.state.borrow();
+        let mut nvim = match state.nvim() {
+            Some(nvim) => nvim,
+            None => return true,
+        };
+        match nvim.command("wa") {
+            Err(ref err) => {
+                error!("Error: {}", err);
+                false
             }
+            _ => true,
         }
+    }
+}
+
+pub fn can_close_window(comps: &UiMutex<Components>, shell: &RefCell<Shell>) -> bool {
+    confirm_close(shell, |changed_bufs| show_not_saved_dlg(comps, changed_bufs))
+}
+
+/// Dialog runs nested main loop, it can dispatch redraw or settings events
+/// that borrow shell mutably, so shell is not borrowed while user answers
+fn confirm_close<T, F>(target: &RefCell<T>, ask: F) -> bool
+where
+    T: ChangedBuffers,
+    F: FnOnce(&[String]) -> SaveAnswer,
+{
+    let changed_bufs = target.borrow().changed_buffers();
+    match changed_bufs {
+        Ok(ref changed_bufs) if changed_bufs.is_empty() => true,
+        Ok(changed_bufs) => match ask(&changed_bufs) {
+            SaveAnswer::Save => target.borrow().save_all(),
+            SaveAnswer::Discard => true,
+            SaveAnswer::Cancel => false,
+        },
         Err(ref err) => {
             error!("Error getting info from nvim: {}", err);
             true
@@ -24,7 +68,7 @@ pub fn can_close_window(comps: &UiMutex<Components>, shell: &RefCell<Shell>) ->
     }
 }
 
-fn show_not_saved_dlg(comps: &UiMutex<Components>, shell: &Shell, changed_bufs: &[String]) -> bool {
+fn show_not_saved_dlg(comps: &UiMutex<Components>, changed_bufs: &[String]) -> SaveAnswer {
     let mut changed_files = changed_bufs
         .iter()
         .map(|n| if n.is_empty() { "<No name>" } else { n })
@@ -53,19 +97,9 @@ fn show_not_saved_dlg(comps: &UiMutex<Components>, shell: &Shell, changed_bufs:
     );
 
     let res = match dlg.run() {
-        SAVE_ID => {
-            let state = shell.state.borrow();
-            let mut nvim = state.nvim().unwrap();
-            match nvim.command("wa") {
-                Err(ref err) => {
-                    error!("Error: {}", err);
-                    false
-                }
-                _ => true,
-            }
-        }
-        CLOSE_WITHOUT_SAVE => true,
-        CANCEL_ID | _ => false,
+        SAVE_ID => SaveAnswer::Save,
+        CLOSE_WITHOUT_SAVE => SaveAnswer::Discard,
+        CANCEL_ID | _ => SaveAnswer::Cancel,
     };
 
     dlg.destroy();
@@ -118,3 +152,48 @@ fn get_changed_buffers(shell: &Shell) -> Result<Vec<String>, CallError> {
         Ok(vec![])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct Buffers {
+        changed: Vec<String>,
+        saved: Cell<bool>,
+        redraws: usize,
+    }
+
+    impl ChangedBuffers for Buffers {
+        fn changed_buffers(&self) -> Result<Vec<String>, CallError> {
+            Ok(self.changed.clone())
+        }
+
+        fn save_all(&self) -> bool {
+            self.saved.set(true);
+            true
+        }
+    }
+
+    #[test]
+    fn test_confirm_close_reentrant() {
+        let buffers = RefCell::new(Buffers {
+            changed: vec!["a.rs".to_owned()],
+            saved: Cell::new(false),
+            redraws: 0,
+        });
+
+        // redraw dispatched by dialog main loop borrows state mutably
+        let closed = confirm_close(&buffers, |changed_bufs| {
+            assert_eq!(["a.rs".to_owned()], changed_bufs);
+            buffers.borrow_mut().redraws += 1;
+            SaveAnswer::Save
+        });
+
+        assert!(closed);
+        assert!(buffers.borrow().saved.get());
+        assert_eq!(1, buffers.borrow().redraws);
+
+        assert!(!confirm_close(&buffers, |_| SaveAnswer::Cancel));
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::Deref;
+
+use gtk;
+use gtk::prelude::*;
+use pango;
+
+/// Expressions evaluated by status bar subscription, in `update` arguments order
+pub const STATUS_ARGS: &[&str] = &[
+    "line('.')",
+    "col('.')",
+    "&fileencoding != '' ? &fileencoding : &encoding",
+    "expand('%:t')",
+    "&modified",
+];
+
+/// Cursor position and current file info below grid
+pub struct StatusBar {
+    container: gtk::Box,
+    file: gtk::Label,
+    encoding: gtk::Label,
+    position: gtk::Label,
+    counts: gtk::Label,
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        container.set_border_width(2);
+
+        let file = gtk::Label::new(None);
+        file.set_halign(gtk::Align::Start);
+        file.set_ellipsize(pango::EllipsizeMode::Start);
+        container.pack_start(&file, true, true, 6);
+
+        let position = gtk::Label::new(None);
+        container.pack_end(&position, false, false, 6);
+
+        let encoding = gtk::Label::new(None);
+        container.pack_end(&encoding, false, false, 0);
+
+        let counts = gtk::Label::new(None);
+        container.pack_end(&counts, false, false, 6);
+
+        StatusBar {
+            container,
+            file,
+            encoding,
+            position,
+            counts,
+        }
+    }
+
+    pub fn set_counts(&self, words: usize, chars: usize) {
+        self.counts.set_text(&format!("{} words, {} chars", words, chars));
+    }
+
+    pub fn update(&self, args: &[String]) {
+        self.position.set_text(&format!("[{}, {}]", args[0], args[1]));
+        self.encoding.set_text(&format!("[{}]", args[2]));
+        self.file.set_text(&file_label(&args[3], args[4] == "1"));
+    }
+}
+
+pub fn file_label(name: &str, modified: bool) -> String {
+    let name = if name.is_empty() { "[No Name]" } else { name };
+
+    if modified {
+        format!("{} [+]", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+impl Deref for StatusBar {
+    type Target = gtk::Box;
+
+    fn deref(&self) -> &gtk::Box {
+        &self.container
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_label() {
+        assert_eq!("[No Name]", file_label("", false));
+        assert_eq!("main.rs [+]", file_label("main.rs", true));
+    }
+}
@@ -47,7 +47,7 @@ pub struct Subscriptions(HashMap<SubscriptionKey, Vec<Subscription>>);
 /// Can be used to trigger the subscription manually even when the event was not triggered.
 ///
 /// Could be used in the future to suspend individual subscriptions.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SubscriptionHandle {
     key: SubscriptionKey,
     index: usize,
@@ -106,10 +106,10 @@ impl Subscriptions {
         SubscriptionHandle { key, index }
     }
 
-    /// Register all subscriptions with Neovim.
+    /// Register all subscriptions with Neovim, notifications are sent to `channel_id`.
     ///
     /// This function is wrapped by `shell::State`.
-    pub fn set_autocmds(&self, nvim: &mut NeovimRef) {
+    pub fn set_autocmds(&self, nvim: &mut NeovimRef, channel_id: u64) {
         for (key, subscriptions) in &self.0 {
             let SubscriptionKey {
                 event_name,
@@ -121,8 +121,8 @@ impl Subscriptions {
                     .iter()
                     .fold("".to_owned(), |acc, arg| acc + ", " + &arg);
                 let autocmd = format!(
-                    "autocmd {} {} call rpcnotify(1, 'subscription', '{}', '{}', {} {})",
-                    event_name, pattern, event_name, pattern, i, args,
+                    "autocmd {} {} call rpcnotify({}, 'subscription', '{}', '{}', {} {})",
+                    event_name, pattern, channel_id, event_name, pattern, i, args,
                 );
                 nvim.command_async(&autocmd).cb(|r| r.report_err()).call();
             }
@@ -159,17 +159,26 @@ impl Subscriptions {
             .next()
             .and_then(|i| i.as_u64())
             .ok_or("Error reading index")? as usize;
-        let args = params_iter
-            .map(|arg| {
-                arg.as_str()
-                    .map(str::to_owned)
-                    .or_else(|| arg.as_u64().map(|uint| uint.to_string()))
-            }).collect::<Option<Vec<String>>>()
-            .ok_or("Error reading args")?;
+        let args = args_to_strings(params_iter).ok_or("Error reading args")?;
         self.on_notify(&key, index, args);
         Ok(())
     }
 
+    /// Expression that evaluates all `args` of given subscription to a list.
+    ///
+    /// Used to trigger subscription asynchronously with `notify_values`.
+    pub fn args_expr(&self, handle: &SubscriptionHandle) -> String {
+        let subscription = &self.0.get(&handle.key).unwrap()[handle.index];
+        format!("[{}]", subscription.args.join(", "))
+    }
+
+    /// Trigger the given subscription with values of `args_expr` expression.
+    pub fn notify_values(&self, handle: &SubscriptionHandle, values: Vec<Value>) -> Result<(), String> {
+        let args = args_to_strings(values.into_iter()).ok_or("Error reading args")?;
+        self.on_notify(&handle.key, handle.index, args);
+        Ok(())
+    }
+
     /// Manually trigger the given subscription.
     ///
     /// The `nvim` instance is needed to evaluate the `args` expressions.
@@ -195,3 +204,11 @@ impl Subscriptions {
         }
     }
 }
+
+fn args_to_strings<I: Iterator<Item = Value>>(args: I) -> Option<Vec<String>> {
+    args.map(|arg| {
+        arg.as_str()
+            .map(str::to_owned)
+            .or_else(|| arg.as_u64().map(|uint| uint.to_string()))
+    }).collect()
+}
@@ -1,7 +1,10 @@
 use std::ops::Deref;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
+use gdk;
 use gtk;
 use gtk::prelude::*;
 
@@ -10,16 +13,69 @@ use glib::signal;
 
 use pango;
 
-use neovim_lib::{NeovimApi, NeovimApiAsync};
+use neovim_lib::{NeovimApiAsync, Value};
 use neovim_lib::neovim_api::Tabpage;
 
+use misc;
 use nvim;
 use nvim::ErrorReport;
+use status_bar;
+use throttle::Throttle;
+use ui::UiMutex;
+
+/// Minimal interval between tabs widget updates
+const UPDATE_INTERVAL_MS: u32 = 50;
+
+/// Tab tooltips loaded on hover, valid until next tabs update
+struct Tooltips {
+    /// Changed on every tabs update, so replies about old tabs are dropped
+    generation: u64,
+    /// Tooltip by page index, None in case tab info can't be loaded
+    cache: HashMap<u32, Option<String>>,
+    pending: HashSet<u32>,
+}
+
+impl Tooltips {
+    fn new() -> Self {
+        Tooltips {
+            generation: 0,
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.generation += 1;
+        self.cache.clear();
+        self.pending.clear();
+    }
+
+    /// Return true in case tooltip is still actual and must be shown
+    fn loaded(&mut self, generation: u64, idx: u32, text: Option<String>) -> bool {
+        if generation != self.generation {
+            return false;
+        }
+
+        self.pending.remove(&idx);
+        let has_text = text.is_some();
+        self.cache.insert(idx, text);
+        has_text
+    }
+}
 
 struct State {
     data: Vec<Tabpage>,
     selected: Option<Tabpage>,
     nvim: Option<Rc<nvim::NeovimClient>>,
+    switch_handler_id: Option<glib::SignalHandlerId>,
+    tooltips: Arc<UiMutex<Tooltips>>,
+
+    enabled: bool,
+    hide_in_fullscreen: bool,
+    fullscreen: bool,
+    tabs_count: usize,
+    /// Value of nvim 'showtabline' option
+    show_tabline: u64,
 }
 
 impl State {
@@ -28,6 +84,14 @@ impl State {
             data: Vec::new(),
             selected: None,
             nvim: None,
+            switch_handler_id: None,
+            tooltips: Arc::new(UiMutex::new(Tooltips::new())),
+
+            enabled: true,
+            hide_in_fullscreen: false,
+            fullscreen: false,
+            tabs_count: 0,
+            show_tabline: 1,
         }
     }
 
@@ -35,7 +99,9 @@ impl State {
         let target = &self.data[idx as usize];
         if Some(target) != self.selected.as_ref() {
             if let Some(mut nvim) = self.nvim.as_ref().unwrap().nvim() {
-                nvim.set_current_tabpage(target).report_err();
+                nvim.set_current_tabpage_async(target)
+                    .cb(|r| r.report_err())
+                    .call();
             }
         }
     }
@@ -47,12 +113,64 @@ impl State {
                 .call();
         }
     }
+
+    /// Cached tooltip of tab, missing one is requested from nvim
+    /// and shown when reply comes
+    fn tooltip(&self, idx: u32) -> Option<String> {
+        let mut tooltips = self.tooltips.borrow_mut();
+        if let Some(text) = tooltips.cache.get(&idx) {
+            return text.clone();
+        }
+
+        if tooltips.pending.insert(idx) {
+            self.request_tooltip(idx, tooltips.generation);
+        }
+        None
+    }
+
+    fn request_tooltip(&self, idx: u32, generation: u64) {
+        let mut nvim = match self.nvim.as_ref().and_then(|nvim| nvim.nvim()) {
+            Some(nvim) => nvim,
+            None => return,
+        };
+
+        let tooltips = self.tooltips.clone();
+        nvim.eval_async(&tab_windows_expr(idx + 1))
+            .cb(move |res| {
+                // tab can be closed while request is in flight
+                let mut text = res
+                    .ok()
+                    .and_then(|windows| parse_tab_windows(&windows))
+                    .map(|windows| tooltip_text(&windows));
+
+                glib::idle_add(move || {
+                    if tooltips.borrow_mut().loaded(generation, idx, text.take()) {
+                        if let Some(display) = gdk::Display::get_default() {
+                            gtk::Tooltip::trigger_tooltip_query(&display);
+                        }
+                    }
+                    glib::Continue(false)
+                });
+            })
+            .call();
+    }
+
+    /// Visibility change resizes grid, so widget is touched only when it changes
+    fn update_visibility(&self, tabs: &gtk::Notebook) {
+        let hidden_by_fullscreen = self.hide_in_fullscreen && self.fullscreen;
+        let visible = self.enabled
+            && !hidden_by_fullscreen
+            && tabline_visible(self.show_tabline, self.tabs_count);
+        if tabs.get_visible() != visible {
+            tabs.set_visible(visible);
+        }
+    }
 }
 
 pub struct Tabline {
     tabs: gtk::Notebook,
     state: Rc<RefCell<State>>,
-    switch_handler_id: glib::SignalHandlerId,
+    update_throttle: Throttle<(Tabpage, Vec<(Tabpage, Option<String>)>)>,
 }
 
 impl Tabline {
@@ -64,6 +182,7 @@ impl Tabline {
         tabs.set_show_border(false);
         tabs.set_border_width(0);
         tabs.set_hexpand(true);
+        tabs.get_style_context().unwrap().add_class("nvim-tabline");
         tabs.hide();
 
         let state = Rc::new(RefCell::new(State::new()));
@@ -71,115 +190,220 @@ impl Tabline {
         let state_ref = state.clone();
         let switch_handler_id =
             tabs.connect_switch_page(move |_, _, idx| state_ref.borrow().switch_page(idx));
+        state.borrow_mut().switch_handler_id = Some(switch_handler_id);
+
+        // buffer switching plugins can send a lot of updates,
+        // so widgets are rebuilt only with latest tabs state
+        let tabs_ref = tabs.clone();
+        let state_ref = state.clone();
+        let update_throttle = Throttle::new(UPDATE_INTERVAL_MS, move |(selected, tabs)| {
+            update_tabs(&tabs_ref, &state_ref, &selected, &tabs)
+        });
 
         Tabline {
             tabs,
             state,
-            switch_handler_id,
+            update_throttle,
         }
     }
 
-    fn update_state(
-        &self,
-        nvim: &Rc<nvim::NeovimClient>,
-        selected: &Tabpage,
-        tabs: &[(Tabpage, Option<String>)],
-    ) {
+    /// Disabled tabline is never shown, but still tracks tabs
+    /// so it can be shown immediately after enabling
+    pub fn set_enabled(&mut self, enabled: bool) {
         let mut state = self.state.borrow_mut();
+        state.enabled = enabled;
+        state.update_visibility(&self.tabs);
+    }
 
-        if state.nvim.is_none() {
-            state.nvim = Some(nvim.clone());
-        }
+    pub fn set_hide_in_fullscreen(&mut self, hide_in_fullscreen: bool) {
+        let mut state = self.state.borrow_mut();
+        state.hide_in_fullscreen = hide_in_fullscreen;
+        state.update_visibility(&self.tabs);
+    }
 
-        state.selected = Some(selected.clone());
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        let mut state = self.state.borrow_mut();
+        state.fullscreen = fullscreen;
+        state.update_visibility(&self.tabs);
+    }
 
-        state.data = tabs.iter().map(|item| item.0.clone()).collect();
+    /// Apply 'showtabline': 0 never, 1 with more than one tab, 2 always.
+    /// Without ext_tabline nvim draws tabline in grid and widget is disabled anyway
+    pub fn set_show_tabline(&mut self, show_tabline: u64) {
+        let mut state = self.state.borrow_mut();
+        state.show_tabline = show_tabline;
+        state.update_visibility(&self.tabs);
     }
 
     pub fn update_tabs(
-        &self,
+        &mut self,
         nvim: &Rc<nvim::NeovimClient>,
         selected: &Tabpage,
         tabs: &[(Tabpage, Option<String>)],
     ) {
-        if tabs.len() <= 1 {
-            self.tabs.hide();
-            return;
-        } else {
-            self.tabs.show();
-        }
-
-        self.update_state(nvim, selected, tabs);
-
-
-        signal::signal_handler_block(&self.tabs, &self.switch_handler_id);
-
-        let count = self.tabs.get_n_pages() as usize;
-        if count < tabs.len() {
-            for _ in count..tabs.len() {
-                let empty = gtk::Box::new(gtk::Orientation::Vertical, 0);
-                empty.show_all();
-                let title = gtk::Label::new(None);
-                title.set_ellipsize(pango::EllipsizeMode::Middle);
-                title.set_width_chars(25);
-                let close_btn = gtk::Button::new_from_icon_name(
-                    "window-close-symbolic",
-                    gtk::IconSize::Menu.into(),
-                );
-                close_btn.set_relief(gtk::ReliefStyle::None);
-                close_btn.get_style_context().unwrap().add_class("small-button");
-                close_btn.set_focus_on_click(false);
-                let label_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
-                label_box.pack_start(&title, true, false, 0);
-                label_box.pack_start(&close_btn, false, false, 0);
-                title.show();
-                close_btn.show();
-                self.tabs.append_page(&empty, Some(&label_box));
-
-                let tabs = self.tabs.clone();
-                let state_ref = Rc::clone(&self.state);
-                close_btn.connect_clicked(move |btn| {
-                    let current_label = btn
-                        .get_parent().unwrap();
-                    for i in 0..tabs.get_n_pages() {
-                        let page = tabs.get_nth_page(Some(i)).unwrap();
-                        let label = tabs.get_tab_label(&page).unwrap();
-                        if label == current_label {
-                            state_ref.borrow().close_tab(i);
-                        }
-                    }
-                });
-            }
-        } else if count > tabs.len() {
-            for _ in tabs.len()..count {
-                self.tabs.remove_page(None);
+        {
+            let mut state = self.state.borrow_mut();
+            if state.nvim.is_none() {
+                state.nvim = Some(nvim.clone());
             }
         }
 
-        for (idx, tab) in tabs.iter().enumerate() {
-            let tab_child = self.tabs.get_nth_page(Some(idx as u32));
-            let tab_label = self.tabs
-                .get_tab_label(&tab_child.unwrap())
-                .unwrap()
-                .downcast::<gtk::Box>()
-                .unwrap()
-                .get_children()
-                .into_iter()
-                .next()
-                .unwrap()
-                .downcast::<gtk::Label>()
-                .unwrap();
-            tab_label.set_text(tab.1.as_ref().unwrap_or(&"??".to_owned()));
-
-            if *selected == tab.0 {
-                self.tabs.set_current_page(Some(idx as u32));
+        self.update_throttle
+            .update((selected.clone(), tabs.to_vec()));
+    }
+}
+
+fn update_tabs(
+    tabs_widget: &gtk::Notebook,
+    state: &Rc<RefCell<State>>,
+    selected: &Tabpage,
+    tabs: &[(Tabpage, Option<String>)],
+) {
+    {
+        let mut state = state.borrow_mut();
+        state.tooltips.borrow_mut().reset();
+        state.tabs_count = tabs.len();
+        state.update_visibility(tabs_widget);
+
+        // hidden tabline is synced on next update after it becomes visible
+        if !tabline_visible(state.show_tabline, tabs.len()) {
+            return;
+        }
+
+        state.selected = Some(selected.clone());
+        state.data = tabs.iter().map(|item| item.0.clone()).collect();
+    }
+
+    let state_ref = state.borrow();
+    let switch_handler_id = state_ref.switch_handler_id.as_ref().unwrap();
+    signal::signal_handler_block(tabs_widget, switch_handler_id);
+
+    // existing pages are reused, so only count difference is added or removed
+    let count = tabs_widget.get_n_pages() as usize;
+    if count < tabs.len() {
+        for _ in count..tabs.len() {
+            append_page(tabs_widget, state);
+        }
+    } else if count > tabs.len() {
+        for _ in tabs.len()..count {
+            tabs_widget.remove_page(None);
+        }
+    }
+
+    for (idx, tab) in tabs.iter().enumerate() {
+        let tab_child = tabs_widget.get_nth_page(Some(idx as u32));
+        let tab_label = tabs_widget
+            .get_tab_label(&tab_child.unwrap())
+            .unwrap()
+            .downcast::<gtk::Box>()
+            .unwrap()
+            .get_children()
+            .into_iter()
+            .next()
+            .unwrap()
+            .downcast::<gtk::Label>()
+            .unwrap();
+
+        let title = tab.1.as_ref().map(String::as_str).unwrap_or("??");
+        if tab_label.get_text().as_ref().map(String::as_str) != Some(title) {
+            tab_label.set_text(title);
+        }
+
+        if *selected == tab.0 && tabs_widget.get_current_page() != Some(idx as u32) {
+            tabs_widget.set_current_page(Some(idx as u32));
+        }
+    }
+
+    signal::signal_handler_unblock(tabs_widget, switch_handler_id);
+}
+
+fn append_page(tabs: &gtk::Notebook, state: &Rc<RefCell<State>>) {
+    let empty = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    empty.show_all();
+    let title = gtk::Label::new(None);
+    title.set_ellipsize(pango::EllipsizeMode::Middle);
+    title.set_width_chars(25);
+    let close_btn =
+        gtk::Button::new_from_icon_name("window-close-symbolic", gtk::IconSize::Menu.into());
+    close_btn.set_relief(gtk::ReliefStyle::None);
+    close_btn.get_style_context().unwrap().add_class("small-button");
+    close_btn.set_focus_on_click(false);
+    let label_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    label_box.pack_start(&title, true, false, 0);
+    label_box.pack_start(&close_btn, false, false, 0);
+    title.show();
+    close_btn.show();
+    label_box.set_has_tooltip(true);
+    tabs.append_page(&empty, Some(&label_box));
+
+    let tabs_ref = tabs.clone();
+    let state_ref = Rc::clone(state);
+    close_btn.connect_clicked(move |btn| {
+        let current_label = btn.get_parent().unwrap();
+        if let Some(idx) = page_index(&tabs_ref, &current_label) {
+            state_ref.borrow().close_tab(idx);
+        }
+    });
+
+    let tabs_ref = tabs.clone();
+    let state_ref = Rc::clone(state);
+    label_box.connect_query_tooltip(move |label_box, _, _, _, tooltip| {
+        let label_box = label_box.clone().upcast::<gtk::Widget>();
+        let text = page_index(&tabs_ref, &label_box).and_then(|idx| state_ref.borrow().tooltip(idx));
+
+        match text {
+            Some(text) => {
+                tooltip.set_text(Some(text.as_str()));
+                true
             }
+            None => false,
         }
+    });
+}
 
-        signal::signal_handler_unblock(&self.tabs, &self.switch_handler_id);
+fn tabline_visible(show_tabline: u64, tabs_count: usize) -> bool {
+    match show_tabline {
+        0 => false,
+        1 => tabs_count > 1,
+        _ => true,
     }
 }
 
+fn page_index(tabs: &gtk::Notebook, tab_label: &gtk::Widget) -> Option<u32> {
+    (0..tabs.get_n_pages()).find(|&i| {
+        let page = tabs.get_nth_page(Some(i)).unwrap();
+        tabs.get_tab_label(&page).as_ref() == Some(tab_label)
+    })
+}
+
+/// Buffer name and modified flag of every window in tab
+fn tab_windows_expr(tabnr: u32) -> String {
+    format!(
+        "map(map(copy(gettabinfo({})[0].windows), \
+         'getbufinfo(winbufnr(v:val))[0]'), '[v:val.name, v:val.changed]')",
+        tabnr
+    )
+}
+
+fn parse_tab_windows(windows: &Value) -> Option<Vec<(String, bool)>> {
+    windows
+        .as_array()?
+        .iter()
+        .map(|win| {
+            let win = win.as_array()?;
+            Some((win.get(0)?.as_str()?.to_owned(), win.get(1)?.as_i64()? != 0))
+        })
+        .collect()
+}
+
+fn tooltip_text(windows: &[(String, bool)]) -> String {
+    windows
+        .iter()
+        .map(|&(ref name, modified)| status_bar::file_label(&misc::shorten_home(name), modified))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 impl Deref for Tabline {
     type Target = gtk::Notebook;
 
@@ -187,3 +411,31 @@ impl Deref for Tabline {
         &self.tabs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tabline_visible() {
+        assert!(!tabline_visible(0, 3));
+        assert!(!tabline_visible(1, 1));
+        assert!(tabline_visible(1, 2));
+        assert!(tabline_visible(2, 1));
+    }
+
+    #[test]
+    fn test_parse_tab_windows() {
+        let windows = Value::from(vec![
+            Value::from(vec![Value::from("/a/mod.rs"), Value::from(1)]),
+            Value::from(vec![Value::from(""), Value::from(0)]),
+        ]);
+
+        let windows = parse_tab_windows(&windows).unwrap();
+        assert_eq!(
+            vec![("/a/mod.rs".to_owned(), true), ("".to_owned(), false)],
+            windows
+        );
+        assert_eq!("/a/mod.rs [+]\n[No Name]", tooltip_text(&windows));
+    }
+}
@@ -14,6 +14,10 @@ use value::ValueMapExt;
 struct State {
     pmenu: Pmenu,
     cursor: Cursor,
+    cursor_line: CursorLine,
+    color_column: ColorColumn,
+    chrome: Chrome,
+    changed_cb: Option<Box<Fn(&Chrome)>>,
 }
 
 impl State {
@@ -21,6 +25,10 @@ impl State {
         State {
             pmenu: Pmenu::new(),
             cursor: Cursor::new(),
+            cursor_line: CursorLine::new(),
+            color_column: ColorColumn::new(),
+            chrome: Chrome::default(),
+            changed_cb: None,
         }
     }
 }
@@ -44,11 +52,33 @@ impl Theme {
         Ref::map(self.state.borrow(), |s| &s.cursor)
     }
 
+    pub fn cursor_line(&self) -> Ref<CursorLine> {
+        Ref::map(self.state.borrow(), |s| &s.cursor_line)
+    }
+
+    pub fn color_column(&self) -> Ref<ColorColumn> {
+        Ref::map(self.state.borrow(), |s| &s.color_column)
+    }
+
+    /// Called on main thread after each group update,
+    /// colorscheme switch calls it once per group
+    pub fn set_changed_cb<F: Fn(&Chrome) + 'static>(&self, cb: F) {
+        self.state.borrow_mut().changed_cb = Some(Box::new(cb));
+    }
+
     pub fn queue_update(&self, nvim: &mut Neovim) {
         self.get_hl(nvim, "Cursor", |state, bg, _fg| {
             state.cursor.bg = bg;
         });
 
+        self.get_hl(nvim, "CursorLine", |state, bg, _fg| {
+            state.cursor_line.bg = bg;
+        });
+
+        self.get_hl(nvim, "ColorColumn", |state, bg, _fg| {
+            state.color_column.bg = bg;
+        });
+
         self.get_hl(nvim, "Pmenu", |state, bg, fg| {
             state.pmenu.bg = bg;
             state.pmenu.fg = fg;
@@ -58,6 +88,26 @@ impl Theme {
             state.pmenu.bg_sel = bg_sel;
             state.pmenu.fg_sel = fg_sel;
         });
+
+        self.get_hl(nvim, "Normal", |state, bg, fg| {
+            state.chrome.normal = HlColors { bg, fg };
+        });
+
+        self.get_hl(nvim, "TabLine", |state, bg, fg| {
+            state.chrome.tabline = HlColors { bg, fg };
+        });
+
+        self.get_hl(nvim, "TabLineSel", |state, bg, fg| {
+            state.chrome.tabline_sel = HlColors { bg, fg };
+        });
+
+        self.get_hl(nvim, "MsgArea", |state, bg, fg| {
+            state.chrome.msg_area = HlColors { bg, fg };
+        });
+
+        self.get_hl(nvim, "ErrorMsg", |state, bg, fg| {
+            state.chrome.error_msg = HlColors { bg, fg };
+        });
     }
 
     fn get_hl<CB>(&self, nvim: &mut Neovim, hl_name: &str, mut cb: CB)
@@ -71,8 +121,11 @@ impl Theme {
                 let mut hl = Some(hl_colors(v));
                 glib::idle_add(move || {
                     let (bg, fg) = hl.take().unwrap();
-                    let mut state = state.borrow_mut();
-                    cb(&mut *state, bg, fg);
+                    cb(&mut *state.borrow_mut(), bg, fg);
+                    let state = state.borrow();
+                    if let Some(ref changed_cb) = state.changed_cb {
+                        changed_cb(&state.chrome);
+                    }
                     glib::Continue(false)
                 });
             })
@@ -92,6 +145,26 @@ impl Cursor {
     }
 }
 
+pub struct CursorLine {
+    pub bg: Option<Color>,
+}
+
+impl CursorLine {
+    pub fn new() -> Self {
+        CursorLine { bg: None }
+    }
+}
+
+pub struct ColorColumn {
+    pub bg: Option<Color>,
+}
+
+impl ColorColumn {
+    pub fn new() -> Self {
+        ColorColumn { bg: None }
+    }
+}
+
 pub struct Pmenu {
     pub bg: Option<Color>,
     pub fg: Option<Color>,
@@ -110,6 +183,68 @@ impl Pmenu {
     }
 }
 
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct HlColors {
+    pub bg: Option<Color>,
+    pub fg: Option<Color>,
+}
+
+/// Groups used to color GTK widgets around the grid
+#[derive(Default, Debug)]
+pub struct Chrome {
+    pub normal: HlColors,
+    pub tabline: HlColors,
+    pub tabline_sel: HlColors,
+    pub msg_area: HlColors,
+    pub error_msg: HlColors,
+}
+
+impl Chrome {
+    /// Rules for set colors only, so unset group keeps GTK theme look
+    /// Rules match only widgets with `scope` style class
+    pub fn css(&self, scope: &str) -> String {
+        let mut css = String::new();
+
+        let tabline = format!("notebook.nvim-tabline.{}", scope);
+        css_rule(&mut css, &format!("{} header", tabline), &self.tabline);
+        css_rule(&mut css, &format!("{} tab", tabline), &self.tabline);
+        css_rule(&mut css, &format!("{} tab:checked", tabline), &self.tabline_sel);
+
+        // MsgArea is linked to Normal by default and missing in nvim < 0.5
+        let msg_area = HlColors {
+            bg: self.msg_area.bg.clone().or_else(|| self.normal.bg.clone()),
+            fg: self.msg_area.fg.clone().or_else(|| self.normal.fg.clone()),
+        };
+        css_rule(&mut css, &format!("popover.nvim-cmdline.{}", scope), &msg_area);
+
+        let error_label = HlColors {
+            bg: None,
+            fg: self.error_msg.fg.clone(),
+        };
+        let error_bar = format!("infobar.nvim-error-bar.{}.error", scope);
+        css_rule(&mut css, &format!("{} box", error_bar), &self.error_msg);
+        css_rule(&mut css, &format!("{} label", error_bar), &error_label);
+
+        css
+    }
+}
+
+fn css_rule(css: &mut String, selector: &str, colors: &HlColors) {
+    if colors.bg.is_none() && colors.fg.is_none() {
+        return;
+    }
+
+    css.push_str(selector);
+    css.push_str(" {");
+    if let Some(ref bg) = colors.bg {
+        css.push_str(&format!(" background-color: {}; background-image: none;", bg.to_hex()));
+    }
+    if let Some(ref fg) = colors.fg {
+        css.push_str(&format!(" color: {};", fg.to_hex()));
+    }
+    css.push_str(" }\n");
+}
+
 fn get_hl_color(map: &HashMap<&str, &Value>, color_name: &str) -> Option<Color> {
     map.get(color_name)
         .and_then(|col| col.as_u64())
@@ -135,3 +270,30 @@ fn hl_colors(hl: Result<Vec<(Value, Value)>, CallError>) -> (Option<Color>, Opti
         .unwrap_or((None, None))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrome_css_skips_unset_groups() {
+        assert_eq!("", Chrome::default().css("nvim-gtk-shell-0"));
+
+        let chrome = Chrome {
+            tabline_sel: HlColors {
+                bg: Some(Color(1.0, 0.0, 0.0)),
+                fg: None,
+            },
+            normal: HlColors {
+                bg: None,
+                fg: Some(Color(1.0, 1.0, 1.0)),
+            },
+            ..Chrome::default()
+        };
+        assert_eq!(
+            "notebook.nvim-tabline.nvim-gtk-shell-0 tab:checked \
+             { background-color: #FF0000; background-image: none; }\n\
+             popover.nvim-cmdline.nvim-gtk-shell-0 { color: #FFFFFF; }\n",
+            chrome.css("nvim-gtk-shell-0")
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glib;
+use gtk;
+
+struct State<T> {
+    pending: Option<T>,
+    timer: Option<glib::SourceId>,
+}
+
+/// Apply values at most once per interval
+///
+/// First value is applied immediately, values that come during interval
+/// are collapsed and only last one is applied at interval end.
+pub struct Throttle<T> {
+    interval_ms: u32,
+    state: Rc<RefCell<State<T>>>,
+    apply: Rc<Fn(T)>,
+}
+
+impl<T: 'static> Throttle<T> {
+    pub fn new<F>(interval_ms: u32, apply: F) -> Self
+    where
+        F: Fn(T) + 'static,
+    {
+        Throttle {
+            interval_ms,
+            state: Rc::new(RefCell::new(State {
+                pending: None,
+                timer: None,
+            })),
+            apply: Rc::new(apply),
+        }
+    }
+
+    pub fn update(&self, value: T) {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.timer.is_some() {
+                state.pending = Some(value);
+                return;
+            }
+
+            let state_ref = self.state.clone();
+            let apply = self.apply.clone();
+            state.timer = Some(gtk::timeout_add(self.interval_ms, move || {
+                let pending = state_ref.borrow_mut().pending.take();
+                match pending {
+                    Some(value) => {
+                        apply(value);
+                        glib::Continue(true)
+                    }
+                    None => {
+                        state_ref.borrow_mut().timer = None;
+                        glib::Continue(false)
+                    }
+                }
+            }));
+        }
+
+        (self.apply)(value);
+    }
+}
+
+impl<T> Clone for Throttle<T> {
+    fn clone(&self) -> Self {
+        Throttle {
+            interval_ms: self.interval_ms,
+            state: self.state.clone(),
+            apply: self.apply.clone(),
+        }
+    }
+}
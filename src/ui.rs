@@ -7,6 +7,7 @@ use std::{env, thread};
 use gdk::{self, ScreenExt};
 use gio::prelude::*;
 use gio::{Menu, MenuExt, MenuItem, SimpleAction};
+use glib;
 use glib::variant::FromVariant;
 use gtk;
 use gtk::prelude::*;
@@ -14,15 +15,21 @@ use gtk::{AboutDialog, ApplicationWindow, Button, HeaderBar, Orientation, Paned,
 
 use toml;
 
+use command_palette::CommandPalette;
 use file_browser::FileBrowserWidget;
+use gui_config::GuiConfig;
+use header_title::{self, HeaderTitle};
 use misc;
 use nvim::NvimCommand;
 use plug_manager;
 use project::Projects;
-use settings::{Settings, SettingsLoader};
+use render;
+use settings::{FontSource, Settings, SettingsLoader};
 use shell::{self, Shell, ShellOptions};
 use shell_dlg;
+use status_bar::{self, StatusBar};
 use subscriptions::{SubscriptionHandle, SubscriptionKey};
+use throttle::Throttle;
 
 macro_rules! clone {
     (@param _) => ( _ );
@@ -45,10 +52,14 @@ const DEFAULT_WIDTH: i32 = 800;
 const DEFAULT_HEIGHT: i32 = 600;
 const DEFAULT_SIDEBAR_WIDTH: i32 = 200;
 
+/// Minimal interval between window title updates
+const TITLE_UPDATE_INTERVAL_MS: u32 = 50;
+
 pub struct Ui {
     initialized: bool,
     comps: Arc<UiMutex<Components>>,
     settings: Rc<RefCell<Settings>>,
+    config: GuiConfig,
     shell: Rc<RefCell<Shell>>,
     projects: Rc<RefCell<Projects>>,
     plug_manager: Arc<UiMutex<plug_manager::Manager>>,
@@ -59,6 +70,10 @@ pub struct Components {
     window: Option<ApplicationWindow>,
     window_state: WindowState,
     open_btn: Button,
+    /// Set by `GtkGuiWindowBorder`
+    border_css: gtk::CssProvider,
+    /// Scopes border css to this window
+    border_class: String,
 }
 
 impl Components {
@@ -78,6 +93,8 @@ impl Components {
             open_btn,
             window: None,
             window_state: WindowState::load(),
+            border_css: gtk::CssProvider::new(),
+            border_class: misc::unique_style_class("nvim-gtk-window"),
         }
     }
 
@@ -91,7 +108,7 @@ impl Components {
 }
 
 impl Ui {
-    pub fn new(options: ShellOptions) -> Ui {
+    pub fn new(options: ShellOptions, config: GuiConfig) -> Ui {
         let plug_manager = plug_manager::Manager::new();
 
         let plug_manager = Arc::new(UiMutex::new(plug_manager));
@@ -108,22 +125,47 @@ impl Ui {
             comps,
             shell,
             settings,
+            config,
             projects,
             plug_manager,
             file_browser,
         }
     }
 
-    pub fn init(&mut self, app: &gtk::Application, restore_win_state: bool) {
+    pub fn init(
+        &mut self,
+        app: &gtk::Application,
+        restore_win_state: bool,
+        geometry: Option<misc::Geometry>,
+        role: Option<&str>,
+    ) {
         if self.initialized {
             return;
         }
         self.initialized = true;
 
         let mut settings = self.settings.borrow_mut();
+        self.apply_config(&mut settings);
         settings.init();
 
         let window = ApplicationWindow::new(app);
+        // must be set before window is realized
+        if let Some(role) = role {
+            window.set_role(role);
+        }
+
+        // background transparency needs ARGB visual, it can't be changed after realize
+        if let Some(screen) = window.get_screen() {
+            if screen.is_composited() {
+                if let Some(visual) = screen.get_rgba_visual() {
+                    window.set_visual(Some(&visual));
+                }
+            }
+        }
+
+        let prefer_dark_theme = env::var("NVIM_GTK_PREFER_DARK_THEME")
+            .map(|opt| opt.trim() == "1")
+            .unwrap_or(false);
 
         let main = Paned::new(Orientation::Horizontal);
 
@@ -136,17 +178,32 @@ impl Ui {
             self.shell.borrow_mut().init();
 
             comps.window = Some(window.clone());
+            // decoration is not a child of window, so provider is added to screen
+            // and limited to this window by style class
+            if let Some(style_context) = window.get_style_context() {
+                style_context.add_class(&comps.border_class);
+            }
+            misc::add_screen_provider(&window, &comps.border_css);
 
-            let prefer_dark_theme = env::var("NVIM_GTK_PREFER_DARK_THEME")
-                .map(|opt| opt.trim() == "1")
-                .unwrap_or(false);
             if prefer_dark_theme {
                 if let Some(settings) = window.get_settings() {
                     settings.set_property_gtk_application_prefer_dark_theme(true);
                 }
             }
 
-            if restore_win_state {
+            if let Some(geometry) = geometry {
+                // window is sized by grid size request
+                window.set_default_size(1, 1);
+                main.set_position(DEFAULT_SIDEBAR_WIDTH);
+                self.shell
+                    .borrow()
+                    .state
+                    .borrow()
+                    .set_grid_size_request(geometry.columns, geometry.rows);
+                if let Some(offset) = geometry.offset {
+                    move_window(&window, &offset);
+                }
+            } else if restore_win_state {
                 if comps.window_state.is_maximized {
                     window.maximize();
                 }
@@ -176,12 +233,14 @@ impl Ui {
             window.set_decorated(false);
         }
 
-        let update_subtitle = if use_header_bar {
-            Some(self.create_header_bar(app))
+        let update_header_title = if use_header_bar {
+            Some(self.create_header_bar())
         } else {
             None
         };
 
+        self.create_actions(app, &window);
+
         let show_sidebar_action =
             SimpleAction::new_stateful("show-sidebar", None, &false.to_variant());
         let file_browser_ref = self.file_browser.clone();
@@ -194,7 +253,7 @@ impl Ui {
                 comps_ref.borrow_mut().window_state.show_sidebar = is_active;
             }
         });
-        app.add_action(&show_sidebar_action);
+        window.add_action(&show_sidebar_action);
 
         let comps_ref = self.comps.clone();
         window.connect_size_allocate(clone!(main => move |window, _| {
@@ -206,8 +265,9 @@ impl Ui {
         }));
 
         let comps_ref = self.comps.clone();
+        let shell_ref = self.shell.clone();
         window.connect_window_state_event(move |_, event| {
-            gtk_window_state_event(event, &mut *comps_ref.borrow_mut());
+            gtk_window_state_event(event, &mut *comps_ref.borrow_mut(), &*shell_ref);
             Inhibit(false)
         });
 
@@ -221,10 +281,30 @@ impl Ui {
         main.pack1(&**file_browser, false, false);
         main.pack2(&**shell, true, false);
 
-        window.add(&main);
+        let show_status_bar = self.config.show_status_bar.unwrap_or(true);
+        let status_bar = Rc::new(StatusBar::new());
+        let content = gtk::Box::new(Orientation::Vertical, 0);
+        content.pack_start(&main, true, true, 0);
+        if show_status_bar {
+            content.pack_end(&**status_bar, false, false, 0);
+        }
+        window.add(&content);
 
         window.show_all();
 
+        if self.config.export_cursor_rect.unwrap_or(false) {
+            self.create_cursor_rect_action(&window);
+        }
+
+        if geometry.is_some() {
+            // size request is needed only for first allocation, allow resize after it
+            let state_ref = self.shell.borrow().state.clone();
+            gtk::idle_add(move || {
+                state_ref.borrow().reset_size_request();
+                Continue(false)
+            });
+        }
+
         if restore_win_state {
             // Hide sidebar, if it wasn't shown last time.
             // Has to be done after show_all(), so it won't be shown again.
@@ -233,11 +313,40 @@ impl Ui {
         }
 
         let comps_ref = self.comps.clone();
+        let title_throttle = Throttle::new(TITLE_UPDATE_INTERVAL_MS, move |args| {
+            update_window_title(&comps_ref, args)
+        });
         let update_title = shell.state.borrow().subscribe(
-            SubscriptionKey::from("BufEnter,DirChanged"),
+            SubscriptionKey::from("BufEnter,DirChanged,WinEnter,TabEnter"),
             &["expand('%:p')", "getcwd()"],
-            move |args| update_window_title(&comps_ref, args),
+            move |args| title_throttle.update(args),
         );
+        run_deferred_on(&self.shell, "BufFilePost", &update_title);
+
+        let shell_ref = self.shell.clone();
+        let update_cwd = shell.state.borrow().subscribe(
+            SubscriptionKey::from("DirChanged,WinEnter,TabEnter"),
+            &["getcwd()"],
+            move |args| shell_ref.borrow().state.borrow().set_cwd(&args[0]),
+        );
+
+        // buffer content is tracked only for status bar counts
+        let attach_buffer = if show_status_bar {
+            let status_bar = status_bar.clone();
+            shell
+                .state
+                .borrow_mut()
+                .set_buffer_stats_cb(move |words, chars| status_bar.set_counts(words, chars));
+
+            let shell_ref = self.shell.clone();
+            Some(shell.state.borrow().subscribe(
+                SubscriptionKey::from("BufEnter"),
+                &["bufnr('%')"],
+                move |_| shell_ref.borrow().state.borrow().attach_current_buffer(),
+            ))
+        } else {
+            None
+        };
 
         let shell_ref = self.shell.clone();
         let update_completeopt = shell.state.borrow().subscribe(
@@ -246,6 +355,165 @@ impl Ui {
             move |args| set_completeopts(&*shell_ref, args),
         );
 
+        // cursor line stripe is limited to current window
+        let cursor_line_args: Vec<&str> = ["&cursorline"]
+            .iter()
+            .chain(render::WIN_INFO_ARGS)
+            .cloned()
+            .collect();
+        let cursor_line_args = &cursor_line_args;
+        let shell_ref = self.shell.clone();
+        let update_cursor_line = shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "cursorline"),
+            cursor_line_args,
+            move |args| set_cursor_line(&*shell_ref, args),
+        );
+
+        let shell_ref = self.shell.clone();
+        let update_tabstop = shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "tabstop"),
+            &["&tabstop"],
+            move |args| shell_ref.borrow().set_tabstop(&args[0]),
+        );
+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("BufEnter"),
+            &["&tabstop"],
+            move |args| shell_ref.borrow().set_tabstop(&args[0]),
+        );
+
+        let shell_ref = self.shell.clone();
+        let update_mouse = shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "mouse"),
+            &["&mouse"],
+            move |args| shell_ref.borrow().set_mouse_option(&args[0]),
+        );
+
+        // 'colorcolumn' is relative to window text area,
+        // so it moves with horizontal scroll
+        let color_column_args: Vec<&str> = ["&colorcolumn", "&textwidth"]
+            .iter()
+            .chain(render::WIN_INFO_ARGS)
+            .cloned()
+            .collect();
+        let color_column_args = &color_column_args;
+        let shell_ref = self.shell.clone();
+        let update_color_columns = shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "colorcolumn"),
+            color_column_args,
+            move |args| set_color_columns(&*shell_ref, args),
+        );
+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("WinEnter,BufEnter,VimResized,CursorMoved,CursorMovedI"),
+            color_column_args,
+            move |args| set_color_columns(&*shell_ref, args),
+        );
+
+        // scroll and resize without cursor move, nvim 0.5+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("WinScrolled"),
+            color_column_args,
+            move |args| set_color_columns(&*shell_ref, args),
+        );
+
+        let nowrap_window_args = &[
+            "&wrap",
+            "win_screenpos(0)[0]",
+            "win_screenpos(0)[1]",
+            "winheight(0)",
+            "winwidth(0)",
+        ];
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "wrap"),
+            nowrap_window_args,
+            move |args| set_nowrap_window(&*shell_ref, args),
+        );
+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("WinEnter,BufEnter,VimResized"),
+            nowrap_window_args,
+            move |args| set_nowrap_window(&*shell_ref, args),
+        );
+
+        // window scrolled or resized without entering it, `:resize`, `<C-w>=`, `:vsplit`;
+        // WinScrolled is nvim 0.5+, WinResized is nvim 0.9+, so they are subscribed separately
+        for event in &["WinScrolled", "WinResized"] {
+            let shell_ref = self.shell.clone();
+            shell.state.borrow().subscribe(
+                SubscriptionKey::from(*event),
+                nowrap_window_args,
+                move |args| set_nowrap_window(&*shell_ref, args),
+            );
+        }
+
+        if self.config.overscroll.unwrap_or(false) {
+            let edge_args = &["line('w0')", "line('$')"];
+            let shell_ref = self.shell.clone();
+            shell.state.borrow().subscribe(
+                SubscriptionKey::from("CursorMoved,CursorMovedI,BufEnter,WinEnter"),
+                edge_args,
+                move |args| shell_ref.borrow().set_buffer_edges(&args),
+            );
+
+            // viewport scrolled without cursor move, nvim 0.5+
+            let shell_ref = self.shell.clone();
+            shell.state.borrow().subscribe(
+                SubscriptionKey::from("WinScrolled"),
+                edge_args,
+                move |args| shell_ref.borrow().set_buffer_edges(&args),
+            );
+        }
+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("WinEnter,BufEnter,VimResized"),
+            cursor_line_args,
+            move |args| set_cursor_line(&*shell_ref, args),
+        );
+
+        // window resized without entering it, nvim 0.5+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("WinScrolled"),
+            cursor_line_args,
+            move |args| set_cursor_line(&*shell_ref, args),
+        );
+
+        let status_events = "CursorMoved,CursorMovedI,BufEnter,BufWritePost,TextChanged,TextChangedI";
+        let update_status_bar = if show_status_bar {
+            Some(shell.state.borrow().subscribe(
+                SubscriptionKey::from(status_events),
+                status_bar::STATUS_ARGS,
+                move |args| status_bar.update(&args),
+            ))
+        } else {
+            None
+        };
+
+        let shell_ref = self.shell.clone();
+        shell.state.borrow().subscribe(
+            SubscriptionKey::from("ColorScheme"),
+            &[
+                "get(g:, 'colors_name', '')",
+                "synIDattr(hlID('Normal'), 'bg#', 'gui')",
+                "synIDattr(hlID('Normal'), 'bg', 'cterm')",
+            ],
+            move |args| shell_ref.borrow().check_colorscheme(&args),
+        );
+
+        let comps_ref = self.comps.clone();
+        let update_dark_theme = shell.state.borrow().subscribe(
+            SubscriptionKey::with_pattern("OptionSet", "background"),
+            &["&background"],
+            move |args| set_prefer_dark_theme(&comps_ref, args),
+        );
+
         let comps_ref = self.comps.clone();
         let shell_ref = self.shell.clone();
         window.connect_delete_event(move |_, _| gtk_delete(&*comps_ref, &*shell_ref));
@@ -264,18 +532,41 @@ impl Ui {
         let state_ref = self.shell.borrow().state.clone();
         let file_browser_ref = self.file_browser.clone();
         let plug_manager_ref = self.plug_manager.clone();
+        let restore_session = self.config.restore_session.unwrap_or(false);
         shell.set_nvim_started_cb(Some(move || {
             let state = state_ref.borrow();
             plug_manager_ref
                 .borrow_mut()
                 .init_nvim_client(state_ref.borrow().nvim_clone());
             file_browser_ref.borrow_mut().init(&state);
+            state.set_channel_var();
             state.set_autocmds();
             state.run_now(&update_title);
+            state.run_now(&update_cwd);
+            if let Some(ref attach_buffer) = attach_buffer {
+                state.run_now(attach_buffer);
+            }
             state.run_now(&update_completeopt);
-            if let Some(ref update_subtitle) = update_subtitle {
-                state.run_now(&update_subtitle);
+            state.run_now(&update_cursor_line);
+            state.run_now(&update_color_columns);
+            state.run_now(&update_tabstop);
+            state.run_now(&update_mouse);
+            // environment variable has priority over 'background' at startup
+            if !prefer_dark_theme {
+                state.run_now(&update_dark_theme);
+            }
+            if let Some(ref update_header_title) = update_header_title {
+                state.run_now(&update_header_title);
             }
+            if let Some(ref update_status_bar) = update_status_bar {
+                state.run_now(&update_status_bar);
+            }
+            // after ginit.vim, so session overrides user configuration
+            if restore_session {
+                shell::State::init_session(&state_ref);
+            }
+            state.check_gui_plugins();
+            state.set_version_var();
         }));
 
         let sidebar_action = UiMutex::new(show_sidebar_action);
@@ -287,6 +578,146 @@ impl Ui {
         ));
     }
 
+    /// GUI commands registered as window actions, so they can be bound to any
+    /// shortcut and always act on shell of the window they belong to
+    fn create_actions(&self, app: &gtk::Application, window: &ApplicationWindow) {
+        let new_tab_action = SimpleAction::new("new-tab", None);
+        let shell_ref = self.shell.clone();
+        new_tab_action.connect_activate(move |_, _| shell_ref.borrow().new_tab());
+        window.add_action(&new_tab_action);
+
+        let paste_action = SimpleAction::new("paste", None);
+        let shell_ref = self.shell.clone();
+        paste_action.connect_activate(move |_, _| shell_ref.borrow().edit_paste());
+        window.add_action(&paste_action);
+
+        let save_all_action = SimpleAction::new("save-all", None);
+        let shell_ref = self.shell.clone();
+        save_all_action.connect_activate(move |_, _| shell_ref.borrow().edit_save_all());
+        window.add_action(&save_all_action);
+
+        let open_project_action = SimpleAction::new("open-project", None);
+        let projects = self.projects.clone();
+        open_project_action.connect_activate(move |_, _| projects.borrow_mut().show());
+        window.add_action(&open_project_action);
+
+        let command_palette = CommandPalette::new(&*self.shell.borrow());
+        let command_palette_action = SimpleAction::new("command-palette", None);
+        command_palette_action.connect_activate(move |_, _| command_palette.show());
+        window.add_action(&command_palette_action);
+        app.set_accels_for_action("win.command-palette", &["<Primary><Shift>p"]);
+
+        let select_font_action = SimpleAction::new("select-font", None);
+        let shell_ref = self.shell.clone();
+        select_font_action.connect_activate(
+            clone!(window => move |_, _| select_font(&window, &*shell_ref)),
+        );
+        window.add_action(&select_font_action);
+
+        let fullscreen_action =
+            SimpleAction::new_stateful("fullscreen", None, &false.to_variant());
+        fullscreen_action.connect_change_state(clone!(window => move |action, value| {
+            if let Some(ref value) = *value {
+                action.set_state(value);
+                if value.get::<bool>().unwrap() {
+                    window.fullscreen();
+                } else {
+                    window.unfullscreen();
+                }
+            }
+        }));
+        window.add_action(&fullscreen_action);
+    }
+
+    /// Window action exported on D-Bus by `GtkApplication`
+    ///
+    /// State is cursor rectangle on screen as `"x,y,width,height"`, so external
+    /// tools get `org.gtk.Actions.Changed` on cursor moves. `Describe` returns
+    /// last rectangle, activation recalculates it, e.g. after window move.
+    fn create_cursor_rect_action(&self, window: &ApplicationWindow) {
+        let action = SimpleAction::new_stateful("cursor-rect", None, &"".to_variant());
+
+        let state_ref = self.shell.borrow().state.clone();
+        action.connect_activate(clone!(state_ref => move |action, _| {
+            if let Some(rect) = state_ref.borrow().cursor_screen_rect() {
+                action.set_state(&cursor_rect_variant(rect));
+            }
+        }));
+
+        state_ref
+            .borrow_mut()
+            .set_cursor_rect_cb(clone!(action => move |rect| {
+                action.set_state(&cursor_rect_variant(rect));
+            }));
+
+        window.add_action(&action);
+    }
+
+    fn apply_config(&self, settings: &mut Settings) {
+        let shell = self.shell.borrow();
+        let mut state = shell.state.borrow_mut();
+
+        if let Some(ref font) = self.config.font {
+            settings.set_font_source(FontSource::Config);
+            state.set_font_desc(font);
+        }
+
+        if let Some(ref font_features) = self.config.font_features {
+            state.set_font_features(font_features.clone());
+        }
+
+        if let Some(ref antialiasing) = self.config.antialiasing {
+            state.set_antialiasing(antialiasing);
+        }
+
+        if let Some(ref padding) = self.config.padding {
+            state.set_padding(padding);
+        }
+
+        let font_variants = [
+            ("bold", &self.config.font_bold),
+            ("italic", &self.config.font_italic),
+            ("bold_italic", &self.config.font_bold_italic),
+        ];
+        for &(variant, font) in &font_variants {
+            if let Some(ref font) = *font {
+                state.set_font_variant(variant.to_owned(), font.clone());
+            }
+        }
+
+        if let Some(line_space) = self.config.line_space {
+            state.set_line_space(line_space.to_string());
+        }
+
+        if let Some(cursor_animation) = self.config.cursor_animation {
+            state.set_cursor_animation(cursor_animation);
+        }
+
+        if let Some(ref easing) = self.config.cursor_animation_easing {
+            state.set_cursor_animation_easing(easing);
+        }
+
+        if let Some(ref cursor_shape) = self.config.cursor_shape {
+            state.set_cursor_shape(cursor_shape);
+        }
+
+        if let Some(fullscreen_hide_tabline) = self.config.fullscreen_hide_tabline {
+            state.set_fullscreen_hide_tabline(fullscreen_hide_tabline);
+        }
+
+        if let Some(scroll_without_mouse) = self.config.scroll_without_mouse {
+            state.set_scroll_without_mouse(scroll_without_mouse);
+        }
+
+        if let Some(dim_inactive) = self.config.dim_inactive {
+            state.set_dim_inactive(dim_inactive);
+        }
+
+        if let Some(overscroll) = self.config.overscroll {
+            state.set_overscroll(overscroll);
+        }
+    }
+
     fn nvim_command(
         shell: &mut shell::State,
         command: NvimCommand,
@@ -311,6 +742,20 @@ impl Ui {
                     warn!("Screen is not composited");
                 }
             }
+            NvimCommand::WindowBorder(radius) => {
+                let comps = comps.borrow();
+                let css = format!(
+                    "window.csd.{0}, window.csd.{0} decoration {{ border-radius: {1}px; }}",
+                    comps.border_class, radius
+                );
+                if let Err(e) = comps.border_css.load_from_data(css.as_bytes()) {
+                    error!("Can't update window border css: {}", e);
+                }
+            }
+            NvimCommand::Suspend => {
+                let comps = comps.borrow();
+                comps.window.as_ref().unwrap().iconify();
+            }
             NvimCommand::PreferDarkTheme(prefer_dark_theme) => {
                 let comps = comps.borrow();
                 let window = comps.window.as_ref().unwrap();
@@ -322,7 +767,7 @@ impl Ui {
         }
     }
 
-    fn create_header_bar(&self, app: &gtk::Application) -> SubscriptionHandle {
+    fn create_header_bar(&self) -> SubscriptionHandle {
         let header_bar = HeaderBar::new();
         let comps = self.comps.borrow();
         let window = comps.window.as_ref().unwrap();
@@ -341,19 +786,19 @@ impl Ui {
         new_tab_btn.set_tooltip_text("Open a new tab");
         header_bar.pack_start(&new_tab_btn);
 
-        header_bar.pack_end(&self.create_primary_menu_btn(app, &window));
+        header_bar.pack_end(&self.create_primary_menu_btn(window));
 
         let paste_btn =
             Button::new_from_icon_name("edit-paste-symbolic", gtk::IconSize::SmallToolbar.into());
         let shell = self.shell.clone();
-        paste_btn.connect_clicked(move |_| shell.borrow_mut().edit_paste());
+        paste_btn.connect_clicked(move |_| shell.borrow().edit_paste());
         paste_btn.set_can_focus(false);
         paste_btn.set_tooltip_text("Paste from clipboard");
         header_bar.pack_end(&paste_btn);
 
         let save_btn = Button::new_with_label("Save All");
         let shell = self.shell.clone();
-        save_btn.connect_clicked(move |_| shell.borrow_mut().edit_save_all());
+        save_btn.connect_clicked(move |_| shell.borrow().edit_save_all());
         save_btn.set_can_focus(false);
         header_bar.pack_end(&save_btn);
 
@@ -363,22 +808,24 @@ impl Ui {
 
         let shell = self.shell.borrow();
 
-        let update_subtitle = shell.state.borrow().subscribe(
-            SubscriptionKey::from("DirChanged"),
-            &["getcwd()"],
-            move |args| {
-                header_bar.set_subtitle(&*args[0]);
-            },
-        );
+        let title = Rc::new(RefCell::new(HeaderTitle::new()));
+        header_bar.set_custom_title(Some(&**title.borrow()));
 
-        update_subtitle
+        let title_throttle = Throttle::new(TITLE_UPDATE_INTERVAL_MS, move |args: Vec<String>| {
+            title.borrow_mut().update(&args)
+        });
+        let update_title = shell.state.borrow().subscribe(
+            SubscriptionKey::from(
+                "BufEnter,BufWritePost,DirChanged,WinEnter,TabEnter,TextChanged,TextChangedI",
+            ),
+            header_title::TITLE_ARGS,
+            move |args| title_throttle.update(args),
+        );
+        run_deferred_on(&self.shell, "BufFilePost", &update_title);
+        update_title
     }
 
-    fn create_primary_menu_btn(
-        &self,
-        app: &gtk::Application,
-        window: &gtk::ApplicationWindow,
-    ) -> gtk::MenuButton {
+    fn create_primary_menu_btn(&self, window: &gtk::ApplicationWindow) -> gtk::MenuButton {
         let plug_manager = self.plug_manager.clone();
         let btn = gtk::MenuButton::new();
         btn.set_can_focus(false);
@@ -387,7 +834,6 @@ impl Ui {
             gtk::IconSize::SmallToolbar.into(),
         ));
 
-        // note actions created in application menu
         let menu = Menu::new();
 
         let section = Menu::new();
@@ -395,12 +841,14 @@ impl Ui {
         menu.append_section(None, &section);
 
         let section = Menu::new();
-        section.append_item(&MenuItem::new("Sidebar", "app.show-sidebar"));
+        section.append_item(&MenuItem::new("Sidebar", "win.show-sidebar"));
+        section.append_item(&MenuItem::new("Fullscreen", "win.fullscreen"));
+        section.append_item(&MenuItem::new("Select Font", "win.select-font"));
         menu.append_section(None, &section);
 
         let section = Menu::new();
-        section.append_item(&MenuItem::new("Plugins", "app.Plugins"));
-        section.append_item(&MenuItem::new("About", "app.HelpAbout"));
+        section.append_item(&MenuItem::new("Plugins", "win.Plugins"));
+        section.append_item(&MenuItem::new("About", "win.HelpAbout"));
         menu.append_section(None, &section);
 
         menu.freeze();
@@ -411,30 +859,79 @@ impl Ui {
         );
 
         let about_action = SimpleAction::new("HelpAbout", None);
-        about_action.connect_activate(clone!(window => move |_, _| on_help_about(&window)));
+        let shell = self.shell.clone();
+        about_action.connect_activate(
+            clone!(window => move |_, _| on_help_about(&window, &shell.borrow())),
+        );
         about_action.set_enabled(true);
 
-        app.add_action(&about_action);
-        app.add_action(&plugs_action);
+        window.add_action(&about_action);
+        window.add_action(&plugs_action);
 
         btn.set_menu_model(&menu);
         btn
     }
 }
 
-fn on_help_about(window: &gtk::ApplicationWindow) {
+fn cursor_rect_variant((x, y, width, height): shell::CursorScreenRect) -> glib::Variant {
+    format!("{},{},{},{}", x, y, width, height).to_variant()
+}
+
+fn on_help_about(window: &gtk::ApplicationWindow, shell: &Shell) {
     let about = AboutDialog::new();
     about.set_transient_for(window);
     about.set_program_name("NeovimGtk");
     about.set_version(env!("CARGO_PKG_VERSION"));
     about.set_logo_icon_name("org.daa.NeovimGtk");
     about.set_authors(&[env!("CARGO_PKG_AUTHORS")]);
-    about.set_comments(misc::about_comments().as_str());
+    about.set_comments(
+        format!(
+            "{}\n\n{}",
+            misc::about_comments(),
+            shell.state.borrow().version_info().to_text()
+        ).as_str(),
+    );
 
     about.connect_response(|about, _| about.destroy());
     about.show();
 }
 
+fn select_font(window: &gtk::ApplicationWindow, shell: &RefCell<Shell>) {
+    let dlg = gtk::FontChooserDialog::new(Some("Select Font"), Some(window));
+
+    if dlg.run() == gtk::ResponseType::Ok.into() {
+        if let Some(font) = dlg.get_font() {
+            shell.borrow().state.borrow_mut().set_font_rpc(&font);
+        }
+    }
+
+    dlg.destroy();
+}
+
+/// Move window as X geometry offset, ignored by platforms that don't allow it
+fn move_window(window: &gtk::ApplicationWindow, offset: &misc::GeometryOffset) {
+    let gravity = match (offset.from_right, offset.from_bottom) {
+        (false, false) => gdk::Gravity::NorthWest,
+        (true, false) => gdk::Gravity::NorthEast,
+        (false, true) => gdk::Gravity::SouthWest,
+        (true, true) => gdk::Gravity::SouthEast,
+    };
+    window.set_gravity(gravity);
+
+    let screen = window.get_screen().unwrap();
+    let x = if offset.from_right {
+        screen.get_width() - offset.x
+    } else {
+        offset.x
+    };
+    let y = if offset.from_bottom {
+        screen.get_height() - offset.y
+    } else {
+        offset.y
+    };
+    window.move_(x, y);
+}
+
 fn gtk_delete(comps: &UiMutex<Components>, shell: &RefCell<Shell>) -> Inhibit {
     if !shell.borrow().is_nvim_initialized() {
         return Inhibit(false);
@@ -443,7 +940,7 @@ fn gtk_delete(comps: &UiMutex<Components>, shell: &RefCell<Shell>) -> Inhibit {
     Inhibit(if shell_dlg::can_close_window(comps, shell) {
         let comps = comps.borrow();
         comps.close_window();
-        shell.borrow_mut().detach_ui();
+        shell.borrow().detach_ui();
         false
     } else {
         true
@@ -465,10 +962,33 @@ fn gtk_window_size_allocate(
     }
 }
 
-fn gtk_window_state_event(event: &gdk::EventWindowState, comps: &mut Components) {
+fn gtk_window_state_event(
+    event: &gdk::EventWindowState,
+    comps: &mut Components,
+    shell: &RefCell<Shell>,
+) {
     comps.window_state.is_maximized = event
         .get_new_window_state()
         .contains(gdk::WindowState::MAXIMIZED);
+
+    if event
+        .get_changed_mask()
+        .contains(gdk::WindowState::FULLSCREEN)
+    {
+        let fullscreen = event
+            .get_new_window_state()
+            .contains(gdk::WindowState::FULLSCREEN);
+        shell.borrow().state.borrow_mut().set_fullscreen(fullscreen);
+    }
+}
+
+fn set_prefer_dark_theme(comps: &UiMutex<Components>, args: Vec<String>) {
+    let comps = comps.borrow();
+    let window = comps.window.as_ref().unwrap();
+
+    if let Some(settings) = window.get_settings() {
+        settings.set_property_gtk_application_prefer_dark_theme(args[0] == "dark");
+    }
 }
 
 fn set_completeopts(shell: &RefCell<Shell>, args: Vec<String>) {
@@ -477,6 +997,30 @@ fn set_completeopts(shell: &RefCell<Shell>, args: Vec<String>) {
     shell.borrow().set_completeopts(options);
 }
 
+fn set_cursor_line(shell: &RefCell<Shell>, args: Vec<String>) {
+    shell.borrow().set_cursor_line(&args);
+}
+
+fn set_color_columns(shell: &RefCell<Shell>, args: Vec<String>) {
+    shell.borrow().set_color_columns(&args);
+}
+
+fn set_nowrap_window(shell: &RefCell<Shell>, args: Vec<String>) {
+    shell.borrow().set_nowrap_window(&args);
+}
+
+/// Buffer renamed by `nvim_buf_set_name` is current only while its autocmd runs,
+/// so subscription is evaluated after it
+fn run_deferred_on(shell: &Rc<RefCell<Shell>>, event: &str, handle: &SubscriptionHandle) {
+    let shell_ref = shell.clone();
+    let handle = handle.clone();
+    shell.borrow().state.borrow().subscribe(
+        SubscriptionKey::from(event),
+        &[],
+        move |_| shell_ref.borrow().run_deferred(&handle),
+    );
+}
+
 fn update_window_title(comps: &Arc<UiMutex<Components>>, args: Vec<String>) {
     let comps_ref = comps.clone();
     let comps = comps_ref.borrow();
@@ -496,7 +1040,7 @@ fn update_window_title(comps: &Arc<UiMutex<Components>>, args: Vec<String>) {
         &file_path
     };
 
-    window.set_title(filename);
+    window.set_title(&format!("{} - {}", filename, misc::shorten_home(&args[1])));
 }
 
 #[derive(Serialize, Deserialize)]
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use color::Color;
 use neovim_lib::Value;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Attrs {
     pub italic: bool,
     pub bold: bool,
@@ -14,6 +14,8 @@ pub struct Attrs {
     pub special: Option<Color>,
     pub reverse: bool,
     pub double_width: bool,
+    /// Background transparency from 0 (opaque) to 100, set by 'winblend'
+    pub blend: u8,
 }
 
 impl Attrs {
@@ -28,6 +30,7 @@ impl Attrs {
             undercurl: false,
             reverse: false,
             double_width: false,
+            blend: 0,
         }
     }
 
@@ -51,11 +54,16 @@ impl Attrs {
                         model_attrs.special = Some(Color::from_indexed_color(bg));
                     }
                 }
-                "reverse" => model_attrs.reverse = true,
-                "bold" => model_attrs.bold = true,
-                "italic" => model_attrs.italic = true,
-                "underline" => model_attrs.underline = true,
-                "undercurl" => model_attrs.undercurl = true,
+                "reverse" => model_attrs.reverse = val.as_bool().unwrap_or(false),
+                "bold" => model_attrs.bold = val.as_bool().unwrap_or(false),
+                "italic" => model_attrs.italic = val.as_bool().unwrap_or(false),
+                "underline" => model_attrs.underline = val.as_bool().unwrap_or(false),
+                "undercurl" => model_attrs.undercurl = val.as_bool().unwrap_or(false),
+                "blend" => {
+                    if let Some(blend) = val.as_u64() {
+                        model_attrs.blend = blend.min(100) as u8;
+                    }
+                }
                 attr_key => error!("unknown attribute {}", attr_key),
             };
         }
@@ -73,6 +81,7 @@ impl Attrs {
         self.background = None;
         self.special = None;
         self.double_width = false;
+        self.blend = 0;
     }
 }
 
@@ -98,3 +107,34 @@ impl Cell {
         self.dirty = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attrs_wrong_value_types() {
+        let mut attrs = HashMap::new();
+        attrs.insert("foreground".to_owned(), Value::from("red"));
+        attrs.insert("bold".to_owned(), Value::from(1));
+        attrs.insert("italic".to_owned(), Value::from(true));
+
+        let model_attrs = Attrs::from_value_map(&attrs);
+        assert_eq!(None, model_attrs.foreground);
+        assert!(!model_attrs.bold);
+        assert!(model_attrs.italic);
+    }
+
+    #[test]
+    fn test_attrs_blend() {
+        let mut attrs = HashMap::new();
+        attrs.insert("blend".to_owned(), Value::from(30));
+        assert_eq!(30, Attrs::from_value_map(&attrs).blend);
+
+        attrs.insert("blend".to_owned(), Value::from(200));
+        assert_eq!(100, Attrs::from_value_map(&attrs).blend);
+
+        attrs.insert("blend".to_owned(), Value::from(-1));
+        assert_eq!(0, Attrs::from_value_map(&attrs).blend);
+    }
+}
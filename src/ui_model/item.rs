@@ -8,6 +8,12 @@ pub struct Item {
     pub cells_count: usize,
     pub glyphs: Option<pango::GlyphString>,
     pub ink_overflow: Option<InkOverflow>,
+    /// Font has no requested bold face, so it must be emboldened on render
+    pub synthetic_bold: bool,
+    /// Font has no requested italic face, so it must be slanted on render
+    pub synthetic_italic: bool,
+    /// Glyphs are wider than cells nvim gave to item, so they are scaled down on render
+    pub glyph_scale: Option<f64>,
     font: pango::Font,
 }
 
@@ -21,6 +27,9 @@ impl Item {
             cells_count,
             glyphs: None,
             ink_overflow: None,
+            synthetic_bold: false,
+            synthetic_italic: false,
+            glyph_scale: None,
         }
     }
 
@@ -29,6 +38,7 @@ impl Item {
         self.item = item;
         self.glyphs = None;
         self.ink_overflow = None;
+        self.glyph_scale = None;
     }
 
     pub fn set_glyphs(&mut self, ctx: &render::Context, glyphs: pango::GlyphString) {
@@ -38,6 +48,35 @@ impl Item {
         self.glyphs = Some(glyphs);
     }
 
+    pub fn update_glyph_scale(&mut self, advance: i32, char_width: i32) {
+        let cells_width = self.cells_count as i32 * char_width;
+        // small overflow of monospace glyphs is not visible
+        self.glyph_scale = if advance - cells_width > char_width / 4 {
+            Some(f64::from(cells_width) / f64::from(advance))
+        } else {
+            None
+        };
+    }
+
+    pub fn update_synthetic_style(&mut self, bold: bool, italic: bool) {
+        let desc = self.font.describe();
+
+        self.synthetic_bold = bold && desc.as_ref().map_or(false, |desc| {
+            match desc.get_weight() {
+                pango::Weight::Semibold
+                | pango::Weight::Bold
+                | pango::Weight::Ultrabold
+                | pango::Weight::Heavy
+                | pango::Weight::Ultraheavy => false,
+                _ => true,
+            }
+        });
+        self.synthetic_italic = italic
+            && desc
+                .as_ref()
+                .map_or(false, |desc| desc.get_style() == pango::Style::Normal);
+    }
+
     pub fn font(&self) -> &pango::Font {
         &self.font
     }
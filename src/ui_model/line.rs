@@ -224,7 +224,11 @@ impl<'a> PangoItemPosition<'a> {
         let offset = item.offset() as usize;
         let length = item.length() as usize;
         let start_cell = styled_line.cell_to_byte[offset];
-        let end_cell = styled_line.cell_to_byte[offset + length - 1];
+        let mut end_cell = styled_line.cell_to_byte[offset + length - 1];
+        // double width char at item end covers also next cell
+        if styled_line.double_width.get(end_cell + 1) == Some(&true) {
+            end_cell += 1;
+        }
 
         PangoItemPosition {
             item,
@@ -242,6 +246,8 @@ impl<'a> PangoItemPosition<'a> {
 pub struct StyledLine {
     pub line_str: String,
     cell_to_byte: Box<[usize]>,
+    /// Cells that are second half of double width char, indexed by cell
+    double_width: Box<[bool]>,
     pub attr_list: pango::AttrList,
 }
 
@@ -250,6 +256,7 @@ impl StyledLine {
         line: &Line,
         color_model: &color::ColorModel,
         font_features: &render::FontFeatures,
+        font_variants: &render::FontVariants,
     ) -> Self {
         let average_capacity = line.line.len() * 4 * 2; // code bytes * grapheme cluster
 
@@ -258,13 +265,15 @@ impl StyledLine {
         let attr_list = pango::AttrList::new();
         let mut byte_offset = 0;
         let mut style_attr = StyleAttr::new();
+        let double_width: Vec<bool> = line.line.iter().map(|c| c.attrs.double_width).collect();
 
         for (cell_idx, cell) in line.line.iter().enumerate() {
             if cell.attrs.double_width {
                 continue;
             }
 
-            if !cell.ch.is_empty() {
+            // tab is not shaped, pango gives it own advance
+            if !cell.ch.is_empty() && cell.ch != "\t" {
                 line_str.push_str(&cell.ch);
             } else {
                 line_str.push(' ');
@@ -275,21 +284,26 @@ impl StyledLine {
                 cell_to_byte.push(cell_idx);
             }
 
-            let next = style_attr.next(byte_offset, byte_offset + len, cell, color_model);
+            let wide = line
+                .line
+                .get(cell_idx + 1)
+                .map_or(false, |c| c.attrs.double_width);
+            let next = style_attr.next(byte_offset, byte_offset + len, cell, wide, color_model);
             if let Some(next) = next {
-                style_attr.insert(&attr_list);
+                style_attr.insert(&attr_list, font_variants);
                 style_attr = next;
             }
 
             byte_offset += len;
         }
 
-        style_attr.insert(&attr_list);
+        style_attr.insert(&attr_list, font_variants);
         font_features.insert_attr(&attr_list);
 
         StyledLine {
             line_str,
             cell_to_byte: cell_to_byte.into_boxed_slice(),
+            double_width: double_width.into_boxed_slice(),
             attr_list,
         }
     }
@@ -298,6 +312,7 @@ impl StyledLine {
 struct StyleAttr<'c> {
     italic: bool,
     bold: bool,
+    wide: bool,
     foreground: Option<&'c color::Color>,
     background: Option<&'c color::Color>,
     empty: bool,
@@ -311,6 +326,7 @@ impl<'c> StyleAttr<'c> {
         StyleAttr {
             italic: false,
             bold: false,
+            wide: false,
             foreground: None,
             background: None,
             empty: true,
@@ -324,11 +340,13 @@ impl<'c> StyleAttr<'c> {
         start_idx: usize,
         end_idx: usize,
         cell: &'c Cell,
+        wide: bool,
         color_model: &'c color::ColorModel,
     ) -> Self {
         StyleAttr {
             italic: cell.attrs.italic,
             bold: cell.attrs.bold,
+            wide,
             foreground: color_model.cell_fg(cell),
             background: color_model.cell_bg(cell),
             empty: false,
@@ -343,9 +361,10 @@ impl<'c> StyleAttr<'c> {
         start_idx: usize,
         end_idx: usize,
         cell: &'c Cell,
+        wide: bool,
         color_model: &'c color::ColorModel,
     ) -> Option<StyleAttr<'c>> {
-        let style_attr = Self::from(start_idx, end_idx, cell, color_model);
+        let style_attr = Self::from(start_idx, end_idx, cell, wide, color_model);
 
         if self != &style_attr {
             Some(style_attr)
@@ -355,38 +374,61 @@ impl<'c> StyleAttr<'c> {
         }
     }
 
-    fn insert(&self, attr_list: &pango::AttrList) {
+    fn insert(&self, attr_list: &pango::AttrList, font_variants: &render::FontVariants) {
         if self.empty {
             return;
         }
 
-        if self.italic {
+        let wide_desc = if self.wide {
+            font_variants.wide()
+        } else {
+            None
+        };
+
+        if let Some(wide_desc) = wide_desc {
+            let mut desc = wide_desc.clone();
+            if self.bold {
+                desc.set_weight(pango::Weight::Bold);
+            }
+            if self.italic {
+                desc.set_style(pango::Style::Italic);
+            }
+            self.insert_attr(attr_list, pango::Attribute::new_font_desc(&desc).unwrap());
+        } else if let Some(desc) = font_variants.get(self.bold, self.italic) {
+            self.insert_attr(attr_list, pango::Attribute::new_font_desc(desc).unwrap());
+        } else {
+            self.insert_style_attrs(attr_list);
+        }
+
+        if let Some(fg) = self.foreground {
+            let (r, g, b) = fg.to_u16();
             self.insert_attr(
                 attr_list,
-                pango::Attribute::new_style(pango::Style::Italic).unwrap(),
+                pango::Attribute::new_foreground(r, g, b).unwrap(),
             );
         }
 
-        if self.bold {
+        if let Some(bg) = self.background {
+            let (r, g, b) = bg.to_u16();
             self.insert_attr(
                 attr_list,
-                pango::Attribute::new_weight(pango::Weight::Bold).unwrap(),
+                pango::Attribute::new_background(r, g, b).unwrap(),
             );
         }
+    }
 
-        if let Some(fg) = self.foreground {
-            let (r, g, b) = fg.to_u16();
+    fn insert_style_attrs(&self, attr_list: &pango::AttrList) {
+        if self.italic {
             self.insert_attr(
                 attr_list,
-                pango::Attribute::new_foreground(r, g, b).unwrap(),
+                pango::Attribute::new_style(pango::Style::Italic).unwrap(),
             );
         }
 
-        if let Some(bg) = self.background {
-            let (r, g, b) = bg.to_u16();
+        if self.bold {
             self.insert_attr(
                 attr_list,
-                pango::Attribute::new_background(r, g, b).unwrap(),
+                pango::Attribute::new_weight(pango::Weight::Bold).unwrap(),
             );
         }
     }
@@ -403,6 +445,7 @@ impl<'c> PartialEq for StyleAttr<'c> {
     fn eq(&self, other: &Self) -> bool {
         self.italic == other.italic
             && self.bold == other.bold
+            && self.wide == other.wide
             && self.foreground == other.foreground
             && self.empty == other.empty
             && self.background == other.background
@@ -424,6 +467,7 @@ mod tests {
             &line,
             &color::ColorModel::new(),
             &render::FontFeatures::new(),
+            &render::FontVariants::new(),
         );
         assert_eq!("abc", styled_line.line_str);
         assert_eq!(3, styled_line.cell_to_byte.len());
@@ -431,4 +475,22 @@ mod tests {
         assert_eq!(1, styled_line.cell_to_byte[1]);
         assert_eq!(2, styled_line.cell_to_byte[2]);
     }
+
+    #[test]
+    fn test_styled_line_double_width() {
+        let mut line = Line::new(3);
+        line[0].ch = "中".to_owned();
+        line[1].attrs.double_width = true;
+        line[2].ch = "b".to_owned();
+
+        let styled_line = StyledLine::from(
+            &line,
+            &color::ColorModel::new(),
+            &render::FontFeatures::new(),
+            &render::FontVariants::new(),
+        );
+        assert_eq!("中b", styled_line.line_str);
+        assert_eq!(&[0, 0, 0, 2], &*styled_line.cell_to_byte);
+        assert_eq!(&[false, true, false], &*styled_line.double_width);
+    }
 }
@@ -10,6 +10,8 @@ pub use self::line::{Line, StyledLine};
 pub use self::model_layout::ModelLayout;
 pub use self::model_rect::{ModelRect, ModelRectVec};
 
+use regex::Regex;
+
 pub struct UiModel {
     pub columns: usize,
     pub rows: usize,
@@ -20,6 +22,7 @@ pub struct UiModel {
     bot: usize,
     left: usize,
     right: usize,
+    cleared: bool,
 }
 
 impl UiModel {
@@ -36,9 +39,10 @@ impl UiModel {
             cur_col: 0,
             model: model.into_boxed_slice(),
             top: 0,
-            bot: (rows - 1) as usize,
+            bot: rows.saturating_sub(1) as usize,
             left: 0,
-            right: (columns - 1) as usize,
+            right: columns.saturating_sub(1) as usize,
+            cleared: true,
         }
     }
 
@@ -53,9 +57,30 @@ impl UiModel {
             bot: 0,
             left: 0,
             right: 0,
+            cleared: true,
         }
     }
 
+    /// Change grid size, content of overlapping top left region is kept
+    /// so grid is not blank until nvim redraws it
+    pub fn resize(&mut self, rows: u64, columns: u64) {
+        let mut resized = UiModel::new(rows, columns);
+
+        let copy_columns = self.columns.min(resized.columns);
+        for (line, old_line) in resized.model.iter_mut().zip(self.model.iter()) {
+            line.line[..copy_columns].clone_from_slice(&old_line.line[..copy_columns]);
+            for cell in &mut line.line[..copy_columns] {
+                cell.dirty = true;
+            }
+        }
+
+        resized.cur_row = self.cur_row.min(resized.rows.saturating_sub(1));
+        resized.cur_col = self.cur_col.min(resized.columns.saturating_sub(1));
+        resized.cleared = self.cleared;
+
+        *self = resized;
+    }
+
     #[inline]
     pub fn model(&self) -> &[Line] {
         &self.model
@@ -66,6 +91,101 @@ impl UiModel {
         &mut self.model
     }
 
+    /// Model was cleared and no content put after that,
+    /// so whole grid has default background
+    #[inline]
+    pub fn is_cleared(&self) -> bool {
+        self.cleared
+    }
+
+    /// Text of rows from `start` to `end` inclusive, trailing spaces are removed
+    pub fn rows_text(&self, start: usize, end: usize) -> String {
+        self.model[start..end + 1]
+            .iter()
+            .map(|line| {
+                let text: String = line
+                    .line
+                    .iter()
+                    .map(|cell| {
+                        // empty cell is a space, except second half of double width char
+                        if cell.ch.is_empty() && !cell.attrs.double_width {
+                            " "
+                        } else {
+                            cell.ch.as_str()
+                        }
+                    })
+                    .collect();
+                text.trim_right().to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Text of cell, second half of double width char gives whole char
+    pub fn cell_text(&self, row: usize, col: usize) -> String {
+        let line = &self.model[row].line;
+        if col > 0 && line[col].ch.is_empty() && line[col].attrs.double_width {
+            line[col - 1].ch.clone()
+        } else {
+            line[col].ch.clone()
+        }
+    }
+
+    /// Url that contains given cell,
+    /// same as `matchstr(line, 'https\?://[^[:space:]]*')` around column
+    pub fn url_at(&self, row: usize, col: usize) -> Option<String> {
+        lazy_static! {
+            static ref URL: Regex = Regex::new(r"https?://[^[:space:]]+").unwrap();
+        }
+
+        self.match_at(row, col, &URL)
+    }
+
+    /// File path candidate that contains given cell, without trailing punctuation
+    pub fn path_at(&self, row: usize, col: usize) -> Option<String> {
+        lazy_static! {
+            static ref PATH: Regex = Regex::new(r#"[^[:space:]"'`()<>\[\]{}|]+"#).unwrap();
+        }
+
+        self.match_at(row, col, &PATH)
+            .map(|path| path.trim_right_matches(|c| ",.;:".contains(c)).to_owned())
+            .and_then(|path| if path.is_empty() { None } else { Some(path) })
+    }
+
+    /// Match of `re` in row text that contains given cell
+    fn match_at(&self, row: usize, col: usize, re: &Regex) -> Option<String> {
+        let line = &self.model[row].line;
+        if col >= line.len() {
+            return None;
+        }
+
+        // second half of double width char belongs to previous char
+        let is_second_half = |cell: &Cell| cell.ch.is_empty() && cell.attrs.double_width;
+        let col = if col > 0 && is_second_half(&line[col]) {
+            col - 1
+        } else {
+            col
+        };
+
+        let mut text = String::new();
+        let mut col_offset = 0;
+        for (idx, cell) in line.iter().enumerate() {
+            if idx == col {
+                col_offset = text.len();
+            }
+
+            if cell.ch.is_empty() && !cell.attrs.double_width {
+                text.push(' ');
+            } else {
+                text.push_str(&cell.ch);
+            }
+        }
+
+        re.find_iter(&text)
+            .find(|m| m.start() <= col_offset && col_offset < m.end())
+            .map(|m| m.as_str().to_owned())
+    }
+
     pub fn cur_point(&self) -> ModelRect {
         ModelRect::point(self.cur_col, self.cur_row)
     }
@@ -91,8 +211,19 @@ impl UiModel {
         (self.cur_row, self.cur_col)
     }
 
+    /// Grid without cells, e.g. window shrunk below one cell
+    #[inline]
+    fn is_degenerate(&self) -> bool {
+        self.rows == 0 || self.columns == 0
+    }
+
     pub fn put(&mut self, ch: &str, double_width: bool, attrs: Option<&Attrs>) -> ModelRect {
         let mut changed_region = self.cur_point();
+        if self.is_degenerate() {
+            return changed_region;
+        }
+
+        self.cleared = false;
         let line = &mut self.model[self.cur_row];
         line.dirty_line = true;
 
@@ -121,10 +252,25 @@ impl UiModel {
         self.right = right as usize;
     }
 
+    pub fn scroll_region(&self) -> ModelRect {
+        ModelRect::new(self.top, self.bot, self.left, self.right)
+    }
+
+    /// Rows changed since last glyph shaping
+    pub fn dirty_rows(&self, top: usize, bot: usize) -> Vec<usize> {
+        (top..bot + 1)
+            .filter(|&row| self.model[row].dirty_line)
+            .collect()
+    }
+
     /// Copy rows from 0 to to_row, col from 0 self.columns
     ///
     /// Don't do any validation!
     pub fn swap_rows(&mut self, target: &mut UiModel, to_row: usize) {
+        target.cleared = self.cleared;
+        if self.is_degenerate() {
+            return;
+        }
         for (row_idx, line) in self.model[0..to_row + 1].iter_mut().enumerate() {
             let mut target_row = &mut target.model[row_idx];
             line.swap_with(target_row, 0, self.columns - 1);
@@ -154,6 +300,9 @@ impl UiModel {
 
     pub fn scroll(&mut self, count: i64) -> ModelRect {
         let (top, bot, left, right) = (self.top as i64, self.bot as i64, self.left, self.right);
+        if self.is_degenerate() || bot >= self.rows as i64 {
+            return ModelRect::new(top as usize, bot as usize, left, right);
+        }
 
         if count > 0 {
             for row in top..(bot - count + 1) {
@@ -176,11 +325,18 @@ impl UiModel {
 
     pub fn clear(&mut self) {
         let (rows, columns) = (self.rows, self.columns);
+        self.cleared = true;
+        if self.is_degenerate() {
+            return;
+        }
         self.clear_region(0, rows - 1, 0, columns - 1);
     }
 
     pub fn eol_clear(&mut self) -> ModelRect {
         let (cur_row, cur_col, columns) = (self.cur_row, self.cur_col, self.columns);
+        if self.is_degenerate() {
+            return self.cur_point();
+        }
         self.clear_region(cur_row, cur_row, cur_col, columns - 1);
 
         ModelRect::new(cur_row, cur_row, cur_col, columns - 1)
@@ -203,6 +359,147 @@ impl UiModel {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_cleared() {
+        let mut model = UiModel::new(10, 20);
+        assert!(model.is_cleared());
+
+        model.put("a", false, None);
+        assert!(!model.is_cleared());
+
+        model.eol_clear();
+        assert!(!model.is_cleared());
+
+        model.clear();
+        assert!(model.is_cleared());
+    }
+
+    #[test]
+    fn test_zero_size() {
+        let mut model = UiModel::new(0, 0);
+        model.put("a", false, None);
+        model.eol_clear();
+        model.scroll(1);
+        model.clear();
+        assert!(model.is_cleared());
+
+        model.resize(2, 3);
+        put_str(&mut model, 1, "abc");
+        assert_eq!("\nabc", model.rows_text(0, 1));
+    }
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut model = UiModel::new(3, 4);
+        for row in 0..3 {
+            model.model[row].dirty_line = false;
+        }
+
+        model.set_cursor(1, 0);
+        model.put("a", false, None);
+        assert_eq!(vec![1], model.dirty_rows(0, 2));
+        assert!(model.dirty_rows(2, 2).is_empty());
+    }
+
+    #[test]
+    fn test_rows_text() {
+        let mut model = UiModel::new(3, 4);
+        model.set_cursor(0, 0);
+        model.put("a", false, None);
+        model.put("b", false, None);
+        model.set_cursor(1, 1);
+        model.put("c", false, None);
+
+        assert_eq!("ab\n c", model.rows_text(0, 1));
+        assert_eq!("", model.rows_text(2, 2));
+    }
+
+    fn put_str(model: &mut UiModel, row: usize, text: &str) {
+        model.set_cursor(row, 0);
+        for ch in text.chars() {
+            model.put(&ch.to_string(), false, None);
+        }
+    }
+
+    #[test]
+    fn test_resize_grow() {
+        let mut model = UiModel::new(2, 3);
+        put_str(&mut model, 0, "abc");
+        put_str(&mut model, 1, "de");
+
+        model.resize(3, 5);
+        assert_eq!(3, model.rows);
+        assert_eq!(5, model.columns);
+        assert_eq!("abc\nde\n", model.rows_text(0, 2));
+        assert!(model.model()[2].line.iter().all(|c| c.ch.is_empty()));
+    }
+
+    #[test]
+    fn test_resize_shrink() {
+        let mut model = UiModel::new(3, 4);
+        put_str(&mut model, 0, "abcd");
+        put_str(&mut model, 2, "efgh");
+
+        model.resize(2, 2);
+        assert_eq!("ab\n", model.rows_text(0, 1));
+        assert_eq!((1, 1), model.get_cursor());
+    }
+
+    #[test]
+    fn test_resize_grow_shrink() {
+        let mut model = UiModel::new(2, 2);
+        put_str(&mut model, 0, "ab");
+
+        model.resize(4, 4);
+        put_str(&mut model, 3, "cdef");
+        model.resize(2, 3);
+        model.resize(4, 4);
+
+        assert_eq!("ab\n\n\n", model.rows_text(0, 3));
+    }
+
+    #[test]
+    fn test_url_at() {
+        let mut model = UiModel::new(1, 20);
+        model.set_cursor(0, 0);
+        for ch in "see http://a.b/c x".chars() {
+            model.put(&ch.to_string(), false, None);
+        }
+
+        assert_eq!(None, model.url_at(0, 2));
+        assert_eq!(Some("http://a.b/c".to_owned()), model.url_at(0, 4));
+        assert_eq!(Some("http://a.b/c".to_owned()), model.url_at(0, 15));
+        assert_eq!(None, model.url_at(0, 16));
+    }
+
+    #[test]
+    fn test_path_at() {
+        let mut model = UiModel::new(1, 30);
+        model.set_cursor(0, 0);
+        for ch in "see (src/main.rs), ok".chars() {
+            model.put(&ch.to_string(), false, None);
+        }
+
+        assert_eq!(Some("see".to_owned()), model.path_at(0, 1));
+        assert_eq!(Some("src/main.rs".to_owned()), model.path_at(0, 6));
+        assert_eq!(None, model.path_at(0, 3));
+        assert_eq!(None, model.path_at(0, 16));
+    }
+
+    #[test]
+    fn test_url_at_double_width() {
+        let mut model = UiModel::new(1, 20);
+        model.set_cursor(0, 0);
+        for ch in "x http://a/".chars() {
+            model.put(&ch.to_string(), false, None);
+        }
+        model.put("\u{4e2d}", false, None);
+        model.put("", true, None);
+
+        assert_eq!(Some("http://a/\u{4e2d}".to_owned()), model.url_at(0, 11));
+        assert_eq!(Some("http://a/\u{4e2d}".to_owned()), model.url_at(0, 12));
+    }
+
     #[test]
     fn test_vec_join_inside() {
         let mut list = ModelRectVec::new(ModelRect::new(0, 23, 0, 69));
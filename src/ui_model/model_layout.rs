@@ -1,4 +1,4 @@
-use std::cmp::max;
+use std::cmp::{max, min};
 
 use unicode_width::UnicodeWidthStr;
 
@@ -8,6 +8,7 @@ pub struct ModelLayout {
     pub model: UiModel,
     rows_filled: usize,
     cols_filled: usize,
+    tabstop: usize,
     lines: Vec<Vec<(Option<Attrs>, Vec<String>)>>,
 }
 
@@ -19,10 +20,15 @@ impl ModelLayout {
             model: UiModel::new(ModelLayout::ROWS_STEP as u64, columns),
             rows_filled: 0,
             cols_filled: 0,
+            tabstop: 8,
             lines: Vec::new(),
         }
     }
 
+    pub fn set_tabstop(&mut self, tabstop: usize) {
+        self.tabstop = max(1, tabstop);
+    }
+
     pub fn layout_append(&mut self, mut lines: Vec<Vec<(Option<Attrs>, Vec<String>)>>) {
         let rows_filled = self.rows_filled;
         let take_from = self.lines.len();
@@ -105,7 +111,8 @@ impl ModelLayout {
     ///
     /// returns actual width
     fn layout_replace(&mut self, row_offset: usize, take_from: usize) {
-        let rows = ModelLayout::count_lines(&self.lines[take_from..], self.model.columns);
+        let rows =
+            ModelLayout::count_lines(&self.lines[take_from..], self.model.columns, self.tabstop);
 
         self.check_model_size(rows + row_offset);
         self.rows_filled = rows + row_offset;
@@ -118,17 +125,25 @@ impl ModelLayout {
         for content in lines {
             for &(ref attr, ref ch_list) in content {
                 for ch in ch_list {
-                    let ch_width = max(1, ch.width());
+                    let mut ch_width = cell_width(ch, col_idx, self.model.columns, self.tabstop);
 
                     if col_idx + ch_width > self.model.columns {
                         col_idx = 0;
                         row_idx += 1;
+                        ch_width = cell_width(ch, col_idx, self.model.columns, self.tabstop);
                     }
 
                     self.model.set_cursor(row_idx, col_idx as usize);
-                    self.model.put(ch, false, attr.as_ref());
-                    if ch_width > 1 {
-                        self.model.put("", true, attr.as_ref());
+                    if ch == "\t" {
+                        // tab is rendered as blank cells up to next tab stop
+                        for _ in 0..ch_width {
+                            self.model.put(" ", false, attr.as_ref());
+                        }
+                    } else {
+                        self.model.put(ch, false, attr.as_ref());
+                        if ch_width > 1 {
+                            self.model.put("", true, attr.as_ref());
+                        }
                     }
 
                     if max_col_idx < col_idx {
@@ -152,18 +167,43 @@ impl ModelLayout {
         }
     }
 
-    fn count_lines(lines: &[Vec<(Option<Attrs>, Vec<String>)>], max_columns: usize) -> usize {
+    fn count_lines(
+        lines: &[Vec<(Option<Attrs>, Vec<String>)>],
+        max_columns: usize,
+        tabstop: usize,
+    ) -> usize {
         let mut row_count = 0;
 
         for line in lines {
-            let len: usize = line.iter().map(|c| c.1.len()).sum();
-            row_count += len / (max_columns + 1) + 1;
+            let mut col_idx = 0;
+            row_count += 1;
+
+            for ch in line.iter().flat_map(|&(_, ref ch_list)| ch_list) {
+                let mut ch_width = cell_width(ch, col_idx, max_columns, tabstop);
+                if col_idx + ch_width > max_columns {
+                    col_idx = 0;
+                    row_count += 1;
+                    ch_width = cell_width(ch, col_idx, max_columns, tabstop);
+                }
+                col_idx += ch_width;
+            }
         }
 
         row_count
     }
 }
 
+/// Cells taken by character at given column, tab is extended
+/// to next tab stop but not after line end
+fn cell_width(ch: &str, col_idx: usize, columns: usize, tabstop: usize) -> usize {
+    if ch == "\t" {
+        let width = tabstop - col_idx % tabstop;
+        max(1, min(width, columns.saturating_sub(col_idx)))
+    } else {
+        max(1, ch.width())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,10 +212,34 @@ mod tests {
     fn test_count_lines() {
         let lines = vec![vec![(None, vec!["a".to_owned(); 5])]];
 
-        let rows = ModelLayout::count_lines(&lines, 4);
+        let rows = ModelLayout::count_lines(&lines, 4, 8);
         assert_eq!(2, rows);
     }
 
+    #[test]
+    fn test_tab_width() {
+        assert_eq!(8, cell_width("\t", 0, 80, 8));
+        assert_eq!(5, cell_width("\t", 3, 80, 8));
+        assert_eq!(2, cell_width("\t", 78, 80, 8));
+        assert_eq!(1, cell_width("a", 3, 80, 8));
+    }
+
+    #[test]
+    fn test_tab_layout() {
+        let lines = vec![vec![(
+            None,
+            vec!["a".to_owned(), "\t".to_owned(), "b".to_owned()],
+        )]];
+        let mut model = ModelLayout::new(10);
+        model.set_tabstop(4);
+
+        model.layout(lines);
+        let (cols, rows) = model.size();
+        assert_eq!(6, cols); // with cursor position
+        assert_eq!(1, rows);
+        assert_eq!("a   b", model.model.rows_text(0, 0));
+    }
+
     #[test]
     fn test_resize() {
         let lines = vec![vec![(None, vec!["a".to_owned(); 5])]; ModelLayout::ROWS_STEP];
@@ -0,0 +1,98 @@
+use gtk;
+use neovim_lib::Value;
+
+use nvim;
+
+/// Commit of build, set by build script
+const GIT_COMMIT: Option<&str> = option_env!("NVIM_GTK_GIT_COMMIT");
+
+/// Build and runtime versions, shown in about dialog
+/// and returned by `Gui.Version` request
+pub struct VersionInfo {
+    nvim_version: Option<String>,
+    features: Vec<String>,
+}
+
+impl VersionInfo {
+    pub fn new(nvim_version: Option<(u32, u32, u32)>, dropped_ui_options: &[String]) -> Self {
+        VersionInfo {
+            nvim_version: nvim_version
+                .map(|(major, minor, patch)| format!("{}.{}.{}", major, minor, patch)),
+            features: features()
+                .into_iter()
+                .filter(|f| !dropped_ui_options.contains(f))
+                .collect(),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let opt_str = |s: Option<&str>| s.map_or(Value::Nil, Value::from);
+
+        Value::Map(vec![
+            ("name".into(), "nvim-gtk".into()),
+            ("version".into(), env!("CARGO_PKG_VERSION").into()),
+            ("commit".into(), opt_str(GIT_COMMIT)),
+            ("gtk".into(), gtk_version().into()),
+            (
+                "nvim".into(),
+                opt_str(self.nvim_version.as_ref().map(String::as_str)),
+            ),
+            (
+                "features".into(),
+                Value::Array(self.features.iter().map(|f| f.as_str().into()).collect()),
+            ),
+        ])
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "Commit: {}\nGTK: {}\nNeovim: {}\nFeatures: {}",
+            GIT_COMMIT.unwrap_or("unknown"),
+            gtk_version(),
+            self.nvim_version
+                .as_ref()
+                .map_or("not connected", String::as_str),
+            self.features.join(", "),
+        )
+    }
+}
+
+fn gtk_version() -> String {
+    format!(
+        "{}.{}.{}",
+        gtk::get_major_version(),
+        gtk::get_minor_version(),
+        gtk::get_micro_version()
+    )
+}
+
+/// Ligatures are always built in, ext_ options are negotiated on attach
+fn features() -> Vec<String> {
+    let mut features = vec!["ligatures".to_owned()];
+
+    if let Value::Map(options) = nvim::ui_attach_options() {
+        features.extend(
+            options
+                .iter()
+                .filter(|&&(_, ref v)| v.as_bool() == Some(true))
+                .filter_map(|&(ref k, _)| k.as_str())
+                .filter(|k| k.starts_with("ext_"))
+                .map(str::to_owned),
+        );
+    }
+
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let features = features();
+        assert!(features.contains(&"ligatures".to_owned()));
+        assert!(features.contains(&"ext_tabline".to_owned()));
+        assert!(!features.contains(&"rgb".to_owned()));
+    }
+}